@@ -15,42 +15,204 @@ extern crate syn;
 extern crate quote;
 
 use proc_macro::TokenStream;
-use syn::{Data, DeriveInput, Fields, WhereClause, WherePredicate};
+use syn::{
+    Attribute, Data, DataEnum, DeriveInput, Expr, Field, Fields, Lit, Meta, NestedMeta, Path, Type,
+    UnOp, WhereClause, WherePredicate,
+};
 
-#[proc_macro_derive(Zero)]
+// Returns whether the attributes contain `#[list(word)]`.
+fn has_attr(attrs: &[Attribute], list: &str, word: &str) -> bool {
+    attrs.iter().filter_map(Attribute::interpret_meta).any(|meta| match meta {
+        Meta::List(ref l) if l.ident == list => l.nested.iter().any(|n| match *n {
+            NestedMeta::Meta(Meta::Word(ref w)) => w == word,
+            _ => false,
+        }),
+        _ => false,
+    })
+}
+
+// Returns whether the attributes contain `#[word]`.
+fn has_word_attr(attrs: &[Attribute], word: &str) -> bool {
+    attrs.iter().filter_map(Attribute::interpret_meta).any(|meta| match meta {
+        Meta::Word(ref w) => w == word,
+        _ => false,
+    })
+}
+
+// Returns the type of the field, unless it is marked
+// `#[zero(unsafe_assume)]`, in which case the user asserts that it is valid
+// when zeroed, and it is not checked.
+fn checked_type(f: &Field) -> Option<&Type> {
+    if has_attr(&f.attrs, "zero", "unsafe_assume") {
+        None
+    } else {
+        Some(&f.ty)
+    }
+}
+
+// Returns whether the attributes contain a `#[repr(...)]` giving an enum a
+// defined layout, where the discriminant comes first.
+fn has_enum_repr(attrs: &[Attribute]) -> bool {
+    [
+        "C", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128",
+        "isize",
+    ]
+    .iter()
+    .any(|repr| has_attr(attrs, "repr", repr))
+}
+
+// Returns the value of a discriminant given as an integer literal, possibly
+// negated, or `None` for any other expression.
+fn literal_discriminant(expr: &Expr) -> Option<i128> {
+    match *expr {
+        Expr::Lit(ref e) => match e.lit {
+            Lit::Int(ref i) => Some(i128::from(i.value())),
+            _ => None,
+        },
+        Expr::Unary(ref e) => match e.op {
+            UnOp::Neg(_) => literal_discriminant(&e.expr).map(|v| -v),
+            _ => None,
+        },
+        Expr::Paren(ref e) => literal_discriminant(&e.expr),
+        _ => None,
+    }
+}
+
+// Returns the field types to check for the variant with a zero
+// discriminant.
+fn zero_variant_types(data: &DataEnum) -> Vec<&Type> {
+    // The discriminant of the next variant, unless it is unknown because a
+    // previous one was given as an expression other than a literal.
+    let mut next = Some(0);
+    for variant in &data.variants {
+        let discriminant = match variant.discriminant {
+            Some((_, ref expr)) => literal_discriminant(expr),
+            None => next,
+        };
+        if discriminant == Some(0) {
+            return variant.fields.iter().filter_map(checked_type).collect();
+        }
+        next = discriminant.map(|d| d + 1);
+    }
+    panic!(
+        "derive(Zero) requires a variant with a zero discriminant, \
+         given explicitly as an integer literal, or implicitly"
+    );
+}
+
+// Returns the path to the `boxext` crate, which is `::boxext` unless
+// overridden with `#[zero(crate = "path")]`, e.g. when the dependency is
+// renamed, or re-exported by another crate.
+fn crate_path(attrs: &[Attribute]) -> Path {
+    for meta in attrs.iter().filter_map(Attribute::interpret_meta) {
+        if let Meta::List(ref l) = meta {
+            if l.ident != "zero" {
+                continue;
+            }
+            for n in &l.nested {
+                if let NestedMeta::Meta(Meta::NameValue(ref nv)) = *n {
+                    if nv.ident == "crate" {
+                        match nv.lit {
+                            Lit::Str(ref s) => return s.parse().expect("invalid crate path"),
+                            _ => panic!("#[zero(crate = ...)] expects a string"),
+                        }
+                    }
+                }
+            }
+        }
+    }
+    parse_quote! { ::boxext }
+}
+
+#[proc_macro_derive(Zero, attributes(zero))]
 pub fn derive_zero(input: TokenStream) -> TokenStream {
     let input: DeriveInput = syn::parse(input).unwrap();
 
     let name = input.ident;
 
+    // With `#[zero(unsafe_transparent)]`, the user asserts that the type
+    // wrapped in a `#[repr(transparent)]` struct is valid when zeroed, even
+    // though it doesn't implement `Zero`.
+    let transparent = has_attr(&input.attrs, "zero", "unsafe_transparent");
+    if transparent && !has_attr(&input.attrs, "repr", "transparent") {
+        panic!("#[zero(unsafe_transparent)] requires #[repr(transparent)]");
+    }
+
+    // The generated code only refers to items through this path, so that it
+    // works in `no_std` crates and from within macros.
+    let krate = crate_path(&input.attrs);
+
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
     let mut types = vec![];
+    // Assertions that a union field covers the full size of the union.
+    let mut size_checks = vec![];
 
     match input.data {
         Data::Struct(ref data) => match data.fields {
-            Fields::Named(ref fields) => for f in &fields.named {
-                types.push(&f.ty);
-            },
-            Fields::Unnamed(ref fields) => for f in &fields.unnamed {
-                types.push(&f.ty);
-            },
+            Fields::Named(ref fields) => types.extend(fields.named.iter().filter_map(checked_type)),
+            Fields::Unnamed(ref fields) => {
+                types.extend(fields.unnamed.iter().filter_map(checked_type))
+            }
             Fields::Unit => {}
         },
-        _ => panic!("Can only derive(Zero) for structs"),
+        // With a defined layout, zeroed memory holds the variant with a zero
+        // discriminant, with zeroed fields.
+        Data::Enum(ref data) => {
+            if transparent {
+                panic!("#[zero(unsafe_transparent)] can only be used on structs");
+            }
+            if !has_enum_repr(&input.attrs) {
+                panic!("derive(Zero) requires enums to be #[repr(C)] or #[repr(<integer>)]");
+            }
+            types = zero_variant_types(data);
+        }
+        // Zeroed memory holds a zeroed value of the first field, or of the
+        // field marked `#[zero]`, which must be as large as the union.
+        Data::Union(ref data) => {
+            if transparent {
+                panic!("#[zero(unsafe_transparent)] can only be used on structs");
+            }
+            if !has_attr(&input.attrs, "repr", "C") {
+                panic!("derive(Zero) requires unions to be #[repr(C)]");
+            }
+            if !input.generics.params.is_empty() {
+                panic!("derive(Zero) doesn't support generic unions");
+            }
+            let mut marked = data
+                .fields
+                .named
+                .iter()
+                .filter(|f| has_word_attr(&f.attrs, "zero"));
+            let field = match (marked.next(), marked.next()) {
+                (Some(f), None) => f,
+                (None, _) => data.fields.named.iter().next().unwrap(),
+                (Some(_), Some(_)) => panic!("Only one union field can be marked #[zero]"),
+            };
+            let ty = &field.ty;
+            size_checks.push(quote! {
+                const _: [(); #krate::__size_of::<#name>()] = [(); #krate::__size_of::<#ty>()];
+            });
+            types.extend(checked_type(field));
+        }
     }
 
     let mut where_clause = where_clause.cloned().unwrap_or_else(|| WhereClause {
         where_token: Default::default(),
         predicates: Default::default(),
     });
-    for t in types {
-        let p: WherePredicate = parse_quote! { #t: ::boxext::Zero };
-        where_clause.predicates.push(p);
+    // Bound each field type rather than each generic parameter, so that
+    // e.g. a `*mut T` field doesn't require `T: Zero`.
+    if !transparent {
+        for t in types {
+            let p: WherePredicate = parse_quote! { #t: #krate::Zero };
+            where_clause.predicates.push(p);
+        }
     }
 
     let expanded = quote! {
-        unsafe impl #impl_generics ::boxext::Zero for #name #ty_generics #where_clause {}
+        unsafe impl #impl_generics #krate::Zero for #name #ty_generics #where_clause {}
+        #(#size_checks)*
     };
 
     expanded.into()
@@ -0,0 +1,41 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::env;
+use std::process::Command;
+
+// Returns the minor version of the compiler, e.g. 63 for Rust 1.63.0.
+fn rustc_minor_version() -> Option<u32> {
+    let rustc = env::var_os("RUSTC")?;
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    let version = String::from_utf8(output.stdout).ok()?;
+    // e.g. "rustc 1.63.0 (4b91a6ea7 2022-08-08)"
+    let mut parts = version.split(' ').nth(1)?.split('.');
+    if parts.next()? != "1" {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rustc-check-cfg=cfg(boxext_std_new_uninit)");
+    println!("cargo:rustc-check-cfg=cfg(boxext_std_new_zeroed)");
+    let minor = match rustc_minor_version() {
+        Some(minor) => minor,
+        None => return,
+    };
+    // `Box::new_uninit`, stable since Rust 1.82.
+    if minor >= 82 {
+        println!("cargo:rustc-cfg=boxext_std_new_uninit");
+    }
+    // `Box::new_zeroed`, stable since Rust 1.92.
+    if minor >= 92 {
+        println!("cargo:rustc-cfg=boxext_std_new_zeroed");
+    }
+}
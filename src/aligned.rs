@@ -0,0 +1,329 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::ops::{Deref, DerefMut};
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::{fmt, ptr, slice};
+use heap::{dealloc_layout, try_alloc_layout};
+#[cfg(feature = "os_alloc")]
+use os_box::release;
+use std::alloc::Layout;
+use {AllocOptions, Zero};
+
+/// A zeroed, heap allocated, byte buffer with an alignment chosen at runtime.
+///
+/// This dereferences to `[u8]`. Contrary to a `Box<[u8]>`, it remembers the
+/// alignment it was allocated with, which is necessary to deallocate it
+/// properly.
+///
+/// See [`aligned_zeroed_bytes`].
+///
+/// [`aligned_zeroed_bytes`]: fn.aligned_zeroed_bytes.html
+pub struct AlignedBytes {
+    ptr: *mut u8,
+    layout: Layout,
+}
+
+/// Allocates `len` zeroed bytes aligned to `align` on the heap.
+///
+/// This doesn't actually allocate if `len` is 0.
+///
+/// # Panics
+///
+/// Panics if `align` is not a power of two, or if `len`, rounded up to a
+/// multiple of `align`, overflows. Calls [`handle_alloc_error`] if memory
+/// couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::aligned_zeroed_bytes;
+///
+/// fn main() {
+///     let sector_size = 4096;
+///     let buf = aligned_zeroed_bytes(3 * sector_size, sector_size);
+///     assert_eq!(buf.len(), 3 * sector_size);
+///     assert_eq!(buf.as_ptr() as usize % sector_size, 0);
+///     assert!(buf.iter().all(|&b| b == 0));
+/// }
+/// ```
+#[track_caller]
+pub fn aligned_zeroed_bytes(len: usize, align: usize) -> AlignedBytes {
+    let layout = Layout::from_size_align(len, align).expect("invalid alignment or size");
+    let ptr = match unsafe { try_alloc_layout(layout, true) } {
+        Ok(ptr) => ptr,
+        Err(e) => e.handle(),
+    };
+    AlignedBytes { ptr, layout }
+}
+
+/// Fallible [`aligned_zeroed_bytes`]
+///
+/// [`aligned_zeroed_bytes`]: fn.aligned_zeroed_bytes.html
+///
+/// This returns `None` if memory couldn't be allocated, if `align` is not a
+/// power of two, or if `len`, rounded up to a multiple of `align`, overflows.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::try_aligned_zeroed_bytes;
+///
+/// fn main() {
+///     let buf = try_aligned_zeroed_bytes(100, 64).unwrap();
+///     assert_eq!(buf.align(), 64);
+///     assert!(try_aligned_zeroed_bytes(100, 48).is_none());
+/// }
+/// ```
+pub fn try_aligned_zeroed_bytes(len: usize, align: usize) -> Option<AlignedBytes> {
+    let layout = Layout::from_size_align(len, align).ok()?;
+    let ptr = unsafe { try_alloc_layout(layout, true).ok()? };
+    Some(AlignedBytes { ptr, layout })
+}
+
+impl AlignedBytes {
+    /// Returns the alignment of the buffer.
+    #[inline]
+    pub fn align(&self) -> usize {
+        self.layout.align()
+    }
+}
+
+impl Deref for AlignedBytes {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.layout.size()) }
+    }
+}
+
+impl DerefMut for AlignedBytes {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedBytes {
+    fn drop(&mut self) {
+        unsafe { dealloc_layout(self.ptr, self.layout) }
+    }
+}
+
+unsafe impl Send for AlignedBytes {}
+
+unsafe impl Sync for AlignedBytes {}
+
+impl fmt::Debug for AlignedBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AlignedBytes")
+            .field("len", &self.layout.size())
+            .field("align", &self.layout.align())
+            .finish()
+    }
+}
+
+/// A heap allocated value with an alignment possibly larger than its type's.
+///
+/// This dereferences to `T`. Contrary to a `Box<T>`, it remembers the
+/// alignment it was allocated with, which is necessary to deallocate it
+/// properly.
+///
+/// See [`AlignedBox::new_zeroed_aligned`] and [`AllocOptions`].
+///
+/// [`AlignedBox::new_zeroed_aligned`]: #method.new_zeroed_aligned
+/// [`AllocOptions`]: struct.AllocOptions.html
+pub struct AlignedBox<T> {
+    ptr: *mut T,
+    layout: Layout,
+    // Size of the mapping the memory comes from, when it was obtained
+    // directly from the OS, or 0 if it comes from the allocator.
+    map_size: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T> AlignedBox<T> {
+    // Takes ownership of memory allocated with the given layout, or mapped
+    // with the given size.
+    pub(crate) unsafe fn from_raw_parts(ptr: *mut T, layout: Layout, map_size: usize) -> Self {
+        AlignedBox {
+            ptr,
+            layout,
+            map_size,
+            marker: PhantomData,
+        }
+    }
+
+    /// Allocates memory aligned to at least `align` bytes, and moves `value`
+    /// into it.
+    ///
+    /// The alignment of `T` is used instead if it is larger.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two. Calls [`handle_alloc_error`]
+    /// if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[inline]
+    #[track_caller]
+    pub fn new_aligned(value: T, align: usize) -> Self {
+        AllocOptions::new().align(align).alloc().write(value)
+    }
+
+    /// Fallible [`AlignedBox::new_aligned`]
+    ///
+    /// [`AlignedBox::new_aligned`]: #method.new_aligned
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if `align`
+    /// is not a power of two.
+    #[inline]
+    pub fn try_new_aligned(value: T, align: usize) -> Option<Self> {
+        Some(AllocOptions::new().align(align).try_alloc()?.write(value))
+    }
+
+    /// Allocates zeroed memory aligned to at least `align` bytes.
+    ///
+    /// The alignment of `T` is used instead if it is larger.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::AlignedBox;
+    ///
+    /// fn main() {
+    ///     // A DMA descriptor ring, aligned to a page boundary.
+    ///     let ring: AlignedBox<[u64; 512]> = AlignedBox::new_zeroed_aligned(4096);
+    ///     assert_eq!(ring.align(), 4096);
+    ///     assert_eq!(ring.as_ptr() as usize % 4096, 0);
+    ///     assert!(ring.iter().all(|&d| d == 0));
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two. Calls [`handle_alloc_error`]
+    /// if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[inline]
+    #[track_caller]
+    pub fn new_zeroed_aligned(align: usize) -> Self
+    where
+        T: Zero,
+    {
+        unsafe { AllocOptions::new().zeroed().align(align).alloc().assume_init() }
+    }
+
+    /// Fallible [`AlignedBox::new_zeroed_aligned`]
+    ///
+    /// [`AlignedBox::new_zeroed_aligned`]: #method.new_zeroed_aligned
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if `align`
+    /// is not a power of two.
+    #[inline]
+    pub fn try_new_zeroed_aligned(align: usize) -> Option<Self>
+    where
+        T: Zero,
+    {
+        unsafe { Some(AllocOptions::new().zeroed().align(align).try_alloc()?.assume_init()) }
+    }
+
+    /// Returns the alignment of the allocation.
+    #[inline]
+    pub fn align(&self) -> usize {
+        self.layout.align()
+    }
+
+    /// Returns whether the memory was obtained directly from the operating
+    /// system, rather than from the global allocator. See
+    /// [`AllocOptions::huge_pages`] and [`AllocOptions::on_node`].
+    ///
+    /// [`AllocOptions::huge_pages`]: struct.AllocOptions.html#method.huge_pages
+    /// [`AllocOptions::on_node`]: struct.AllocOptions.html#method.on_node
+    #[cfg(feature = "os_alloc")]
+    #[inline]
+    pub fn is_mapped(&self) -> bool {
+        self.map_size != 0
+    }
+}
+
+impl<T> AlignedBox<MaybeUninit<T>> {
+    /// Converts to `AlignedBox<T>`.
+    ///
+    /// # Safety
+    ///
+    /// As with `MaybeUninit::assume_init`, it is up to the caller to
+    /// guarantee that the value really is in an initialized state.
+    pub unsafe fn assume_init(self) -> AlignedBox<T> {
+        let result = AlignedBox::from_raw_parts(self.ptr as *mut T, self.layout, self.map_size);
+        mem::forget(self);
+        result
+    }
+
+    /// Writes the given value and converts to `AlignedBox<T>`.
+    pub fn write(mut self, value: T) -> AlignedBox<T> {
+        unsafe {
+            ptr::write(self.as_mut_ptr(), value);
+            self.assume_init()
+        }
+    }
+}
+
+impl<T> Deref for AlignedBox<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<T> DerefMut for AlignedBox<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.ptr }
+    }
+}
+
+impl<T> Drop for AlignedBox<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.ptr);
+            #[cfg(feature = "os_alloc")]
+            release(self.ptr as *mut u8, self.layout, self.map_size);
+            #[cfg(not(feature = "os_alloc"))]
+            dealloc_layout(self.ptr as *mut u8, self.layout);
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for AlignedBox<T> {}
+
+unsafe impl<T: Sync> Sync for AlignedBox<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for AlignedBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,305 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::boxed::Box;
+use core::alloc::Layout;
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::panic::Location;
+use core::ptr::{self, NonNull};
+use handler::alloc_error;
+use {cold, BoxExt, Zero};
+
+/// Extensions to the `allocator_api2::boxed::Box` type
+///
+/// Note that `allocator_api2::boxed::Box` has inherent `new_zeroed_in` and
+/// `try_new_zeroed_in` methods returning a `Box<MaybeUninit<T>, A>`, which
+/// take precedence over the ones from this trait when called with the
+/// `Box::` prefix.
+pub trait BoxAllocatorExt<A: Allocator>: Sized {
+    /// Type contained inside the `Box`.
+    type Inner;
+
+    /// Allocates memory in the given allocator and then places the result of
+    /// `f` into it.
+    ///
+    /// When building with optimization enabled, this is expected to avoid
+    /// copies, contrary to `Box::new_in`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate allocator_api2;
+    /// extern crate boxext;
+    /// use allocator_api2::alloc::Global;
+    /// use allocator_api2::boxed::Box;
+    /// use boxext::BoxAllocatorExt;
+    ///
+    /// fn main() {
+    ///     let buf = Box::new_with_in(|| [1u32; 64], Global);
+    ///     assert_eq!(buf[63], 1);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_with_in<F: FnOnce() -> Self::Inner>(f: F, a: A) -> Self;
+
+    /// Allocates zeroed memory in the given allocator, with
+    /// `Allocator::allocate_zeroed`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate allocator_api2;
+    /// extern crate boxext;
+    /// use allocator_api2::alloc::Global;
+    /// use allocator_api2::boxed::Box;
+    /// use boxext::BoxAllocatorExt;
+    ///
+    /// fn main() {
+    ///     let buf: Box<[usize; 32], _> = BoxAllocatorExt::new_zeroed_in(Global);
+    ///     assert_eq!(*buf, [0usize; 32]);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for `Self::Inner` types implementing
+    /// the [`Zero`] trait, and not available otherwise. See the definition
+    /// of that trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_zeroed_in(a: A) -> Self
+    where
+        Self::Inner: Zero;
+
+    /// Fallible [`BoxAllocatorExt::new_with_in`]
+    ///
+    /// [`BoxAllocatorExt::new_with_in`]: #tymethod.new_with_in
+    ///
+    /// This returns `None` if memory couldn't be allocated, in which case
+    /// `f` is not called.
+    fn try_new_with_in<F: FnOnce() -> Self::Inner>(f: F, a: A) -> Option<Self>;
+
+    /// Fallible [`BoxAllocatorExt::new_zeroed_in`]
+    ///
+    /// [`BoxAllocatorExt::new_zeroed_in`]: #tymethod.new_zeroed_in
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    fn try_new_zeroed_in(a: A) -> Option<Self>
+    where
+        Self::Inner: Zero;
+}
+
+// Memory allocated in the given allocator for a `T` that is not initialized
+// yet. A `Box` is only created once the value is in place, and the memory is
+// deallocated if the guard is dropped before that, e.g. when the function
+// computing the value panics.
+// Contrary to the other guards in this crate, zero-sized types go through
+// the allocator too, because `allocator_api2::boxed::Box` deallocates them.
+struct UninitBoxIn<T, A: Allocator> {
+    ptr: NonNull<T>,
+    a: ManuallyDrop<A>,
+}
+
+impl<T, A: Allocator> UninitBoxIn<T, A> {
+    // Allocates memory for a `T` in the given allocator, zeroed if `zeroed`
+    // is true. If the memory couldn't be allocated, returns Err(layout).
+    #[inline]
+    fn try_new(a: A, zeroed: bool) -> Result<Self, Layout> {
+        let layout = Layout::new::<T>();
+        let raw = if zeroed {
+            a.allocate_zeroed(layout)
+        } else {
+            a.allocate(layout)
+        };
+        match raw {
+            Ok(ptr) => Ok(UninitBoxIn {
+                ptr: ptr.cast(),
+                a: ManuallyDrop::new(a),
+            }),
+            Err(_) => {
+                cold();
+                Err(layout)
+            }
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn new(a: A, zeroed: bool) -> Self {
+        match UninitBoxIn::try_new(a, zeroed) {
+            Ok(b) => b,
+            Err(l) => alloc_error(l, Location::caller()),
+        }
+    }
+
+    // Writes the value and turns the memory into a `Box`.
+    #[inline]
+    fn init(self, value: T) -> Box<T, A> {
+        unsafe {
+            ptr::write(self.ptr.as_ptr(), value);
+            self.assume_init()
+        }
+    }
+
+    // Turns the memory into a `Box`. The memory must hold a valid `T`.
+    #[inline]
+    unsafe fn assume_init(mut self) -> Box<T, A> {
+        let raw = self.ptr.as_ptr();
+        let a = ManuallyDrop::take(&mut self.a);
+        mem::forget(self);
+        Box::from_raw_in(raw, a)
+    }
+}
+
+impl<T, A: Allocator> Drop for UninitBoxIn<T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            self.a.deallocate(self.ptr.cast(), Layout::new::<T>());
+            ManuallyDrop::drop(&mut self.a);
+        }
+    }
+}
+
+impl<T, A: Allocator> BoxAllocatorExt<A> for Box<T, A> {
+    type Inner = T;
+
+    #[inline]
+    #[track_caller]
+    fn new_with_in<F: FnOnce() -> T>(f: F, a: A) -> Self {
+        UninitBoxIn::new(a, false).init(f())
+    }
+
+    #[inline]
+    #[track_caller]
+    fn new_zeroed_in(a: A) -> Self
+    where
+        T: Zero,
+    {
+        unsafe { UninitBoxIn::new(a, true).assume_init() }
+    }
+
+    #[inline]
+    fn try_new_with_in<F: FnOnce() -> T>(f: F, a: A) -> Option<Self> {
+        Some(UninitBoxIn::try_new(a, false).ok()?.init(f()))
+    }
+
+    #[inline]
+    fn try_new_zeroed_in(a: A) -> Option<Self>
+    where
+        T: Zero,
+    {
+        unsafe { Some(UninitBoxIn::try_new(a, true).ok()?.assume_init()) }
+    }
+}
+
+/// Same as for `std::boxed::Box`, using the allocator's `Default`
+/// implementation.
+///
+/// # Example
+///
+/// ```
+/// extern crate allocator_api2;
+/// extern crate boxext;
+/// use allocator_api2::boxed::Box;
+/// use boxext::BoxExt;
+///
+/// fn main() {
+///     let buf: Box<[u8; 4096]> = BoxExt::new_zeroed();
+///     assert!(buf.iter().all(|&b| b == 0));
+///
+///     let buf: Box<[u32; 64]> = Box::new_with(|| [1; 64]);
+///     assert_eq!(buf[0], 1);
+/// }
+/// ```
+impl<T, A: Allocator + Default> BoxExt for Box<T, A> {
+    type Inner = T;
+
+    #[inline]
+    #[track_caller]
+    fn new_with<F: FnOnce() -> T>(f: F) -> Self {
+        BoxAllocatorExt::new_with_in(f, A::default())
+    }
+
+    #[inline]
+    #[track_caller]
+    fn new_zeroed() -> Self
+    where
+        T: Zero,
+    {
+        BoxAllocatorExt::new_zeroed_in(A::default())
+    }
+
+    #[inline]
+    fn try_new(x: T) -> Option<Self> {
+        Some(UninitBoxIn::try_new(A::default(), false).ok()?.init(x))
+    }
+
+    #[inline]
+    fn try_new_with<F: FnOnce() -> T>(f: F) -> Option<Self> {
+        BoxAllocatorExt::try_new_with_in(f, A::default())
+    }
+
+    #[inline]
+    fn try_new_zeroed() -> Option<Self>
+    where
+        T: Zero,
+    {
+        BoxAllocatorExt::try_new_zeroed_in(A::default())
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn new_zeroed_unchecked() -> Self {
+        UninitBoxIn::new(A::default(), true).assume_init()
+    }
+
+    #[inline]
+    unsafe fn try_new_zeroed_unchecked() -> Option<Self> {
+        Some(UninitBoxIn::try_new(A::default(), true).ok()?.assume_init())
+    }
+
+    #[inline]
+    #[track_caller]
+    fn new_with_fallible<E, F: FnOnce() -> Result<T, E>>(f: F) -> Result<Self, E> {
+        let uninit = UninitBoxIn::new(A::default(), false);
+        Ok(uninit.init(f()?))
+    }
+
+    #[inline]
+    fn try_new_with_fallible<E, F: FnOnce() -> Result<T, E>>(f: F) -> Option<Result<Self, E>> {
+        let uninit = UninitBoxIn::try_new(A::default(), false).ok()?;
+        Some(f().map(|value| uninit.init(value)))
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn new_with_ptr<F: FnOnce(&mut MaybeUninit<T>)>(f: F) -> Self {
+        let uninit = UninitBoxIn::new(A::default(), false);
+        f(&mut *(uninit.ptr.as_ptr() as *mut MaybeUninit<T>));
+        uninit.assume_init()
+    }
+
+    #[inline]
+    unsafe fn try_new_with_ptr<F: FnOnce(&mut MaybeUninit<T>)>(f: F) -> Option<Self> {
+        let uninit = UninitBoxIn::try_new(A::default(), false).ok()?;
+        f(&mut *(uninit.ptr.as_ptr() as *mut MaybeUninit<T>));
+        Some(uninit.assume_init())
+    }
+}
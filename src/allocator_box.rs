@@ -6,9 +6,12 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use allocator_api::{Alloc, Box, Layout, handle_alloc_error};
+use allocator_api::{Alloc, Box, Layout};
+use core::mem::{self, ManuallyDrop, MaybeUninit};
 use core::ptr::{self, NonNull};
-use {BoxExt, Zero};
+use core::panic::Location;
+use handler::alloc_error;
+use {cold, BoxExt, IsZst, Zero};
 
 /// Extensions to the `allocator_api::Box` type
 pub trait BoxInExt<A: Alloc> {
@@ -201,23 +204,124 @@ pub trait BoxInExt<A: Alloc> {
     fn try_new_zeroed_in(a: A) -> Option<Self>
     where
         Self: Sized;
+
+    /// Same as [`Box::new_in_with`], with a name consistent with
+    /// [`Box::new_zeroed_in`].
+    ///
+    /// [`Box::new_in_with`]: #tymethod.new_in_with
+    /// [`Box::new_zeroed_in`]: #tymethod.new_zeroed_in
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate allocator_api;
+    /// extern crate boxext;
+    /// use allocator_api::Box;
+    /// use boxext::BoxInExt;
+    /// # include!("dummy.rs");
+    ///
+    /// fn main() {
+    ///     let buf = Box::new_with_in(|| [1u32; 64], MyHeap);
+    ///     assert_eq!(buf[63], 1);
+    /// }
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn new_with_in<F: FnOnce() -> Self::Inner>(f: F, a: A) -> Self
+    where
+        Self: Sized,
+    {
+        Self::new_in_with(f, a)
+    }
+
+    /// Same as [`Box::try_new_in_with`], with a name consistent with
+    /// [`Box::try_new_zeroed_in`].
+    ///
+    /// [`Box::try_new_in_with`]: #tymethod.try_new_in_with
+    /// [`Box::try_new_zeroed_in`]: #tymethod.try_new_zeroed_in
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    #[inline]
+    fn try_new_with_in<F: FnOnce() -> Self::Inner>(f: F, a: A) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Self::try_new_in_with(f, a)
+    }
 }
 
-// Creates a new box in the given allocator, for the given type.
-// If the memory could be allocated, returns Ok(box). Otherwise, returns Err(layout),
-// allowing the caller to access the layout that failed allocation.
-unsafe fn new_box_in<T, A: Alloc>(mut a: A, zeroed: bool) -> Result<Box<T, A>, Layout> {
-    let layout = Layout::new::<T>();
-    let raw = if layout.size() == 0 {
-        Ok(NonNull::<T>::dangling())
-    } else if zeroed {
-        a.alloc_zeroed(layout).map(NonNull::cast)
-    } else {
-        a.alloc(layout).map(NonNull::cast)
-    };
-    match raw {
-        Ok(raw) => Ok(Box::from_raw_in(raw.as_ptr(), a)),
-        Err(_) => Err(layout),
+// Memory allocated in the given allocator for a `T` that is not initialized
+// yet. A `Box` is only created once the value is in place, and the memory is
+// deallocated if the guard is dropped before that, e.g. when the function
+// computing the value panics.
+struct UninitBoxIn<T, A: Alloc> {
+    ptr: NonNull<T>,
+    a: ManuallyDrop<A>,
+}
+
+impl<T, A: Alloc> UninitBoxIn<T, A> {
+    // Allocates memory for a `T` in the given allocator, zeroed if `zeroed`
+    // is true. If the memory could be allocated, returns Ok(guard).
+    // Otherwise, returns Err(layout), allowing the caller to access the
+    // layout that failed allocation.
+    #[inline]
+    fn try_new(mut a: A, zeroed: bool) -> Result<Self, Layout> {
+        let layout = Layout::new::<T>();
+        let raw = if IsZst::<T>::VALUE {
+            Ok(NonNull::<T>::dangling())
+        } else if zeroed {
+            unsafe { a.alloc_zeroed(layout).map(NonNull::cast) }
+        } else {
+            unsafe { a.alloc(layout).map(NonNull::cast) }
+        };
+        match raw {
+            Ok(ptr) => Ok(UninitBoxIn {
+                ptr,
+                a: ManuallyDrop::new(a),
+            }),
+            Err(_) => {
+                cold();
+                Err(layout)
+            }
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn new(a: A, zeroed: bool) -> Self {
+        match UninitBoxIn::try_new(a, zeroed) {
+            Ok(b) => b,
+            Err(l) => alloc_error(l, Location::caller()),
+        }
+    }
+
+    // Writes the value and turns the memory into a `Box`.
+    #[inline]
+    fn init(self, value: T) -> Box<T, A> {
+        unsafe {
+            ptr::write(self.ptr.as_ptr(), value);
+            self.assume_init()
+        }
+    }
+
+    // Turns the memory into a `Box`. The memory must hold a valid `T`.
+    #[inline]
+    unsafe fn assume_init(self) -> Box<T, A> {
+        let raw = self.ptr.as_ptr();
+        let a = ManuallyDrop::into_inner(ptr::read(&self.a));
+        mem::forget(self);
+        Box::from_raw_in(raw, a)
+    }
+}
+
+impl<T, A: Alloc> Drop for UninitBoxIn<T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            if !IsZst::<T>::VALUE {
+                self.a.dealloc(self.ptr.cast(), Layout::new::<T>());
+            }
+            ManuallyDrop::drop(&mut self.a);
+        }
     }
 }
 
@@ -225,46 +329,33 @@ impl<T, A: Alloc> BoxInExt<A> for Box<T, A> {
     type Inner = T;
 
     #[inline]
+    #[track_caller]
     fn new_in_with<F: FnOnce() -> T>(f: F, a: A) -> Self {
-        unsafe {
-            let mut b = new_box_in::<T, A>(a, false).unwrap_or_else(|l| handle_alloc_error(l));
-            ptr::write(b.as_mut(), f());
-            b
-        }
+        UninitBoxIn::new(a, false).init(f())
     }
 
     #[inline]
+    #[track_caller]
     fn new_zeroed_in(a: A) -> Self
     where
         T: Zero,
     {
-        unsafe { new_box_in::<T, A>(a, true).unwrap_or_else(|l| handle_alloc_error(l)) }
+        unsafe { UninitBoxIn::new(a, true).assume_init() }
     }
 
     #[inline]
-    fn try_new_in(x: T, mut a: A) -> Option<Self> {
-        unsafe {
-            let raw = a.alloc(Layout::new::<T>()).ok()?.cast().as_ptr();
-            ptr::write(raw, x);
-            Some(Box::from_raw_in(raw, a))
-        }
+    fn try_new_in(x: T, a: A) -> Option<Self> {
+        Some(UninitBoxIn::try_new(a, false).ok()?.init(x))
     }
 
     #[inline]
-    fn try_new_in_with<F: FnOnce() -> Self::Inner>(f: F, mut a: A) -> Option<Self> {
-        unsafe {
-            let raw = a.alloc(Layout::new::<T>()).ok()?.cast().as_ptr();
-            ptr::write(raw, f());
-            Some(Box::from_raw_in(raw, a))
-        }
+    fn try_new_in_with<F: FnOnce() -> Self::Inner>(f: F, a: A) -> Option<Self> {
+        Some(UninitBoxIn::try_new(a, false).ok()?.init(f()))
     }
 
     #[inline]
-    fn try_new_zeroed_in(mut a: A) -> Option<Self> {
-        unsafe {
-            let raw = a.alloc_zeroed(Layout::new::<T>()).ok()?.cast();
-            Some(Box::from_raw_in(raw.as_ptr(), a))
-        }
+    fn try_new_zeroed_in(a: A) -> Option<Self> {
+        unsafe { Some(UninitBoxIn::try_new(a, true).ok()?.assume_init()) }
     }
 }
 
@@ -313,6 +404,7 @@ impl<T, A: Alloc + Default> BoxExt for Box<T, A> {
     ///
     /// [`allocator_api::Box::new_in_with`]: trait.BoxInExt.html#tymethod.new_in_with
     #[inline]
+    #[track_caller]
     fn new_with<F: FnOnce() -> Self::Inner>(f: F) -> Self {
         BoxInExt::new_in_with(f, Default::default())
     }
@@ -352,6 +444,7 @@ impl<T, A: Alloc + Default> BoxExt for Box<T, A> {
     ///
     /// [`Zero`]: trait.Zero.html
     #[inline]
+    #[track_caller]
     fn new_zeroed() -> Self
     where
         T: Zero,
@@ -486,4 +579,87 @@ impl<T, A: Alloc + Default> BoxExt for Box<T, A> {
     {
         BoxInExt::try_new_zeroed_in(Default::default())
     }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn new_zeroed_unchecked() -> Self {
+        UninitBoxIn::new(A::default(), true).assume_init()
+    }
+
+    #[inline]
+    unsafe fn try_new_zeroed_unchecked() -> Option<Self> {
+        Some(UninitBoxIn::try_new(A::default(), true).ok()?.assume_init())
+    }
+
+    /// Allocates memory in the given allocator and then places the result of
+    /// `f` into it, if `f` succeeds.
+    ///
+    /// If `f` returns an error, the memory is freed and the error is
+    /// returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate allocator_api;
+    /// extern crate boxext;
+    /// use allocator_api::Box;
+    /// use boxext::BoxExt;
+    /// # include!("dummy.rs");
+    ///
+    /// fn main() {
+    ///     let buf: Result<Box<u64, MyHeap>, _> = Box::new_with_fallible(|| "42".parse());
+    ///     assert_eq!(*buf.unwrap(), 42);
+    /// }
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn new_with_fallible<E, F: FnOnce() -> Result<T, E>>(f: F) -> Result<Self, E> {
+        let uninit = UninitBoxIn::new(A::default(), false);
+        Ok(uninit.init(f()?))
+    }
+
+    /// Fallible [`Box::new_with_fallible`]
+    ///
+    /// [`Box::new_with_fallible`]: #method.new_with_fallible
+    ///
+    /// This returns `None` if memory couldn't be allocated, in which case
+    /// `f` is not called.
+    #[inline]
+    fn try_new_with_fallible<E, F: FnOnce() -> Result<T, E>>(f: F) -> Option<Result<Self, E>> {
+        let uninit = UninitBoxIn::try_new(A::default(), false).ok()?;
+        Some(f().map(|value| uninit.init(value)))
+    }
+
+    /// Allocates memory in the given allocator and lets `f` initialize it in
+    /// place.
+    ///
+    /// If `f` panics, the memory is freed.
+    ///
+    /// # Safety
+    ///
+    /// `f` must fully initialize the value, unless it panics.
+    #[inline]
+    #[track_caller]
+    unsafe fn new_with_ptr<F: FnOnce(&mut MaybeUninit<T>)>(f: F) -> Self {
+        let uninit = UninitBoxIn::new(A::default(), false);
+        f(&mut *(uninit.ptr.as_ptr() as *mut MaybeUninit<T>));
+        uninit.assume_init()
+    }
+
+    /// Fallible [`Box::new_with_ptr`]
+    ///
+    /// [`Box::new_with_ptr`]: #method.new_with_ptr
+    ///
+    /// This returns `None` if memory couldn't be allocated, in which case
+    /// `f` is not called.
+    ///
+    /// # Safety
+    ///
+    /// `f` must fully initialize the value, unless it panics.
+    #[inline]
+    unsafe fn try_new_with_ptr<F: FnOnce(&mut MaybeUninit<T>)>(f: F) -> Option<Self> {
+        let uninit = UninitBoxIn::try_new(A::default(), false).ok()?;
+        f(&mut *(uninit.ptr.as_ptr() as *mut MaybeUninit<T>));
+        Some(uninit.assume_init())
+    }
 }
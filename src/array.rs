@@ -0,0 +1,161 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use BoxBuilder;
+
+// Turns a boxed slice of `N` elements into a boxed array.
+unsafe fn into_boxed_array<T, const N: usize>(b: Box<[T]>) -> Box<[T; N]> {
+    debug_assert_eq!(b.len(), N);
+    Box::from_raw(Box::into_raw(b) as *mut [T; N])
+}
+
+/// Allocates an array on the heap and initializes each of its elements in
+/// place with the result of `f` called with the element index.
+///
+/// Contrary to `Box::new(core::array::from_fn(f))`, this doesn't build the
+/// whole array on the stack first.
+///
+/// If `f` panics, the elements that were already initialized are dropped and
+/// the memory is freed.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::array_init_boxed;
+///
+/// fn main() {
+///     let squares = array_init_boxed::<usize, 1024, _>(|i| i * i);
+///     assert_eq!(squares[3], 9);
+///     assert_eq!(squares[1023], 1023 * 1023);
+///
+///     let names: Box<[String; 3]> = array_init_boxed(|i| format!("item{}", i));
+///     assert_eq!(*names, ["item0", "item1", "item2"]);
+/// }
+/// ```
+#[track_caller]
+pub fn array_init_boxed<T, const N: usize, F: FnMut(usize) -> T>(f: F) -> Box<[T; N]> {
+    fill(BoxBuilder::new(N), f)
+}
+
+// Fills `builder`, which has a capacity of `N`, with the results of `f`, and
+// returns the boxed array.
+fn fill<T, const N: usize, F: FnMut(usize) -> T>(mut builder: BoxBuilder<T>, f: F) -> Box<[T; N]> {
+    let _ = builder.extend((0..N).map(f));
+    match builder.finish() {
+        Ok(b) => unsafe { into_boxed_array(b) },
+        Err(_) => unreachable!(),
+    }
+}
+
+/// Fallible-initializer variant of [`array_init_boxed`]
+///
+/// [`array_init_boxed`]: fn.array_init_boxed.html
+///
+/// Allocates an array on the heap and initializes each of its elements in
+/// place with the result of `f` called with the element index. If `f`
+/// returns an error, the elements that were already initialized are dropped,
+/// the memory is freed, and the error is returned.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::try_array_init_boxed;
+///
+/// fn main() {
+///     let input = "1 2 3 4";
+///     let mut fields = input.split(' ');
+///     let parsed: Result<Box<[u32; 4]>, _> =
+///         try_array_init_boxed(|_| fields.next().unwrap_or("").parse());
+///     assert_eq!(*parsed.unwrap(), [1, 2, 3, 4]);
+///
+///     let mut fields = input.split(' ');
+///     let parsed: Result<Box<[u32; 5]>, _> =
+///         try_array_init_boxed(|_| fields.next().unwrap_or("").parse());
+///     assert!(parsed.is_err());
+/// }
+/// ```
+#[track_caller]
+pub fn try_array_init_boxed<T, E, const N: usize, F: FnMut(usize) -> Result<T, E>>(
+    mut f: F,
+) -> Result<Box<[T; N]>, E> {
+    let mut builder = BoxBuilder::new(N);
+    for i in 0..N {
+        let _ = builder.push(f(i)?);
+    }
+    match builder.finish() {
+        Ok(b) => Ok(unsafe { into_boxed_array(b) }),
+        Err(_) => unreachable!(),
+    }
+}
+
+/// Extensions to the `Box<[T; N]>` type
+///
+/// [`BoxArrayExt::new_array_with`] is [`array_init_boxed`] as a method, and
+/// is implemented with it. This trait only adds a variant returning `None`
+/// when memory couldn't be allocated, [`BoxArrayExt::try_new_array_with`].
+///
+/// [`BoxArrayExt::new_array_with`]: #tymethod.new_array_with
+/// [`array_init_boxed`]: fn.array_init_boxed.html
+/// [`BoxArrayExt::try_new_array_with`]: #tymethod.try_new_array_with
+pub trait BoxArrayExt: Sized {
+    /// Type of the elements of the array.
+    type Item;
+
+    /// Allocates an array on the heap and initializes each of its elements
+    /// in place with the result of `f` called with the element index.
+    ///
+    /// This calls [`array_init_boxed`].
+    ///
+    /// [`array_init_boxed`]: fn.array_init_boxed.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxArrayExt;
+    ///
+    /// fn main() {
+    ///     let crc_table: Box<[u32; 256]> = Box::new_array_with(|i| {
+    ///         (0..8).fold(i as u32, |c, _| if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 })
+    ///     });
+    ///     assert_eq!(crc_table[1], 0x77073096);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_array_with<F: FnMut(usize) -> Self::Item>(f: F) -> Self;
+
+    /// Fallible [`BoxArrayExt::new_array_with`]
+    ///
+    /// [`BoxArrayExt::new_array_with`]: #tymethod.new_array_with
+    ///
+    /// This returns `None` if memory couldn't be allocated. `f` is not
+    /// called in that case.
+    fn try_new_array_with<F: FnMut(usize) -> Self::Item>(f: F) -> Option<Self>;
+}
+
+impl<T, const N: usize> BoxArrayExt for Box<[T; N]> {
+    type Item = T;
+
+    #[inline]
+    #[track_caller]
+    fn new_array_with<F: FnMut(usize) -> T>(f: F) -> Self {
+        array_init_boxed(f)
+    }
+
+    #[inline]
+    fn try_new_array_with<F: FnMut(usize) -> T>(f: F) -> Option<Self> {
+        Some(fill(BoxBuilder::try_new(N)?, f))
+    }
+}
@@ -0,0 +1,174 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::mem;
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+use {new_slice_box, try_new_slice_box};
+
+const BITS: usize = mem::size_of::<usize>() * 8;
+
+// Number of words needed to hold `len` bits.
+fn words_for(len: usize) -> usize {
+    len / BITS + if len % BITS != 0 { 1 } else { 0 }
+}
+
+/// A fixed-size set of bits, stored in a zeroed heap allocation.
+///
+/// The underlying storage is obtained directly from the allocator as zeroed
+/// memory, so creating a large `BitBox` doesn't involve touching all its
+/// memory, which, for large enough sizes, usually comes straight from fresh
+/// zero pages.
+///
+/// # Examples
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::BitBox;
+///
+/// fn main() {
+///     let mut bits = BitBox::new(1000);
+///     assert_eq!(bits.len(), 1000);
+///     assert_eq!(bits.count_ones(), 0);
+///
+///     bits.set(3);
+///     bits.set(999);
+///     assert!(bits.get(3));
+///     assert!(!bits.get(4));
+///     assert_eq!(bits.count_ones(), 2);
+///
+///     bits.clear(3);
+///     assert!(!bits.get(3));
+///     assert_eq!(bits.count_ones(), 1);
+///
+///     bits.clear_all();
+///     assert_eq!(bits.count_ones(), 0);
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitBox {
+    words: Box<[usize]>,
+    len: usize,
+}
+
+impl BitBox {
+    /// Creates a new `BitBox` holding `len` bits, all cleared.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new(len: usize) -> Self {
+        BitBox {
+            words: unsafe { new_slice_box(words_for(len), true) },
+            len,
+        }
+    }
+
+    /// Fallible [`BitBox::new`]
+    ///
+    /// [`BitBox::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BitBox;
+    ///
+    /// fn main() {
+    ///     let bits = BitBox::try_new(64).unwrap();
+    ///     assert_eq!(bits.len(), 64);
+    /// }
+    /// ```
+    pub fn try_new(len: usize) -> Option<Self> {
+        Some(BitBox {
+            words: unsafe { try_new_slice_box(words_for(len), true).ok()? },
+            len,
+        })
+    }
+
+    /// Returns the number of bits in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the set holds no bits at all.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the value of the bit at index `idx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    #[inline]
+    pub fn get(&self, idx: usize) -> bool {
+        self.check_index(idx);
+        self.words[idx / BITS] & (1 << (idx % BITS)) != 0
+    }
+
+    /// Sets the bit at index `idx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    #[inline]
+    pub fn set(&mut self, idx: usize) {
+        self.check_index(idx);
+        self.words[idx / BITS] |= 1 << (idx % BITS);
+    }
+
+    /// Clears the bit at index `idx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    #[inline]
+    pub fn clear(&mut self, idx: usize) {
+        self.check_index(idx);
+        self.words[idx / BITS] &= !(1 << (idx % BITS));
+    }
+
+    /// Returns the number of bits that are set.
+    pub fn count_ones(&self) -> usize {
+        // Bits past `len` are never set, so there's no need to mask the last
+        // word.
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Clears all the bits.
+    pub fn clear_all(&mut self) {
+        for w in self.words.iter_mut() {
+            *w = 0;
+        }
+    }
+
+    #[inline]
+    fn check_index(&self, idx: usize) {
+        assert!(
+            idx < self.len,
+            "index out of bounds: the len is {} but the index is {}",
+            self.len,
+            idx
+        );
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for BitBox {
+    fn zeroize(&mut self) {
+        self.words.zeroize();
+    }
+}
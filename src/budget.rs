@@ -0,0 +1,151 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Per-thread allocation budgets.
+//!
+//! A budget bounds the number of bytes the crate allocates on the current
+//! thread, whether for values handed out as a `Box` or for memory it owns
+//! itself. Once a budget is set, allocations that would exceed it fail: the
+//! `try_*` functions return `None`, and the others call
+//! [`handle_alloc_error`].
+//!
+//! Budgets count the bytes that were allocated, not the bytes that are
+//! still in use: deallocating memory doesn't replenish the budget.
+//!
+//! Allocations that don't go through this crate, such as `Box::new` or
+//! `Vec::with_capacity`, are not accounted for.
+//!
+//! [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+//!
+//! # Example
+//!
+//! ```
+//! extern crate boxext;
+//! use boxext::budget;
+//! use boxext::BoxExt;
+//!
+//! fn main() {
+//!     let (small, large) = budget::scope(4096, || {
+//!         let small = Box::<[u8; 1024]>::try_new_zeroed();
+//!         let large = Box::<[u8; 4096]>::try_new_zeroed();
+//!         (small, large)
+//!     });
+//!     assert!(small.is_some());
+//!     assert!(large.is_none());
+//!
+//!     let err = budget::take_exceeded().unwrap();
+//!     assert_eq!(err.requested(), 4096);
+//!     assert_eq!(err.remaining(), 3072);
+//! }
+//! ```
+
+use core::cell::Cell;
+use core::fmt;
+use std::error::Error;
+
+thread_local! {
+    static REMAINING: Cell<Option<usize>> = const { Cell::new(None) };
+    static EXCEEDED: Cell<Option<BudgetExceeded>> = const { Cell::new(None) };
+}
+
+/// Error recorded when an allocation didn't fit in the thread's budget.
+///
+/// See [`take_exceeded`].
+///
+/// [`take_exceeded`]: fn.take_exceeded.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    requested: usize,
+    remaining: usize,
+}
+
+impl BudgetExceeded {
+    /// Returns the number of bytes that the failed allocation requested.
+    pub fn requested(&self) -> usize {
+        self.requested
+    }
+
+    /// Returns the number of bytes that were left in the budget.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "allocation of {} bytes exceeds the remaining budget of {} bytes",
+            self.requested, self.remaining
+        )
+    }
+}
+
+impl Error for BudgetExceeded {}
+
+/// Sets the allocation budget of the current thread, in bytes.
+///
+/// `None` removes the budget.
+pub fn set(bytes: Option<usize>) {
+    REMAINING.with(|r| r.set(bytes));
+}
+
+/// Returns the number of bytes left in the current thread's budget, or
+/// `None` if no budget is set.
+pub fn remaining() -> Option<usize> {
+    REMAINING.with(Cell::get)
+}
+
+/// Returns the last allocation failure due to the current thread's budget,
+/// if any, and forgets about it.
+///
+/// This allows to tell such failures from the allocator failing, after a
+/// `try_*` function returned `None`.
+pub fn take_exceeded() -> Option<BudgetExceeded> {
+    EXCEEDED.with(Cell::take)
+}
+
+// Restores the previous budget when a scope ends, even when unwinding.
+struct Restore(Option<usize>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        set(self.0);
+    }
+}
+
+/// Runs `f` with a budget of `bytes` bytes on the current thread.
+///
+/// The previous budget is restored afterwards. The bytes allocated within
+/// the scope are not deducted from the previous budget.
+pub fn scope<R, F: FnOnce() -> R>(bytes: usize, f: F) -> R {
+    let _restore = Restore(remaining());
+    set(Some(bytes));
+    f()
+}
+
+// Deducts `size` bytes from the current thread's budget. Returns false,
+// leaving the budget untouched, if there isn't enough left.
+pub(crate) fn charge(size: usize) -> bool {
+    REMAINING.with(|r| match r.get() {
+        None => true,
+        Some(remaining) if size <= remaining => {
+            r.set(Some(remaining - size));
+            true
+        }
+        Some(remaining) => {
+            EXCEEDED.with(|e| {
+                e.set(Some(BudgetExceeded {
+                    requested: size,
+                    remaining,
+                }))
+            });
+            false
+        }
+    })
+}
@@ -0,0 +1,244 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::{fmt, mem, ptr, slice};
+use std::error::Error;
+use {alloc_slice, dealloc_slice, try_alloc_slice};
+
+/// Error returned when appending more elements to a [`BoxBuilder`] than it
+/// has room for.
+///
+/// [`BoxBuilder`]: struct.BoxBuilder.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CapacityError;
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("capacity exceeded")
+    }
+}
+
+impl Error for CapacityError {}
+
+/// Builds a boxed slice of an exact size from multiple pieces.
+///
+/// The memory for the whole slice is allocated upfront, and elements are
+/// written into it as they are appended, avoiding the reallocations and
+/// the final shrinking copy that building through a `Vec` can involve.
+///
+/// The builder only produces a boxed slice once it has been filled
+/// completely. If it is dropped before that, the elements that were
+/// appended are dropped and the memory is freed.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::BoxBuilder;
+///
+/// fn main() {
+///     let header = [0xca, 0xfe];
+///     let payload = b"hello";
+///     let mut frame = BoxBuilder::new(header.len() + payload.len() + 1);
+///     frame.extend_from_slice(&header).unwrap();
+///     frame.extend_from_slice(payload).unwrap();
+///     assert!(frame.extend_from_slice(&[0, 0]).is_err());
+///     let checksum = payload.iter().fold(0, |a, b| a ^ b);
+///     frame.extend(Some(checksum)).unwrap();
+///     let frame: Box<[u8]> = frame.finish().unwrap();
+///     assert_eq!(&frame[..], b"\xca\xfehello\x62");
+/// }
+/// ```
+pub struct BoxBuilder<T> {
+    ptr: *mut T,
+    len: usize,
+    capacity: usize,
+}
+
+impl<T> BoxBuilder<T> {
+    /// Creates a new builder for a boxed slice of exactly `capacity`
+    /// elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows, and calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new(capacity: usize) -> Self {
+        BoxBuilder {
+            ptr: unsafe { alloc_slice::<T>(capacity, false) },
+            len: 0,
+            capacity,
+        }
+    }
+
+    /// Fallible [`BoxBuilder::new`]
+    ///
+    /// [`BoxBuilder::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new(capacity: usize) -> Option<Self> {
+        let ptr = unsafe { try_alloc_slice::<T>(capacity, false).ok()? };
+        Some(BoxBuilder {
+            ptr,
+            len: 0,
+            capacity,
+        })
+    }
+
+    /// Returns the number of elements appended so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether no element was appended yet.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of elements the final boxed slice will contain.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of elements that still need to be appended.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.capacity - self.len
+    }
+
+    /// Returns whether the builder has been filled completely.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len == self.capacity
+    }
+
+    /// Appends an element.
+    ///
+    /// If the builder is already full, the element is given back.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxBuilder;
+    ///
+    /// // Decodes a count-prefixed list of little-endian `u16`s.
+    /// fn decode(input: &[u8]) -> Option<Box<[u16]>> {
+    ///     let (&count, mut input) = input.split_first()?;
+    ///     let mut values = BoxBuilder::try_new(count as usize)?;
+    ///     while let [lo, hi, rest @ ..] = input {
+    ///         values.push(u16::from_le_bytes([*lo, *hi])).ok()?;
+    ///         input = rest;
+    ///     }
+    ///     values.finish().ok()
+    /// }
+    ///
+    /// fn main() {
+    ///     assert_eq!(&decode(b"\x02\x01\x00\x00\x01").unwrap()[..], &[1, 256]);
+    ///     assert!(decode(b"\x02\x01\x00").is_none());
+    ///     assert!(decode(b"\x01\x01\x00\x00\x01").is_none());
+    /// }
+    /// ```
+    #[inline]
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+        unsafe {
+            ptr::write(self.ptr.add(self.len), value);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Appends the elements from the given iterator.
+    ///
+    /// If the iterator yields more elements than there is room for, the
+    /// builder is filled completely, and `CapacityError` is returned. The
+    /// element that didn't fit is dropped, and the remainder of the iterator
+    /// is left unconsumed.
+    pub fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) -> Result<(), CapacityError> {
+        for value in iter {
+            if self.push(value).is_err() {
+                return Err(CapacityError);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the elements appended so far.
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    /// Returns the elements appended so far.
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+
+    /// Turns the builder into a boxed slice.
+    ///
+    /// If the builder hasn't been filled completely, it is given back.
+    pub fn finish(self) -> Result<Box<[T]>, Self> {
+        if !self.is_full() {
+            return Err(self);
+        }
+        let result = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(self.ptr, self.capacity)) };
+        mem::forget(self);
+        Ok(result)
+    }
+}
+
+impl<T: Clone> BoxBuilder<T> {
+    /// Appends clones of all the elements of the given slice.
+    ///
+    /// If there is not enough room for the whole slice, nothing is appended
+    /// and `CapacityError` is returned.
+    pub fn extend_from_slice(&mut self, other: &[T]) -> Result<(), CapacityError> {
+        if other.len() > self.remaining() {
+            return Err(CapacityError);
+        }
+        for value in other {
+            unsafe {
+                ptr::write(self.ptr.add(self.len), value.clone());
+            }
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+unsafe impl<T: Send> Send for BoxBuilder<T> {}
+
+unsafe impl<T: Sync> Sync for BoxBuilder<T> {}
+
+impl<T> Drop for BoxBuilder<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.as_mut_slice());
+            dealloc_slice(self.ptr, self.capacity);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for BoxBuilder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BoxBuilder")
+            .field("elements", &self.as_slice())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
@@ -0,0 +1,347 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::mem::{self, MaybeUninit};
+use core::{cmp, fmt, ptr, slice};
+use std::error::Error;
+use std::panic;
+use std::thread;
+use {alloc_slice, dealloc_slice, try_alloc_slice};
+
+/// Error returned by [`ChunkedInit::run`] when some chunk was not fully
+/// initialized.
+///
+/// [`ChunkedInit::run`]: struct.ChunkedInit.html#method.run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IncompleteInitError {
+    index: usize,
+}
+
+impl IncompleteInitError {
+    /// Returns the index, in the whole slice, of the first element that was
+    /// not initialized.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl fmt::Display for IncompleteInitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "element {} was not initialized", self.index)
+    }
+}
+
+impl Error for IncompleteInitError {}
+
+/// Initializes a boxed slice from multiple threads.
+///
+/// The memory for the whole slice is allocated upfront, and split in
+/// disjoint [`Chunk`]s, each of which is handed to a scoped thread for
+/// initialization. Once all the threads are done, the boxed slice is only
+/// returned if every chunk was fully initialized.
+///
+/// By default, the slice is split in as many chunks as the available
+/// parallelism.
+///
+/// [`Chunk`]: struct.Chunk.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::ChunkedInit;
+///
+/// fn main() {
+///     let squares: Box<[u64]> = ChunkedInit::new(1 << 20)
+///         .threads(4)
+///         .run(|chunk| {
+///             for i in chunk.offset()..chunk.offset() + chunk.len() {
+///                 chunk.push((i * i) as u64).unwrap();
+///             }
+///         })
+///         .unwrap();
+///     assert_eq!(squares.len(), 1 << 20);
+///     assert_eq!(squares[1000], 1_000_000);
+///
+///     let err = ChunkedInit::<u64>::new(100)
+///         .threads(4)
+///         .run(|chunk| {
+///             if chunk.index() != 2 {
+///                 while chunk.push(0).is_ok() {}
+///             }
+///         })
+///         .unwrap_err();
+///     assert_eq!(err.index(), 50);
+/// }
+/// ```
+pub struct ChunkedInit<T> {
+    ptr: *mut T,
+    len: usize,
+    threads: usize,
+}
+
+impl<T: Send> ChunkedInit<T> {
+    /// Allocates memory for a boxed slice of `len` elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows, and calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new(len: usize) -> Self {
+        ChunkedInit {
+            ptr: unsafe { alloc_slice::<T>(len, false) },
+            len,
+            threads: 0,
+        }
+    }
+
+    /// Fallible [`ChunkedInit::new`]
+    ///
+    /// [`ChunkedInit::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new(len: usize) -> Option<Self> {
+        Some(ChunkedInit {
+            ptr: unsafe { try_alloc_slice::<T>(len, false).ok()? },
+            len,
+            threads: 0,
+        })
+    }
+
+    /// Sets the number of threads, and thus of chunks, to use.
+    ///
+    /// Fewer threads are used if the slice has fewer elements.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Runs `f` on each chunk, each in its own thread.
+    ///
+    /// If `f` panics in any thread, the panic is propagated once all the
+    /// threads are done. If some chunk was not fully initialized, an error
+    /// is returned. In both cases, the elements that were initialized are
+    /// dropped and the memory is freed.
+    pub fn run<F: Fn(&mut Chunk<T>) + Sync>(self, f: F) -> Result<Box<[T]>, IncompleteInitError> {
+        let threads = match self.threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let threads = cmp::min(threads, self.len);
+        let (ptr, len) = (self.ptr, self.len);
+        mem::forget(self);
+        if threads == 0 {
+            return Ok(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, 0)) });
+        }
+        // Returns the offset and length of the chunk with the given index.
+        // The remainder is spread over the first chunks.
+        let bounds = |index: usize| {
+            let (q, r) = (len / threads, len % threads);
+            (index * q + cmp::min(index, r), q + if index < r { 1 } else { 0 })
+        };
+        let chunks = (0..threads)
+            .map(|index| {
+                let (offset, len) = bounds(index);
+                Chunk {
+                    ptr: unsafe { ptr.add(offset) },
+                    index,
+                    offset,
+                    len,
+                    initialized: 0,
+                }
+            })
+            .collect::<Vec<_>>();
+        let f = &f;
+        // Each thread returns the number of elements its chunk initialized.
+        // Incomplete chunks drop their elements.
+        let results = thread::scope(|s| {
+            let handles = chunks
+                .into_iter()
+                .map(|mut chunk| {
+                    s.spawn(move || {
+                        f(&mut chunk);
+                        let initialized = chunk.initialized;
+                        if initialized == chunk.len {
+                            mem::forget(chunk);
+                        }
+                        initialized
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles.into_iter().map(|h| h.join()).collect::<Vec<_>>()
+        });
+        let complete = results
+            .iter()
+            .enumerate()
+            .all(|(index, r)| matches!(*r, Ok(n) if n == bounds(index).1));
+        if complete {
+            return Ok(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)) });
+        }
+        let mut error = None;
+        let mut panic = None;
+        for (index, result) in results.into_iter().enumerate() {
+            let (offset, chunk_len) = bounds(index);
+            match result {
+                Ok(n) if n == chunk_len => unsafe {
+                    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr.add(offset), n));
+                },
+                Ok(n) => {
+                    error = error.or(Some(IncompleteInitError { index: offset + n }));
+                }
+                Err(e) => panic = panic.or(Some(e)),
+            }
+        }
+        unsafe {
+            dealloc_slice(ptr, len);
+        }
+        if let Some(e) = panic {
+            panic::resume_unwind(e);
+        }
+        Err(error.unwrap())
+    }
+}
+
+unsafe impl<T: Send> Send for ChunkedInit<T> {}
+
+unsafe impl<T: Sync> Sync for ChunkedInit<T> {}
+
+impl<T> Drop for ChunkedInit<T> {
+    fn drop(&mut self) {
+        unsafe {
+            dealloc_slice(self.ptr, self.len);
+        }
+    }
+}
+
+impl<T> fmt::Debug for ChunkedInit<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ChunkedInit")
+            .field("len", &self.len)
+            .field("threads", &self.threads)
+            .finish()
+    }
+}
+
+/// A part of a slice being initialized by [`ChunkedInit`].
+///
+/// Elements are initialized in order, either by appending them with
+/// [`push`], or by writing them through [`as_uninit_mut`] and then calling
+/// [`set_initialized`].
+///
+/// [`ChunkedInit`]: struct.ChunkedInit.html
+/// [`push`]: #method.push
+/// [`as_uninit_mut`]: #method.as_uninit_mut
+/// [`set_initialized`]: #method.set_initialized
+pub struct Chunk<T> {
+    ptr: *mut T,
+    index: usize,
+    offset: usize,
+    len: usize,
+    initialized: usize,
+}
+
+impl<T> Chunk<T> {
+    /// Returns the index of the chunk.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the index, in the whole slice, of the first element of the
+    /// chunk.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the number of elements in the chunk.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the chunk has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of elements initialized so far.
+    #[inline]
+    pub fn initialized(&self) -> usize {
+        self.initialized
+    }
+
+    /// Initializes the next element of the chunk.
+    ///
+    /// If the chunk is already fully initialized, `value` is given back.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.initialized == self.len {
+            return Err(value);
+        }
+        unsafe {
+            ptr::write(self.ptr.add(self.initialized), value);
+        }
+        self.initialized += 1;
+        Ok(())
+    }
+
+    /// Returns the elements of the chunk that are not initialized yet.
+    pub fn as_uninit_mut(&mut self) -> &mut [MaybeUninit<T>] {
+        unsafe {
+            slice::from_raw_parts_mut(
+                self.ptr.add(self.initialized) as *mut MaybeUninit<T>,
+                self.len - self.initialized,
+            )
+        }
+    }
+
+    /// Marks the first `n` elements returned by [`as_uninit_mut`] as
+    /// initialized.
+    ///
+    /// [`as_uninit_mut`]: #method.as_uninit_mut
+    ///
+    /// # Safety
+    ///
+    /// Those elements must have been initialized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is larger than the number of elements that were not
+    /// initialized.
+    pub unsafe fn set_initialized(&mut self, n: usize) {
+        assert!(n <= self.len - self.initialized);
+        self.initialized += n;
+    }
+}
+
+unsafe impl<T: Send> Send for Chunk<T> {}
+
+unsafe impl<T: Sync> Sync for Chunk<T> {}
+
+impl<T> Drop for Chunk<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.ptr, self.initialized));
+        }
+    }
+}
+
+impl<T> fmt::Debug for Chunk<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Chunk")
+            .field("index", &self.index)
+            .field("offset", &self.offset)
+            .field("len", &self.len)
+            .field("initialized", &self.initialized)
+            .finish()
+    }
+}
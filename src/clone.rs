@@ -0,0 +1,114 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(feature = "specialization")]
+use core::ptr;
+use UninitBox;
+
+/// Cloning of values directly into a new heap allocation
+///
+/// `Box::new(value.clone())` builds the clone on the stack before moving it
+/// to the heap. The methods of this trait allocate memory first, and then
+/// place the clone into it, which, as with [`BoxExt::new_with`], is expected
+/// to avoid copies when building with optimization enabled.
+///
+/// With the `specialization` feature, values of `Copy` types are copied
+/// straight from the original into the new allocation, which also avoids
+/// copies without optimizations.
+///
+/// Note that when called with the method syntax on a `Box<T>`, the methods
+/// of this trait clone the `Box` itself, into a `Box<Box<T>>`.
+///
+/// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::CloneToBox;
+///
+/// #[derive(Clone)]
+/// struct Snapshot {
+///     counters: [u64; 4096],
+/// }
+///
+/// fn main() {
+///     let current = Box::new(Snapshot { counters: [1; 4096] });
+///     let saved = Snapshot::clone_boxed(&current);
+///     assert_eq!(saved.counters[4095], 1);
+/// }
+/// ```
+pub trait CloneToBox: Clone {
+    /// Clones the value into a new `Box`.
+    ///
+    /// This doesn't actually allocate if `Self` is zero-sized.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn clone_boxed(&self) -> Box<Self>;
+
+    /// Fallible [`CloneToBox::clone_boxed`]
+    ///
+    /// [`CloneToBox::clone_boxed`]: #tymethod.clone_boxed
+    ///
+    /// This returns `None` if memory couldn't be allocated, in which case
+    /// the value is not cloned.
+    fn try_clone_boxed(&self) -> Option<Box<Self>>;
+}
+
+// Helper for `CloneToBox`, allowing, with the `specialization` feature, to
+// pick a different implementation for `Copy` types.
+trait CloneIntoBox: Clone {
+    fn clone_into_box(&self, uninit: UninitBox<Self>) -> Box<Self>;
+}
+
+macro_rules! clone_into_box_impl {
+    ($($default:ident)*) => {
+        impl<T: Clone> CloneIntoBox for T {
+            #[inline]
+            $($default)* fn clone_into_box(&self, uninit: UninitBox<T>) -> Box<T> {
+                uninit.init(self.clone())
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "specialization"))]
+clone_into_box_impl!();
+
+#[cfg(feature = "specialization")]
+clone_into_box_impl!(default);
+
+#[cfg(feature = "specialization")]
+impl<T: Copy> CloneIntoBox for T {
+    #[inline]
+    fn clone_into_box(&self, uninit: UninitBox<T>) -> Box<T> {
+        unsafe {
+            ptr::copy_nonoverlapping(self, uninit.0.as_ptr(), 1);
+            uninit.assume_init()
+        }
+    }
+}
+
+impl<T: Clone> CloneToBox for T {
+    #[inline]
+    #[track_caller]
+    fn clone_boxed(&self) -> Box<T> {
+        self.clone_into_box(UninitBox::new(false))
+    }
+
+    #[inline]
+    fn try_clone_boxed(&self) -> Option<Box<T>> {
+        Some(self.clone_into_box(UninitBox::try_new(false).ok()?))
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::boxed::Box;
+use core::cell::Cell;
+use core::num::Wrapping;
+use core::sync::atomic;
+#[cfg(feature = "alloc")]
+use UninitBox;
+use Zero;
+
+/// Trait indicating that the default value of a type is represented by
+/// zeroes.
+///
+/// With the `specialization` feature, this allows [`Box::new_default`] to
+/// get zeroed memory from the allocator instead of writing the default value
+/// into the new allocation, as [`Box::new_zeroed`] does.
+///
+/// [`Box::new_default`]: trait.BoxExt.html#method.new_default
+/// [`Box::new_zeroed`]: trait.BoxExt.html#tymethod.new_zeroed
+///
+/// # Safety
+///
+/// Do **not** implement this trait for types whose `Default` implementation
+/// returns anything else than a value full of zeroes, or that has side
+/// effects.
+///
+/// # Implementations
+///
+/// `ZeroDefault` is implemented for integer and floating point types,
+/// `bool`, `char` and atomic types, and for `Cell<T>` and `Wrapping<T>` when
+/// `T` implements `ZeroDefault`.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{BoxExt, Zero, ZeroDefault};
+///
+/// #[derive(Default)]
+/// struct Histogram {
+///     buckets: [u64; 32],
+///     total: u64,
+/// }
+///
+/// unsafe impl Zero for Histogram {}
+/// unsafe impl ZeroDefault for Histogram {}
+///
+/// fn main() {
+/// #   #[cfg(feature = "std")]
+///     let hist: Box<Histogram> = BoxExt::new_default();
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(hist.total, 0);
+/// }
+/// ```
+pub unsafe trait ZeroDefault: Zero + Default {}
+
+macro_rules! zero_default_impl {
+    ($($t:ty)+) => { $(unsafe impl ZeroDefault for $t {})+ }
+}
+
+zero_default_impl! {
+    u8 u16 u32 u64 u128 usize
+    i8 i16 i32 i64 i128 isize
+    f32 f64
+    bool char
+}
+
+#[cfg(target_has_atomic = "8")]
+zero_default_impl! {
+    atomic::AtomicBool atomic::AtomicU8 atomic::AtomicI8
+}
+
+#[cfg(target_has_atomic = "16")]
+zero_default_impl! {
+    atomic::AtomicU16 atomic::AtomicI16
+}
+
+#[cfg(target_has_atomic = "32")]
+zero_default_impl! {
+    atomic::AtomicU32 atomic::AtomicI32
+}
+
+#[cfg(target_has_atomic = "64")]
+zero_default_impl! {
+    atomic::AtomicU64 atomic::AtomicI64
+}
+
+#[cfg(target_has_atomic = "ptr")]
+zero_default_impl! {
+    atomic::AtomicUsize atomic::AtomicIsize
+}
+
+unsafe impl<T: ZeroDefault> ZeroDefault for Cell<T> {}
+
+unsafe impl<T: ZeroDefault> ZeroDefault for Wrapping<T> {}
+
+// Helper for `BoxExt::new_default`, allowing, with the `specialization`
+// feature, to pick a different implementation for `ZeroDefault` types.
+#[cfg(feature = "alloc")]
+pub(crate) trait BoxedDefault: Default {
+    fn boxed_default() -> Box<Self>;
+
+    fn try_boxed_default() -> Option<Box<Self>>;
+}
+
+macro_rules! boxed_default_impl {
+    ($($default:ident)*) => {
+        #[cfg(feature = "alloc")]
+        impl<T: Default> BoxedDefault for T {
+            #[inline]
+            #[track_caller]
+            $($default)* fn boxed_default() -> Box<T> {
+                UninitBox::new(false).init(T::default())
+            }
+
+            #[inline]
+            $($default)* fn try_boxed_default() -> Option<Box<T>> {
+                Some(UninitBox::try_new(false).ok()?.init(T::default()))
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "specialization"))]
+boxed_default_impl!();
+
+#[cfg(feature = "specialization")]
+boxed_default_impl!(default);
+
+#[cfg(all(feature = "alloc", feature = "specialization"))]
+impl<T: ZeroDefault> BoxedDefault for T {
+    #[inline]
+    #[track_caller]
+    fn boxed_default() -> Box<T> {
+        unsafe { UninitBox::new(true).assume_init() }
+    }
+
+    #[inline]
+    fn try_boxed_default() -> Option<Box<T>> {
+        unsafe { Some(UninitBox::try_new(true).ok()?.assume_init()) }
+    }
+}
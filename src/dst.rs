@@ -0,0 +1,304 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::{mem, ptr};
+use heap::poison;
+use {dealloc, try_alloc_global, AllocError, Zero};
+
+/// Trait for dynamically sized types made of a sized header followed by a
+/// slice, which [`BoxDstExt`] allocates.
+///
+/// [`BoxDstExt`]: trait.BoxDstExt.html
+///
+/// # Safety
+///
+/// The type must be a `#[repr(C)]` struct with exactly two fields: a first
+/// field of type `Self::Header`, and a last field of type `[Self::Item]`.
+/// [`from_raw_slice`] must only cast the pointer it is given.
+///
+/// [`from_raw_slice`]: #tymethod.from_raw_slice
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{BoxDstExt, Dst};
+///
+/// #[repr(C)]
+/// struct Path {
+///     len: usize,
+///     points: [(f32, f32)],
+/// }
+///
+/// unsafe impl Dst for Path {
+///     type Header = usize;
+///     type Item = (f32, f32);
+///
+///     fn from_raw_slice(ptr: *mut [(f32, f32)]) -> *mut Self {
+///         ptr as *mut Self
+///     }
+/// }
+///
+/// fn main() {
+///     let path: Box<Path> = Box::new_dst_with(3, 3, |i| (i as f32, 0.0));
+///     assert_eq!(path.len, path.points.len());
+///     assert_eq!(path.points[2], (2.0, 0.0));
+/// }
+/// ```
+pub unsafe trait Dst {
+    /// Type of the header.
+    type Header;
+    /// Type of the elements of the slice.
+    type Item;
+
+    /// Casts a pointer to a slice, with the address of the allocation and
+    /// the length of the trailing slice, to a pointer to `Self`.
+    fn from_raw_slice(ptr: *mut [Self::Item]) -> *mut Self;
+}
+
+/// A header of type `H`, followed by a slice of elements of type `T`.
+///
+/// This is a dynamically sized type, which can be allocated with
+/// [`BoxDstExt`], e.g. for data read from a file or the network, where a
+/// header describes a payload.
+///
+/// [`BoxDstExt`]: trait.BoxDstExt.html
+#[repr(C)]
+#[derive(Debug)]
+pub struct HeaderSlice<H, T> {
+    /// The header.
+    pub header: H,
+    /// The trailing slice.
+    pub slice: [T],
+}
+
+unsafe impl<H, T> Dst for HeaderSlice<H, T> {
+    type Header = H;
+    type Item = T;
+
+    #[inline]
+    fn from_raw_slice(ptr: *mut [T]) -> *mut Self {
+        ptr as *mut Self
+    }
+}
+
+/// Extensions to allocate `Box<D>` for types implementing [`Dst`].
+///
+/// The header and the slice are allocated in one allocation, and the result
+/// is a plain `Box`, freed as usual.
+///
+/// [`Dst`]: trait.Dst.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{BoxDstExt, HeaderSlice};
+///
+/// fn main() {
+///     let mut frame: Box<HeaderSlice<u32, u8>> = Box::new_zeroed_dst(1500);
+///     frame.header = 0xcafe;
+///     frame.slice[..5].copy_from_slice(b"hello");
+///     assert_eq!(frame.slice.len(), 1500);
+///     assert_eq!(&frame.slice[..5], b"hello");
+/// }
+/// ```
+pub trait BoxDstExt: Sized {
+    /// Type of the header.
+    type Header;
+    /// Type of the elements of the slice.
+    type Item;
+
+    /// Allocates zeroed memory for a header and `len` trailing elements.
+    ///
+    /// This doesn't actually allocate if both the header and the elements
+    /// are zero-sized.
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the allocation overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_zeroed_dst(len: usize) -> Self
+    where
+        Self::Header: Zero,
+        Self::Item: Zero;
+
+    /// Fallible [`BoxDstExt::new_zeroed_dst`]
+    ///
+    /// [`BoxDstExt::new_zeroed_dst`]: #tymethod.new_zeroed_dst
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the allocation overflows.
+    fn try_new_zeroed_dst(len: usize) -> Option<Self>
+    where
+        Self::Header: Zero,
+        Self::Item: Zero;
+
+    /// Allocates memory for a header and `len` trailing elements, places
+    /// `header` into it, and initializes each element in place with the
+    /// result of `f` called with the element index.
+    ///
+    /// If `f` panics, the header and the elements that were already
+    /// initialized are dropped, and the memory is freed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the allocation overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_dst_with<F: FnMut(usize) -> Self::Item>(header: Self::Header, len: usize, f: F) -> Self;
+
+    /// Fallible [`BoxDstExt::new_dst_with`]
+    ///
+    /// [`BoxDstExt::new_dst_with`]: #tymethod.new_dst_with
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the allocation overflows. `f` is not called in that case.
+    fn try_new_dst_with<F: FnMut(usize) -> Self::Item>(
+        header: Self::Header,
+        len: usize,
+        f: F,
+    ) -> Option<Self>;
+}
+
+// Returns the layout for a header of type `H` followed by `len` elements of
+// type `T`, and the offset of those elements, as for a `#[repr(C)]` struct.
+fn dst_layout<H, T>(len: usize) -> Option<(Layout, usize)> {
+    let tail = Layout::array::<T>(len).ok()?;
+    let (layout, offset) = Layout::new::<H>().extend(tail).ok()?;
+    Some((layout.pad_to_align(), offset))
+}
+
+// Memory for a `D` being initialized, freed if dropped, after dropping the
+// header and the `len` first elements.
+struct DstGuard<D: Dst + ?Sized> {
+    raw: *mut u8,
+    layout: Layout,
+    offset: usize,
+    len: usize,
+    marker: PhantomData<*mut D>,
+}
+
+impl<D: Dst + ?Sized> DstGuard<D> {
+    fn try_alloc(len: usize, zeroed: bool) -> Result<Self, AllocError> {
+        let (layout, offset) =
+            dst_layout::<D::Header, D::Item>(len).ok_or_else(AllocError::capacity_overflow)?;
+        let raw = if layout.size() == 0 {
+            // A dangling, but aligned, pointer.
+            layout.align() as *mut u8
+        } else {
+            unsafe { try_alloc_global(layout, zeroed)?.as_ptr() }
+        };
+        Ok(DstGuard {
+            raw,
+            layout,
+            offset,
+            len: 0,
+            marker: PhantomData,
+        })
+    }
+
+    fn tail(&self) -> *mut D::Item {
+        unsafe { self.raw.add(self.offset) as *mut D::Item }
+    }
+
+    // Turns the memory into a `Box`.
+    unsafe fn finish(self, len: usize) -> Box<D> {
+        let raw = D::from_raw_slice(ptr::slice_from_raw_parts_mut(self.raw as *mut D::Item, len));
+        mem::forget(self);
+        Box::from_raw(raw)
+    }
+}
+
+impl<D: Dst + ?Sized> Drop for DstGuard<D> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.raw as *mut D::Header);
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.tail(), self.len));
+            if self.layout.size() != 0 {
+                poison(self.raw, self.layout.size());
+                dealloc(self.raw, self.layout);
+            }
+        }
+    }
+}
+
+// Writes the header and the results of `f` to `guard`, and returns the box.
+fn fill<D, F>(mut guard: DstGuard<D>, header: D::Header, len: usize, mut f: F) -> Box<D>
+where
+    D: Dst + ?Sized,
+    F: FnMut(usize) -> D::Item,
+{
+    unsafe {
+        ptr::write(guard.raw as *mut D::Header, header);
+        let tail = guard.tail();
+        for i in 0..len {
+            ptr::write(tail.add(i), f(i));
+            guard.len += 1;
+        }
+        guard.finish(len)
+    }
+}
+
+impl<D: Dst + ?Sized> BoxDstExt for Box<D> {
+    type Header = D::Header;
+    type Item = D::Item;
+
+    #[track_caller]
+    fn new_zeroed_dst(len: usize) -> Self
+    where
+        D::Header: Zero,
+        D::Item: Zero,
+    {
+        match DstGuard::<D>::try_alloc(len, true) {
+            Ok(guard) => unsafe { guard.finish(len) },
+            Err(e) => e.handle(),
+        }
+    }
+
+    fn try_new_zeroed_dst(len: usize) -> Option<Self>
+    where
+        D::Header: Zero,
+        D::Item: Zero,
+    {
+        let guard = DstGuard::<D>::try_alloc(len, true).ok()?;
+        Some(unsafe { guard.finish(len) })
+    }
+
+    #[track_caller]
+    fn new_dst_with<F: FnMut(usize) -> D::Item>(header: D::Header, len: usize, f: F) -> Self {
+        match DstGuard::<D>::try_alloc(len, false) {
+            Ok(guard) => fill(guard, header, len, f),
+            Err(e) => e.handle(),
+        }
+    }
+
+    fn try_new_dst_with<F: FnMut(usize) -> D::Item>(
+        header: D::Header,
+        len: usize,
+        f: F,
+    ) -> Option<Self> {
+        let guard = DstGuard::<D>::try_alloc(len, false).ok()?;
+        Some(fill(guard, header, len, f))
+    }
+}
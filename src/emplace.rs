@@ -0,0 +1,224 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::boxed::Box;
+use core::cell::Cell;
+#[cfg(feature = "alloc")]
+use core::mem::MaybeUninit;
+use core::ptr;
+#[cfg(feature = "alloc")]
+use UninitBox;
+
+// Support for `emplace!` and `init!`. These are only public so that the
+// macros can use them.
+
+#[doc(hidden)]
+pub use core::ptr::addr_of_mut as __addr_of_mut;
+
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub use alloc::boxed::Box as __Box;
+
+// Memory for a `T` whose fields are being written, freed if dropped before
+// `finish`.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub struct __Emplace<T>(Box<MaybeUninit<T>>);
+
+#[cfg(feature = "alloc")]
+impl<T> __Emplace<T> {
+    #[track_caller]
+    pub fn alloc() -> Self {
+        __Emplace(UninitBox::new(false).init(MaybeUninit::uninit()))
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.0.as_mut_ptr()
+    }
+
+    /// Turns the memory into a `Box` once all the fields are written, and
+    /// disarms the field guards.
+    ///
+    /// # Safety
+    ///
+    /// All the fields must have been written.
+    pub unsafe fn finish(self, done: &__EmplaceDone) -> Box<T> {
+        done.disarm();
+        Box::from_raw(Box::into_raw(self.0) as *mut T)
+    }
+}
+
+// Whether all the fields were written, shared by the field guards.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct __EmplaceDone(Cell<bool>);
+
+impl __EmplaceDone {
+    /// Returns a guard dropping the field at `ptr` unless all the fields are
+    /// written.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a field of memory from `__Emplace`, holding a
+    /// valid value.
+    pub unsafe fn guard<F>(&self, ptr: *mut F) -> __EmplaceField<'_, F> {
+        __EmplaceField { ptr, done: self }
+    }
+
+    // Marks all the fields as written.
+    pub fn disarm(&self) {
+        self.0.set(true);
+    }
+
+    /// Writes a field, and returns a guard dropping it if a later field
+    /// fails to be written. Writes are unaligned, so that this works for
+    /// fields of packed structs.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a field of memory from `__Emplace`.
+    pub unsafe fn write<F>(&self, ptr: *mut F, value: F) -> __EmplaceField<'_, F> {
+        ptr::write_unaligned(ptr, value);
+        self.guard(ptr)
+    }
+}
+
+#[doc(hidden)]
+pub struct __EmplaceField<'a, F> {
+    ptr: *mut F,
+    done: &'a __EmplaceDone,
+}
+
+impl<'a, F> Drop for __EmplaceField<'a, F> {
+    fn drop(&mut self) {
+        if !self.done.0.get() {
+            unsafe { drop(ptr::read_unaligned(self.ptr)) }
+        }
+    }
+}
+
+/// Allocates a struct on the heap, and writes its fields in place, one by
+/// one.
+///
+/// Contrary to [`BoxExt::new_with`], this guarantees that the struct is
+/// never built on the stack, even in debug builds: only the value of each
+/// field is, before being written directly to the heap. This only applies
+/// to the outermost struct, though: fields are themselves built on the stack.
+///
+/// The syntax is that of a struct expression, prefixed with `Box`, and all
+/// the fields must be given. Fields are evaluated and written in the order
+/// they are given. If evaluating a field panics or returns early, the fields
+/// written so far are dropped, and the memory is freed.
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate boxext;
+///
+/// struct Frame {
+///     header: [u8; 16],
+///     payload: [u8; 65536],
+///     checksum: u32,
+/// }
+///
+/// fn main() {
+///     let frame = emplace!(Box<Frame> {
+///         header: [0xaa; 16],
+///         payload: [0; 65536],
+///         checksum: 0xdeadbeef,
+///     });
+///     assert_eq!(frame.checksum, 0xdeadbeef);
+///
+///     // Fields of tuple structs are given by index.
+///     struct Pair(String, Vec<u8>);
+///     let pair = emplace!(Box<Pair> { 0: "key".to_string(), 1: vec![1, 2, 3] });
+///     assert_eq!(pair.1.len(), 3);
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! emplace {
+    (Box<$name:path> { $($field:tt: $value:expr),* $(,)* }) => {{
+        // Ensure that all the fields are given, once, and that the type is a
+        // struct.
+        #[allow(unreachable_code, unused_variables)]
+        let _check = || -> $name { $name { $($field: loop {}),* } };
+        let done = <$crate::__EmplaceDone as Default>::default();
+        let mut emplace = $crate::__Emplace::<$name>::alloc();
+        let raw = emplace.as_mut_ptr();
+        $(
+            let value = $value;
+            let _guard = unsafe { done.write($crate::__addr_of_mut!((*raw).$field), value) };
+        )*
+        unsafe { emplace.finish(&done) }
+    }};
+}
+
+/// Allocates memory on the heap for a value of a concrete type, places the
+/// value into it, and returns it as a boxed trait object.
+///
+/// The first argument is the type of the trait object, and the second the
+/// expression for the value, which is placed on the heap as with
+/// [`BoxExt::new_with`]. This is equivalent to calling `BoxExt::new_with`
+/// for the concrete type and coercing the result, which type inference
+/// doesn't do on its own when a `Box<dyn Trait>` is expected.
+///
+/// As the expression is evaluated in a closure, `return` and `?` apply to
+/// the closure, not to the enclosing function.
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate boxext;
+///
+/// trait Plugin {
+///     fn name(&self) -> &str;
+/// }
+///
+/// struct Reverb {
+///     buffer: [f32; 65536],
+/// }
+///
+/// impl Plugin for Reverb {
+///     fn name(&self) -> &str {
+///         "reverb"
+///     }
+/// }
+///
+/// fn main() {
+///     let mut plugins: Vec<Box<dyn Plugin>> = Vec::new();
+///     plugins.push(boxed_dyn!(dyn Plugin, Reverb { buffer: [0.0; 65536] }));
+///     assert_eq!(plugins[0].name(), "reverb");
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! boxed_dyn {
+    ($target:ty, $value:expr) => {{
+        let boxed = <$crate::__Box<_> as $crate::BoxExt>::new_with(|| $value);
+        let boxed: $crate::__Box<$target> = boxed;
+        boxed
+    }};
+}
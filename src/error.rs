@@ -0,0 +1,184 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use alloc::collections::TryReserveError;
+use core::alloc::Layout;
+use core::fmt;
+use core::panic::Location;
+#[cfg(feature = "std")]
+use std::error::Error;
+use capacity_overflow;
+use handler::alloc_error;
+
+/// The reason why memory couldn't be allocated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AllocErrorKind {
+    /// The size of the requested memory overflowed.
+    CapacityOverflow,
+    /// The allocator failed to provide the requested memory.
+    AllocFailed,
+    /// The requested memory didn't fit in the current thread's budget.
+    /// See the [`budget`] module.
+    ///
+    /// [`budget`]: budget/index.html
+    #[cfg(feature = "budget")]
+    BudgetExceeded,
+}
+
+/// Error returned when memory couldn't be allocated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError {
+    kind: AllocErrorKind,
+    layout: Option<Layout>,
+    location: &'static Location<'static>,
+}
+
+impl AllocError {
+    #[track_caller]
+    pub(crate) fn capacity_overflow() -> Self {
+        AllocError {
+            kind: AllocErrorKind::CapacityOverflow,
+            layout: None,
+            location: Location::caller(),
+        }
+    }
+
+    #[track_caller]
+    pub(crate) fn alloc_failed(layout: Layout) -> Self {
+        AllocError {
+            kind: AllocErrorKind::AllocFailed,
+            layout: Some(layout),
+            location: Location::caller(),
+        }
+    }
+
+    #[cfg(feature = "budget")]
+    #[track_caller]
+    pub(crate) fn budget_exceeded(layout: Layout) -> Self {
+        AllocError {
+            kind: AllocErrorKind::BudgetExceeded,
+            layout: Some(layout),
+            location: Location::caller(),
+        }
+    }
+
+    /// Returns the reason why memory couldn't be allocated.
+    pub fn kind(&self) -> AllocErrorKind {
+        self.kind
+    }
+
+    /// Returns the layout of the requested memory, if its size didn't
+    /// overflow.
+    pub fn layout(&self) -> Option<Layout> {
+        self.layout
+    }
+
+    /// Returns the location of the call that requested the memory.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    // Panics on capacity overflow, and calls the allocation error handler
+    // otherwise. The error is attributed to the caller, which is expected
+    // to be the call site in the crate's public API.
+    #[track_caller]
+    pub(crate) fn handle(mut self) -> ! {
+        self.location = Location::caller();
+        match self.layout {
+            Some(layout) => alloc_error(layout, self.location),
+            None => capacity_overflow(),
+        }
+    }
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.kind, self.layout) {
+            #[cfg(feature = "budget")]
+            (AllocErrorKind::BudgetExceeded, Some(layout)) => write!(
+                f,
+                "allocation of {} bytes exceeds the thread's budget",
+                layout.size()
+            ),
+            (_, Some(layout)) => write!(f, "memory allocation of {} bytes failed", layout.size()),
+            (_, None) => f.write_str("capacity overflow"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for AllocError {}
+
+/// Either an allocation error from this crate, or one from a standard
+/// collection.
+///
+/// This allows to funnel failures from e.g. `Vec::try_reserve` and from
+/// this crate's fallible functions through a single error type.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::AnyAllocError;
+///
+/// fn copy(data: &[u8]) -> Result<Vec<u8>, AnyAllocError> {
+///     let mut buf = Vec::new();
+///     buf.try_reserve_exact(data.len())?;
+///     buf.extend_from_slice(data);
+///     Ok(buf)
+/// }
+///
+/// fn main() {
+///     assert_eq!(copy(b"data").unwrap(), b"data");
+///
+///     let err = Vec::<u8>::new().try_reserve(usize::MAX).unwrap_err();
+///     match AnyAllocError::from(err) {
+///         AnyAllocError::Reserve(_) => {}
+///         AnyAllocError::Alloc(_) => unreachable!(),
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnyAllocError {
+    /// An error from this crate.
+    Alloc(AllocError),
+    /// An error from a standard collection.
+    Reserve(TryReserveError),
+}
+
+impl From<AllocError> for AnyAllocError {
+    fn from(e: AllocError) -> Self {
+        AnyAllocError::Alloc(e)
+    }
+}
+
+impl From<TryReserveError> for AnyAllocError {
+    fn from(e: TryReserveError) -> Self {
+        AnyAllocError::Reserve(e)
+    }
+}
+
+impl fmt::Display for AnyAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AnyAllocError::Alloc(ref e) => fmt::Display::fmt(e, f),
+            AnyAllocError::Reserve(ref e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for AnyAllocError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            AnyAllocError::Alloc(ref e) => Some(e),
+            AnyAllocError::Reserve(ref e) => Some(e),
+        }
+    }
+}
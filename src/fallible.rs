@@ -0,0 +1,101 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {AllocError, UninitBox, Zero};
+
+/// Extensions to the `Box` type, reporting allocation failures with an
+/// [`AllocError`].
+///
+/// These are equivalent to the `try_*` methods from [`BoxExt`], except they
+/// return an error carrying the reason for the failure and the requested
+/// layout, instead of `None`.
+///
+/// [`AllocError`]: struct.AllocError.html
+/// [`BoxExt`]: trait.BoxExt.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{AllocErrorKind, BoxAllocExt, Zero};
+///
+/// # #[cfg(target_pointer_width = "64")]
+/// struct Huge([u8; 1 << 60]);
+///
+/// # #[cfg(target_pointer_width = "64")]
+/// unsafe impl Zero for Huge {}
+///
+/// fn main() {
+///     let buf: Box<[u8; 4096]> = Box::try_alloc_zeroed().unwrap();
+///     assert_eq!(buf[0], 0);
+///
+/// #   #[cfg(target_pointer_width = "64")]
+/// #   {
+///     let err = Box::<Huge>::try_alloc_zeroed().err().unwrap();
+///     assert_eq!(err.kind(), AllocErrorKind::AllocFailed);
+///     assert_eq!(err.layout().unwrap().size(), 1 << 60);
+///     assert_eq!(err.location().line(), line!() - 3);
+/// #   }
+/// }
+/// ```
+pub trait BoxAllocExt: Sized {
+    /// Type contained inside the `Box`.
+    type Inner;
+
+    /// Fallible `Box::new`, returning an error on allocation failure.
+    fn try_alloc(x: Self::Inner) -> Result<Self, AllocError>;
+
+    /// Fallible [`BoxExt::new_with`], returning an error on allocation
+    /// failure.
+    ///
+    /// `f` is not called if memory couldn't be allocated.
+    ///
+    /// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+    fn try_alloc_with<F: FnOnce() -> Self::Inner>(f: F) -> Result<Self, AllocError>;
+
+    /// Fallible [`BoxExt::new_zeroed`], returning an error on allocation
+    /// failure.
+    ///
+    /// [`BoxExt::new_zeroed`]: trait.BoxExt.html#tymethod.new_zeroed
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for `Self::Inner` types implementing
+    /// the [`Zero`] trait, and not available otherwise. See the definition
+    /// of that trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    fn try_alloc_zeroed() -> Result<Self, AllocError>
+    where
+        Self::Inner: Zero;
+}
+
+impl<T> BoxAllocExt for Box<T> {
+    type Inner = T;
+
+    #[inline]
+    #[track_caller]
+    fn try_alloc(x: T) -> Result<Self, AllocError> {
+        Ok(UninitBox::try_new(false)?.init(x))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn try_alloc_with<F: FnOnce() -> T>(f: F) -> Result<Self, AllocError> {
+        Ok(UninitBox::try_new(false)?.init(f()))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn try_alloc_zeroed() -> Result<Self, AllocError>
+    where
+        T: Zero,
+    {
+        unsafe { Ok(UninitBox::try_new(true)?.assume_init()) }
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::ffi::c_void;
+use core::mem::{self, MaybeUninit};
+use core::{fmt, ptr};
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use {capacity_overflow, new_slice_box, try_new_slice_box, BoxUninitSliceExt};
+
+/// A boxed value handed over to C code, along with its destructor.
+///
+/// This is laid out as the following C structure:
+///
+/// ```c
+/// struct c_owned {
+///     void *ptr;
+///     size_t size;
+///     size_t align;
+///     void (*drop)(void *ptr);
+/// };
+/// ```
+///
+/// C code releases the value with `owned.drop(owned.ptr)`, which drops it
+/// and frees its memory with the allocator it came from. It must not free
+/// `ptr` itself.
+///
+/// When a `COwned` is dropped on the Rust side, the value is released the
+/// same way. On the Rust side, the fields can only be read, through the
+/// accessors, and a `COwned` can only be assembled from its parts with the
+/// unsafe [`COwned::from_raw_parts`].
+///
+/// See [`into_c_owned`] and [`from_c_owned`].
+///
+/// [`COwned::from_raw_parts`]: #method.from_raw_parts
+/// [`into_c_owned`]: fn.into_c_owned.html
+/// [`from_c_owned`]: fn.from_c_owned.html
+#[repr(C)]
+pub struct COwned {
+    ptr: *mut c_void,
+    size: usize,
+    align: usize,
+    drop: unsafe extern "C" fn(*mut c_void),
+}
+
+impl COwned {
+    /// Creates a `COwned` from its parts, e.g. those of a `c_owned`
+    /// structure received from C code.
+    ///
+    /// # Safety
+    ///
+    /// Calling `drop` with `ptr` must be safe, and must release the value,
+    /// which must be `size` bytes large and aligned to `align` bytes. The
+    /// value must not be released in any other way afterwards.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::{from_c_owned, into_c_owned, COwned};
+    ///
+    /// fn main() {
+    ///     let owned = into_c_owned(Box::new(42u32));
+    ///     let (ptr, size, align, drop) = (owned.ptr(), owned.size(), owned.align(), owned.drop_fn());
+    ///     std::mem::forget(owned);
+    ///     let owned = unsafe { COwned::from_raw_parts(ptr, size, align, drop) };
+    ///     assert_eq!(*unsafe { from_c_owned::<u32>(owned) }.ok().unwrap(), 42);
+    /// }
+    /// ```
+    pub unsafe fn from_raw_parts(
+        ptr: *mut c_void,
+        size: usize,
+        align: usize,
+        drop: unsafe extern "C" fn(*mut c_void),
+    ) -> Self {
+        COwned {
+            ptr,
+            size,
+            align,
+            drop,
+        }
+    }
+
+    /// Returns the pointer to the value.
+    #[inline]
+    pub fn ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+
+    /// Returns the size of the value, in bytes.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the alignment of the value, in bytes.
+    #[inline]
+    pub fn align(&self) -> usize {
+        self.align
+    }
+
+    /// Returns the function dropping the value and freeing its memory.
+    #[inline]
+    pub fn drop_fn(&self) -> unsafe extern "C" fn(*mut c_void) {
+        self.drop
+    }
+}
+
+// Destructor handed to C code along with a `Box<T>`.
+unsafe extern "C" fn drop_c_owned<T>(ptr: *mut c_void) {
+    drop(Box::from_raw(ptr as *mut T));
+}
+
+/// Hands a boxed value over to C code.
+///
+/// The returned [`COwned`] carries the destructor to call to release the
+/// value, so that C code never frees it with the wrong allocator. If the
+/// destructor of `T` panics when called from C, the process aborts.
+///
+/// [`COwned`]: struct.COwned.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{from_c_owned, into_c_owned, COwned};
+///
+/// struct Plugin {
+///     name: String,
+/// }
+///
+/// // Stand-in for a C function taking ownership of the value.
+/// extern "C" fn register(owned: COwned) -> COwned {
+///     owned
+/// }
+///
+/// fn main() {
+///     let owned = register(into_c_owned(Box::new(Plugin { name: "foo".into() })));
+///     let plugin = unsafe { from_c_owned::<Plugin>(owned) }.ok().unwrap();
+///     assert_eq!(plugin.name, "foo");
+///
+///     let owned = into_c_owned(Box::new(42u8));
+///     assert!(unsafe { from_c_owned::<u64>(owned) }.is_err());
+/// }
+/// ```
+pub fn into_c_owned<T>(b: Box<T>) -> COwned {
+    COwned {
+        ptr: Box::into_raw(b) as *mut c_void,
+        size: mem::size_of::<T>(),
+        align: mem::align_of::<T>(),
+        drop: drop_c_owned::<T>,
+    }
+}
+
+/// Takes back a boxed value handed over to C code with [`into_c_owned`].
+///
+/// If the size or alignment recorded in `owned` don't match those of `T`,
+/// `owned` is given back.
+///
+/// [`into_c_owned`]: fn.into_c_owned.html
+///
+/// # Safety
+///
+/// `owned` must have been created by [`into_c_owned`] for a `Box<T>`, and
+/// not been released. The size and alignment checks only catch some misuses.
+pub unsafe fn from_c_owned<T>(owned: COwned) -> Result<Box<T>, COwned> {
+    if owned.size != mem::size_of::<T>() || owned.align != mem::align_of::<T>() {
+        return Err(owned);
+    }
+    let result = Box::from_raw(owned.ptr as *mut T);
+    mem::forget(owned);
+    Ok(result)
+}
+
+impl Drop for COwned {
+    fn drop(&mut self) {
+        unsafe { (self.drop)(self.ptr) }
+    }
+}
+
+impl fmt::Debug for COwned {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("COwned")
+            .field("ptr", &self.ptr)
+            .field("size", &self.size)
+            .field("align", &self.align)
+            .finish()
+    }
+}
+
+/// Error returned when the bytes for a `CStr` contain a NUL byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InteriorNulError(usize);
+
+impl InteriorNulError {
+    /// Returns the position of the first NUL byte.
+    pub fn nul_position(&self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Display for InteriorNulError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "nul byte found in provided data at position: {}", self.0)
+    }
+}
+
+impl Error for InteriorNulError {}
+
+// Returns an error with the position of the first NUL byte in `bytes`, if
+// any.
+fn find_nul(bytes: &[u8]) -> Result<(), InteriorNulError> {
+    match bytes.iter().position(|&b| b == 0) {
+        Some(position) => Err(InteriorNulError(position)),
+        None => Ok(()),
+    }
+}
+
+// Turns a boxed byte slice ending with its only NUL byte into a `Box<CStr>`.
+unsafe fn into_c_str_unchecked(bytes: Box<[u8]>) -> Box<CStr> {
+    // The vector's capacity is its length, so this doesn't reallocate.
+    CString::from_vec_with_nul_unchecked(bytes.into_vec()).into_boxed_c_str()
+}
+
+// Turns a NUL-terminated boxed byte slice into a `Box<CStr>`, checking that
+// it contains no other NUL byte.
+fn into_c_str(bytes: Box<[u8]>) -> Result<Box<CStr>, InteriorNulError> {
+    find_nul(&bytes[..bytes.len() - 1])?;
+    Ok(unsafe { into_c_str_unchecked(bytes) })
+}
+
+// Copies `bytes`, which contain no NUL byte, and a NUL terminator into
+// `raw`, which has exactly room for them, and returns the initialized bytes.
+fn copy_c_str(bytes: &[u8], mut raw: Box<[MaybeUninit<u8>]>) -> Box<[u8]> {
+    debug_assert_eq!(raw.len(), bytes.len() + 1);
+    unsafe {
+        let dst = raw.as_mut_ptr() as *mut u8;
+        ptr::copy_nonoverlapping(bytes.as_ptr(), dst, bytes.len());
+        ptr::write(dst.add(bytes.len()), 0);
+        raw.assume_init_slice()
+    }
+}
+
+/// Creates a `Box<CStr>` from bytes, adding the NUL terminator.
+///
+/// Contrary to `CString::new`, which may reallocate to add the terminator,
+/// this allocates the exact amount of memory upfront, and writes the bytes
+/// and the terminator directly to it. The result can be turned into a
+/// `CString` with `CString::from`, without copying.
+///
+/// This returns an error if `bytes` contains a NUL byte, without
+/// allocating.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::boxed_c_str;
+/// use std::ffi::CString;
+///
+/// fn main() {
+///     let name = boxed_c_str(b"libfoo.so").unwrap();
+///     assert_eq!(name.to_bytes_with_nul(), b"libfoo.so\0");
+///     let name = CString::from(name);
+///     assert_eq!(name.as_bytes(), b"libfoo.so");
+///
+///     assert_eq!(boxed_c_str(b"foo\0bar").unwrap_err().nul_position(), 3);
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[track_caller]
+pub fn boxed_c_str(bytes: &[u8]) -> Result<Box<CStr>, InteriorNulError> {
+    find_nul(bytes)?;
+    let raw = BoxUninitSliceExt::new_uninit_slice(bytes.len() + 1);
+    Ok(unsafe { into_c_str_unchecked(copy_c_str(bytes, raw)) })
+}
+
+/// Fallible [`boxed_c_str`]
+///
+/// [`boxed_c_str`]: fn.boxed_c_str.html
+///
+/// This returns `None` if memory couldn't be allocated.
+pub fn try_boxed_c_str(bytes: &[u8]) -> Option<Result<Box<CStr>, InteriorNulError>> {
+    if let Err(e) = find_nul(bytes) {
+        return Some(Err(e));
+    }
+    let raw = BoxUninitSliceExt::try_new_uninit_slice(bytes.len() + 1)?;
+    Some(Ok(unsafe { into_c_str_unchecked(copy_c_str(bytes, raw)) }))
+}
+
+/// Creates a `Box<CStr>` of `len` bytes, not counting the NUL terminator,
+/// filled by `f`.
+///
+/// `f` is given the zeroed bytes to fill, directly in the allocated memory,
+/// and the NUL terminator is already in place after them.
+///
+/// This returns an error if `f` leaves a NUL byte in the bytes it is given.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::boxed_c_str_with;
+///
+/// fn main() {
+///     let path = boxed_c_str_with(8, |buf| buf.copy_from_slice(b"/dev/tty")).unwrap();
+///     assert_eq!(path.to_bytes(), b"/dev/tty");
+///
+///     assert!(boxed_c_str_with(8, |buf| buf[..4].copy_from_slice(b"/dev")).is_err());
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Panics if `len + 1` overflows. Calls [`handle_alloc_error`] if memory
+/// couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[track_caller]
+pub fn boxed_c_str_with<F: FnOnce(&mut [u8])>(
+    len: usize,
+    f: F,
+) -> Result<Box<CStr>, InteriorNulError> {
+    let size = len.checked_add(1).unwrap_or_else(|| capacity_overflow());
+    let mut raw = unsafe { new_slice_box::<u8>(size, true) };
+    f(&mut raw[..len]);
+    into_c_str(raw)
+}
+
+/// Fallible [`boxed_c_str_with`]
+///
+/// [`boxed_c_str_with`]: fn.boxed_c_str_with.html
+///
+/// This returns `None` if memory couldn't be allocated, or if `len + 1`
+/// overflows. `f` is not called in that case.
+pub fn try_boxed_c_str_with<F: FnOnce(&mut [u8])>(
+    len: usize,
+    f: F,
+) -> Option<Result<Box<CStr>, InteriorNulError>> {
+    let mut raw = unsafe { try_new_slice_box::<u8>(len.checked_add(1)?, true).ok()? };
+    f(&mut raw[..len]);
+    Some(into_c_str(raw))
+}
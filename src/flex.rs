@@ -0,0 +1,200 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::marker::PhantomData;
+use core::{fmt, ptr, slice};
+use heap::{dealloc_layout, try_alloc_layout};
+use std::alloc::Layout;
+use {AllocError, Zero};
+
+/// A zeroed heap allocation holding a header of type `H`, followed by a
+/// slice of elements of type `T` with a length chosen at runtime, like a C
+/// struct with a trailing flexible array member.
+///
+/// The slice starts right after the header, at `size_of::<H>()` rounded up
+/// to the alignment of `T`. For a `#[repr(C)]` header ending with a
+/// zero-length array, e.g. `data: [u8; 0]`, this is where that array is as
+/// long as the header has no trailing padding.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{FlexBox, Zero};
+///
+/// #[repr(C)]
+/// struct Message {
+///     kind: u32,
+///     len: u32,
+///     data: [u8; 0],
+/// }
+///
+/// unsafe impl Zero for Message {}
+///
+/// fn main() {
+///     let payload = b"hello";
+///     let mut msg: FlexBox<Message> = FlexBox::new_zeroed(payload.len());
+///     msg.header_mut().kind = 1;
+///     msg.header_mut().len = payload.len() as u32;
+///     msg.tail_mut().copy_from_slice(payload);
+///     assert_eq!(msg.tail(), b"hello");
+///     // `msg.as_mut_ptr()` can be handed over to C code.
+/// }
+/// ```
+pub struct FlexBox<H, T = u8> {
+    ptr: *mut H,
+    len: usize,
+    // Offset of the trailing elements.
+    offset: usize,
+    layout: Layout,
+    marker: PhantomData<(H, T)>,
+}
+
+// Returns the layout for a header of type `H` followed by `len` elements of
+// type `T`, and the offset of those elements.
+fn flex_layout<H, T>(len: usize) -> Option<(Layout, usize)> {
+    let tail = Layout::array::<T>(len).ok()?;
+    let (layout, offset) = Layout::new::<H>().extend(tail).ok()?;
+    Some((layout.pad_to_align(), offset))
+}
+
+impl<H: Zero, T: Zero> FlexBox<H, T> {
+    /// Allocates zeroed memory for a header and `len` trailing elements.
+    ///
+    /// This doesn't actually allocate if both `H` and the trailing elements
+    /// are zero-sized.
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the allocation overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_zeroed(len: usize) -> Self {
+        match FlexBox::try_alloc(len) {
+            Ok(b) => b,
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`FlexBox::new_zeroed`]
+    ///
+    /// [`FlexBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the allocation overflows.
+    pub fn try_new_zeroed(len: usize) -> Option<Self> {
+        FlexBox::try_alloc(len).ok()
+    }
+
+    fn try_alloc(len: usize) -> Result<Self, AllocError> {
+        let (layout, offset) =
+            flex_layout::<H, T>(len).ok_or_else(AllocError::capacity_overflow)?;
+        let ptr = unsafe { try_alloc_layout(layout, true)? } as *mut H;
+        Ok(FlexBox {
+            ptr,
+            len,
+            offset,
+            layout,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<H, T> FlexBox<H, T> {
+    /// Returns the header.
+    #[inline]
+    pub fn header(&self) -> &H {
+        unsafe { &*self.ptr }
+    }
+
+    /// Returns the header, mutably.
+    #[inline]
+    pub fn header_mut(&mut self) -> &mut H {
+        unsafe { &mut *self.ptr }
+    }
+
+    /// Returns the trailing elements.
+    #[inline]
+    pub fn tail(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.tail_ptr(), self.len) }
+    }
+
+    /// Returns the trailing elements, mutably.
+    #[inline]
+    pub fn tail_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.tail_ptr(), self.len) }
+    }
+
+    /// Returns both the header and the trailing elements, mutably.
+    #[inline]
+    pub fn split_mut(&mut self) -> (&mut H, &mut [T]) {
+        unsafe {
+            (
+                &mut *self.ptr,
+                slice::from_raw_parts_mut(self.tail_ptr(), self.len),
+            )
+        }
+    }
+
+    /// Returns the number of trailing elements.
+    #[inline]
+    pub fn tail_len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns a raw pointer to the allocation, e.g. to pass it to C code.
+    #[inline]
+    pub fn as_ptr(&self) -> *const H {
+        self.ptr
+    }
+
+    /// Returns a raw mutable pointer to the allocation, e.g. to pass it to C
+    /// code.
+    #[inline]
+    pub fn as_mut_ptr(&mut self) -> *mut H {
+        self.ptr
+    }
+
+    #[inline]
+    fn tail_ptr(&self) -> *mut T {
+        unsafe { (self.ptr as *mut u8).add(self.offset) as *mut T }
+    }
+}
+
+impl<H, T> Drop for FlexBox<H, T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.ptr);
+            ptr::drop_in_place(self.tail_mut());
+            dealloc_layout(self.ptr as *mut u8, self.layout);
+        }
+    }
+}
+
+unsafe impl<H: Send, T: Send> Send for FlexBox<H, T> {}
+
+unsafe impl<H: Sync, T: Sync> Sync for FlexBox<H, T> {}
+
+impl<H: fmt::Debug, T: fmt::Debug> fmt::Debug for FlexBox<H, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FlexBox")
+            .field("header", self.header())
+            .field("tail", &self.tail())
+            .finish()
+    }
+}
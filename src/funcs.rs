@@ -0,0 +1,244 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Free functions equivalent to the methods of [`BoxExt`] and
+//! [`BoxSliceExt`] for `Box`, which don't require importing those traits.
+//!
+//! The functions are plain wrappers around the trait methods, and behave
+//! the same way. Calling them doesn't depend on how names are resolved
+//! between the traits and future inherent methods of `Box` with the same
+//! names.
+//!
+//! [`BoxExt`]: ../trait.BoxExt.html
+//! [`BoxSliceExt`]: ../trait.BoxSliceExt.html
+//!
+//! # Example
+//!
+//! ```
+//! extern crate boxext;
+//! use boxext::funcs;
+//!
+//! struct Table {
+//!     counts: [u32; 65536],
+//! }
+//!
+//! unsafe impl boxext::Zero for Table {}
+//!
+//! fn main() {
+//!     let table: Box<Table> = funcs::boxed_zeroed();
+//!     assert_eq!(table.counts[0], 0);
+//!
+//!     let squares = funcs::boxed_with(|| {
+//!         let mut squares = [0u64; 1024];
+//!         for (i, s) in squares.iter_mut().enumerate() {
+//!             *s = (i * i) as u64;
+//!         }
+//!         squares
+//!     });
+//!     assert_eq!(squares[3], 9);
+//!
+//! #   #[cfg(feature = "std")]
+//!     let buf: Box<[u8]> = funcs::boxed_zeroed_slice(4096);
+//! #   #[cfg(feature = "std")]
+//!     assert_eq!(buf.len(), 4096);
+//! }
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use BoxSliceExt;
+use {BoxExt, Zero};
+
+/// Allocates memory for a `T`, and places the result of `f` into it.
+///
+/// Equivalent to [`BoxExt::new_with`].
+///
+/// [`BoxExt::new_with`]: ../trait.BoxExt.html#tymethod.new_with
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[inline]
+#[track_caller]
+pub fn boxed_with<T, F: FnOnce() -> T>(f: F) -> Box<T> {
+    BoxExt::new_with(f)
+}
+
+/// Fallible [`boxed_with`]
+///
+/// [`boxed_with`]: fn.boxed_with.html
+///
+/// This returns `None` if memory couldn't be allocated, in which case `f`
+/// is not called.
+#[inline]
+pub fn try_boxed_with<T, F: FnOnce() -> T>(f: F) -> Option<Box<T>> {
+    BoxExt::try_new_with(f)
+}
+
+/// Fallible `Box::new`
+///
+/// Equivalent to [`BoxExt::try_new`].
+///
+/// [`BoxExt::try_new`]: ../trait.BoxExt.html#tymethod.try_new
+///
+/// This returns `None` if memory couldn't be allocated.
+#[inline]
+pub fn try_boxed<T>(x: T) -> Option<Box<T>> {
+    BoxExt::try_new(x)
+}
+
+/// Allocates zeroed memory for a `T`.
+///
+/// Equivalent to [`BoxExt::new_zeroed`].
+///
+/// [`BoxExt::new_zeroed`]: ../trait.BoxExt.html#tymethod.new_zeroed
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[inline]
+#[track_caller]
+pub fn boxed_zeroed<T: Zero>() -> Box<T> {
+    BoxExt::new_zeroed()
+}
+
+/// Fallible [`boxed_zeroed`]
+///
+/// [`boxed_zeroed`]: fn.boxed_zeroed.html
+///
+/// This returns `None` if memory couldn't be allocated.
+#[inline]
+pub fn try_boxed_zeroed<T: Zero>() -> Option<Box<T>> {
+    BoxExt::try_new_zeroed()
+}
+
+/// Allocates memory for a `T`, and places its default value into it.
+///
+/// Equivalent to [`BoxExt::new_default`].
+///
+/// [`BoxExt::new_default`]: ../trait.BoxExt.html#method.new_default
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[inline]
+#[track_caller]
+pub fn boxed_default<T: Default>() -> Box<T> {
+    BoxExt::new_default()
+}
+
+/// Fallible [`boxed_default`]
+///
+/// [`boxed_default`]: fn.boxed_default.html
+///
+/// This returns `None` if memory couldn't be allocated.
+#[inline]
+pub fn try_boxed_default<T: Default>() -> Option<Box<T>> {
+    BoxExt::try_new_default()
+}
+
+/// Allocates memory for a `T`, and places the result of `f` into it, or
+/// returns the error `f` returned.
+///
+/// Equivalent to [`BoxExt::new_with_fallible`].
+///
+/// [`BoxExt::new_with_fallible`]: ../trait.BoxExt.html#tymethod.new_with_fallible
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[inline]
+#[track_caller]
+pub fn boxed_with_fallible<T, E, F: FnOnce() -> Result<T, E>>(f: F) -> Result<Box<T>, E> {
+    BoxExt::new_with_fallible(f)
+}
+
+/// Fallible [`boxed_with_fallible`]
+///
+/// [`boxed_with_fallible`]: fn.boxed_with_fallible.html
+///
+/// This returns `None` if memory couldn't be allocated, in which case `f`
+/// is not called.
+#[inline]
+pub fn try_boxed_with_fallible<T, E, F: FnOnce() -> Result<T, E>>(
+    f: F,
+) -> Option<Result<Box<T>, E>> {
+    BoxExt::try_new_with_fallible(f)
+}
+
+/// Allocates a slice of `len` zeroed elements.
+///
+/// Equivalent to [`BoxSliceExt::new_zeroed_slice`].
+///
+/// [`BoxSliceExt::new_zeroed_slice`]: ../trait.BoxSliceExt.html#tymethod.new_zeroed_slice
+///
+/// # Panics
+///
+/// Panics if the size of the slice overflows. Calls [`handle_alloc_error`]
+/// if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[cfg(feature = "std")]
+#[inline]
+#[track_caller]
+pub fn boxed_zeroed_slice<T: Zero>(len: usize) -> Box<[T]> {
+    BoxSliceExt::new_zeroed_slice(len)
+}
+
+/// Fallible [`boxed_zeroed_slice`]
+///
+/// [`boxed_zeroed_slice`]: fn.boxed_zeroed_slice.html
+///
+/// This returns `None` if memory couldn't be allocated, or if the size of
+/// the slice overflows.
+#[cfg(feature = "std")]
+#[inline]
+pub fn try_boxed_zeroed_slice<T: Zero>(len: usize) -> Option<Box<[T]>> {
+    BoxSliceExt::try_new_zeroed_slice(len)
+}
+
+/// Allocates a slice of `len` elements, initialized in place with the
+/// result of `f` called with each index.
+///
+/// Equivalent to [`BoxSliceExt::new_slice_with`].
+///
+/// [`BoxSliceExt::new_slice_with`]: ../trait.BoxSliceExt.html#tymethod.new_slice_with
+///
+/// # Panics
+///
+/// Panics if the size of the slice overflows. Calls [`handle_alloc_error`]
+/// if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[cfg(feature = "std")]
+#[inline]
+#[track_caller]
+pub fn boxed_slice_with<T, F: FnMut(usize) -> T>(len: usize, f: F) -> Box<[T]> {
+    BoxSliceExt::new_slice_with(len, f)
+}
+
+/// Fallible [`boxed_slice_with`]
+///
+/// [`boxed_slice_with`]: fn.boxed_slice_with.html
+///
+/// This returns `None` if memory couldn't be allocated, or if the size of
+/// the slice overflows. `f` is not called in that case.
+#[cfg(feature = "std")]
+#[inline]
+pub fn try_boxed_slice_with<T, F: FnMut(usize) -> T>(len: usize, f: F) -> Option<Box<[T]>> {
+    BoxSliceExt::try_new_slice_with(len, f)
+}
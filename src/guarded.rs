@@ -0,0 +1,341 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use heap::{charge_budget, dealloc_layout, record_alloc, try_alloc_layout};
+use std::alloc::Layout;
+use {AllocError, Zero};
+
+#[cfg(unix)]
+mod sys {
+    use core::ptr;
+    use libc;
+
+    pub const SUPPORTED: bool = true;
+
+    pub fn page_size() -> usize {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    // Maps `size` bytes of inaccessible memory, and makes the `len` bytes at
+    // `offset` readable and writable. The kernel provides them zeroed.
+    pub unsafe fn map(size: usize, offset: usize, len: usize) -> Option<*mut u8> {
+        let raw = libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_NONE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if raw == libc::MAP_FAILED {
+            return None;
+        }
+        let raw = raw as *mut u8;
+        let prot = libc::PROT_READ | libc::PROT_WRITE;
+        if libc::mprotect(raw.add(offset) as *mut libc::c_void, len, prot) != 0 {
+            unmap(raw, size);
+            return None;
+        }
+        Some(raw)
+    }
+
+    pub unsafe fn unmap(ptr: *mut u8, size: usize) {
+        libc::munmap(ptr as *mut libc::c_void, size);
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use core::ptr;
+    use windows_sys::Win32::System::Memory::{
+        VirtualAlloc, VirtualFree, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_NOACCESS,
+        PAGE_READWRITE,
+    };
+
+    pub const SUPPORTED: bool = true;
+
+    pub fn page_size() -> usize {
+        4096
+    }
+
+    // Reserves `size` bytes of inaccessible memory, and commits the `len`
+    // bytes at `offset` as readable and writable. The kernel provides them
+    // zeroed.
+    pub unsafe fn map(size: usize, offset: usize, len: usize) -> Option<*mut u8> {
+        let raw = VirtualAlloc(ptr::null(), size, MEM_RESERVE, PAGE_NOACCESS) as *mut u8;
+        if raw.is_null() {
+            return None;
+        }
+        let data = raw.add(offset) as *const _;
+        if VirtualAlloc(data, len, MEM_COMMIT, PAGE_READWRITE).is_null() {
+            unmap(raw, size);
+            return None;
+        }
+        Some(raw)
+    }
+
+    pub unsafe fn unmap(ptr: *mut u8, _size: usize) {
+        VirtualFree(ptr as *mut _, 0, MEM_RELEASE);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    pub const SUPPORTED: bool = false;
+
+    pub fn page_size() -> usize {
+        unreachable!()
+    }
+
+    pub unsafe fn map(_size: usize, _offset: usize, _len: usize) -> Option<*mut u8> {
+        unreachable!()
+    }
+
+    pub unsafe fn unmap(_ptr: *mut u8, _size: usize) {
+        unreachable!()
+    }
+}
+
+// Memory surrounded by guard pages, unmapped when dropped.
+struct Mapping {
+    raw: *mut u8,
+    // Size of the mapping, including the guard pages, or 0 if the memory
+    // comes from the allocator.
+    size: usize,
+    layout: Layout,
+}
+
+impl Mapping {
+    // Maps zeroed memory for the given layout, between two guard pages,
+    // and returns the mapping and the address for the value, which ends as
+    // close to the guard page after it as its alignment allows.
+    fn try_new(layout: Layout) -> Result<(Self, *mut u8), AllocError> {
+        if !sys::SUPPORTED || layout.size() == 0 {
+            let raw = unsafe { try_alloc_layout(layout, true)? };
+            return Ok((Mapping { raw, size: 0, layout }, raw));
+        }
+        let page_size = sys::page_size();
+        if layout.align() > page_size {
+            return Err(AllocError::alloc_failed(layout));
+        }
+        let len = layout
+            .size()
+            .checked_add(page_size - 1)
+            .map(|size| size & !(page_size - 1))
+            .ok_or_else(AllocError::capacity_overflow)?;
+        let size = len
+            .checked_add(2 * page_size)
+            .ok_or_else(AllocError::capacity_overflow)?;
+        charge_budget(layout)?;
+        let raw = unsafe { sys::map(size, page_size, len) };
+        record_alloc(layout, true, raw.is_some());
+        let raw = raw.ok_or_else(|| AllocError::alloc_failed(layout))?;
+        let end = page_size + len;
+        let offset = (end - layout.size()) & !(layout.align() - 1);
+        Ok((Mapping { raw, size, layout }, unsafe { raw.add(offset) }))
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            if self.size != 0 {
+                sys::unmap(self.raw, self.size);
+            } else {
+                dealloc_layout(self.raw, self.layout);
+            }
+        }
+    }
+}
+
+/// A heap allocation surrounded by inaccessible guard pages, to catch
+/// buffer overruns in unsafe code.
+///
+/// The memory is obtained with `mmap` on Unix systems, and `VirtualAlloc`
+/// on Windows, with a page that can't be read or written immediately before
+/// it and another one immediately after it. The value is placed at the end
+/// of its pages, as close to the following guard page as its alignment
+/// allows, so that reading or writing past its end faults right away,
+/// instead of silently corrupting other data.
+///
+/// This is meant for debugging: every value takes at least three pages of
+/// address space. On other platforms, the memory comes from the global
+/// allocator, without guard pages.
+///
+/// This dereferences to `T`.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::GuardedBox;
+///
+/// fn main() {
+///     let mut buf: GuardedBox<[u8; 100]> = GuardedBox::new_zeroed();
+///     buf[99] = 1;
+///     // Writing to `buf.as_mut_ptr().add(100)` would crash the program.
+///     assert_eq!(buf.iter().map(|&b| b as usize).sum::<usize>(), 1);
+/// }
+/// ```
+pub struct GuardedBox<T> {
+    ptr: NonNull<T>,
+    mapping: Mapping,
+}
+
+impl<T> GuardedBox<T> {
+    /// Allocates memory between guard pages, and places `x` into it.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new(x: T) -> Self {
+        GuardedBox::new_with(|| x)
+    }
+
+    /// Fallible [`GuardedBox::new`]
+    ///
+    /// [`GuardedBox::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new(x: T) -> Option<Self> {
+        GuardedBox::try_new_with(|| x)
+    }
+
+    /// Allocates memory between guard pages, and places the result of `f`
+    /// into it.
+    ///
+    /// As with [`BoxExt::new_with`], this is expected to avoid copies when
+    /// building with optimization enabled.
+    ///
+    /// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_with<F: FnOnce() -> T>(f: F) -> Self {
+        match Mapping::try_new(Layout::new::<T>()) {
+            Ok((mapping, raw)) => GuardedBox::init(mapping, raw, f),
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`GuardedBox::new_with`]
+    ///
+    /// [`GuardedBox::new_with`]: #method.new_with
+    ///
+    /// This returns `None` if memory couldn't be allocated, in which case
+    /// `f` is not called.
+    pub fn try_new_with<F: FnOnce() -> T>(f: F) -> Option<Self> {
+        let (mapping, raw) = Mapping::try_new(Layout::new::<T>()).ok()?;
+        Some(GuardedBox::init(mapping, raw, f))
+    }
+
+    /// Allocates zeroed memory for a `T`, between guard pages.
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_zeroed() -> Self
+    where
+        T: Zero,
+    {
+        match Mapping::try_new(Layout::new::<T>()) {
+            Ok((mapping, raw)) => GuardedBox::from_mapping(mapping, raw),
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`GuardedBox::new_zeroed`]
+    ///
+    /// [`GuardedBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new_zeroed() -> Option<Self>
+    where
+        T: Zero,
+    {
+        let (mapping, raw) = Mapping::try_new(Layout::new::<T>()).ok()?;
+        Some(GuardedBox::from_mapping(mapping, raw))
+    }
+
+    // Writes the result of `f` at `raw`. The mapping is released if `f`
+    // panics.
+    fn init<F: FnOnce() -> T>(mapping: Mapping, raw: *mut u8, f: F) -> Self {
+        unsafe { ptr::write(raw as *mut T, f()) };
+        GuardedBox::from_mapping(mapping, raw)
+    }
+
+    #[inline]
+    fn from_mapping(mapping: Mapping, raw: *mut u8) -> Self {
+        GuardedBox {
+            ptr: unsafe { NonNull::new_unchecked(raw as *mut T) },
+            mapping,
+        }
+    }
+
+    /// Returns whether the value is surrounded by guard pages. This is
+    /// false on platforms without support for them, and for zero-sized
+    /// types.
+    #[inline]
+    pub fn is_guarded(&self) -> bool {
+        self.mapping.size != 0
+    }
+}
+
+impl<T> Deref for GuardedBox<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for GuardedBox<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for GuardedBox<T> {
+    fn drop(&mut self) {
+        // The mapping is released afterwards, when the field is dropped.
+        unsafe { ptr::drop_in_place(self.ptr.as_ptr()) };
+    }
+}
+
+unsafe impl<T: Send> Send for GuardedBox<T> {}
+
+unsafe impl<T: Sync> Sync for GuardedBox<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for GuardedBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(feature = "alloc")]
+use alloc::alloc::handle_alloc_error;
+#[cfg(all(feature = "allocator_api", not(feature = "alloc")))]
+use allocator_api::handle_alloc_error;
+use core::alloc::Layout;
+use core::fmt;
+#[cfg(any(feature = "alloc", feature = "allocator_api"))]
+use core::mem;
+use core::panic::Location;
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::error::Error;
+
+// The handler set with `set_alloc_error_handler`, as an address, or 0.
+static HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+/// Error returned by [`set_alloc_error_handler`] when a handler was already
+/// set.
+///
+/// [`set_alloc_error_handler`]: fn.set_alloc_error_handler.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SetAllocErrorHandlerError;
+
+impl fmt::Display for SetAllocErrorHandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an allocation error handler was already set")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for SetAllocErrorHandlerError {}
+
+/// Sets the function called when memory couldn't be allocated by the
+/// methods that don't return an error, in place of [`handle_alloc_error`].
+///
+/// The handler is given the layout of the allocation that failed, and the
+/// location of the call that requested it. It must not return: it can panic,
+/// abort, or e.g. reset the device on embedded targets. When no handler is
+/// set, [`handle_alloc_error`] is called.
+///
+/// The handler can only be set once. Otherwise, an error is returned.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::set_alloc_error_handler;
+/// # #[cfg(feature = "std")]
+/// use boxext::BoxSliceExt;
+/// use std::alloc::Layout;
+/// use std::panic::{self, Location};
+///
+/// fn out_of_memory(layout: Layout, location: &'static Location<'static>) -> ! {
+///     panic!("out of memory allocating {} bytes at {}", layout.size(), location)
+/// }
+///
+/// fn main() {
+///     set_alloc_error_handler(out_of_memory).unwrap();
+/// #   #[cfg(feature = "std")]
+/// #   {
+///     let result = panic::catch_unwind(|| {
+///         let _: Box<[u8]> = BoxSliceExt::new_zeroed_slice(isize::MAX as usize);
+///     });
+///     assert!(result.is_err());
+/// #   }
+///     assert!(set_alloc_error_handler(out_of_memory).is_err());
+/// }
+/// ```
+pub fn set_alloc_error_handler(
+    handler: fn(Layout, &'static Location<'static>) -> !,
+) -> Result<(), SetAllocErrorHandlerError> {
+    HANDLER
+        .compare_exchange(0, handler as usize, Ordering::SeqCst, Ordering::SeqCst)
+        .map(|_| ())
+        .map_err(|_| SetAllocErrorHandlerError)
+}
+
+// Returns the handler set with `set_alloc_error_handler`, if any.
+#[cfg(any(feature = "alloc", feature = "allocator_api"))]
+pub(crate) fn alloc_error_handler() -> Option<fn(Layout, &'static Location<'static>) -> !> {
+    match HANDLER.load(Ordering::SeqCst) {
+        0 => None,
+        handler => Some(unsafe {
+            mem::transmute::<usize, fn(Layout, &'static Location<'static>) -> !>(handler)
+        }),
+    }
+}
+
+// Calls the handler set with `set_alloc_error_handler` if any, or
+// `handle_alloc_error` otherwise, for an allocation requested at `location`.
+// Kept out of line, so that it doesn't bloat the functions allocating memory
+// when those are inlined.
+#[cfg(any(feature = "alloc", feature = "allocator_api"))]
+#[cold]
+#[inline(never)]
+pub(crate) fn alloc_error(layout: Layout, location: &'static Location<'static>) -> ! {
+    if let Some(handler) = alloc_error_handler() {
+        handler(layout, location)
+    }
+    handle_alloc_error(layout)
+}
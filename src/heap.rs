@@ -0,0 +1,341 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Allocation functions for memory that the crate deallocates itself, as
+// opposed to memory handed out as a `Box`, which is always deallocated
+// through the global allocator.
+
+use core::alloc::Layout;
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+use {cold, AllocError};
+
+// Byte pattern memory is filled with before deallocation when the
+// `poison_on_free` feature is enabled.
+const POISON: u8 = 0xdd;
+
+#[cfg(all(feature = "std", not(feature = "custom_allocator")))]
+pub(crate) use alloc::alloc::{alloc, alloc_zeroed, dealloc};
+#[cfg(all(feature = "os_alloc", not(feature = "custom_allocator")))]
+use alloc::alloc::realloc;
+
+#[cfg(feature = "custom_allocator")]
+pub(crate) use self::custom::{alloc, alloc_zeroed, dealloc};
+#[cfg(all(feature = "custom_allocator", feature = "os_alloc"))]
+use self::custom::realloc;
+#[cfg(feature = "custom_allocator")]
+pub use self::custom::{set_boxext_allocator, SetAllocatorError};
+
+#[cfg(feature = "custom_allocator")]
+mod custom {
+    use core::fmt;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use std::alloc::{self, GlobalAlloc, Layout};
+    use std::error::Error;
+
+    // The global allocator, as a `GlobalAlloc`.
+    struct Global;
+
+    unsafe impl GlobalAlloc for Global {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            alloc::alloc(layout)
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            alloc::alloc_zeroed(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            alloc::dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            alloc::realloc(ptr, layout, new_size)
+        }
+    }
+
+    // No allocation happened yet, and no allocator was set.
+    const UNSET: usize = 0;
+    // Allocations happened with the global allocator.
+    const GLOBAL: usize = 1;
+    // An allocator is being set.
+    const SETTING: usize = 2;
+    // An allocator was set.
+    const SET: usize = 3;
+
+    static STATE: AtomicUsize = AtomicUsize::new(UNSET);
+    static mut ALLOCATOR: Option<&'static dyn GlobalAlloc> = None;
+
+    /// Error returned by [`set_boxext_allocator`] when the allocator can't be
+    /// changed anymore.
+    ///
+    /// [`set_boxext_allocator`]: fn.set_boxext_allocator.html
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct SetAllocatorError;
+
+    impl fmt::Display for SetAllocatorError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("the boxext allocator can't be changed anymore")
+        }
+    }
+
+    impl Error for SetAllocatorError {}
+
+    /// Sets the allocator used for memory that this crate deallocates itself.
+    ///
+    /// This covers types such as [`AlignedBytes`] and [`AlignedBox`], which
+    /// own their memory.
+    ///
+    /// It does *not* cover values handed out as a `Box`, which always
+    /// deallocates through the global allocator: the `Box`es returned by
+    /// [`BoxExt`], [`BoxSliceExt`] and the functions of the [`funcs`] module,
+    /// among others, are still allocated with the global allocator, whatever
+    /// allocator is set here. To change how those are allocated, use
+    /// `#[global_allocator]`.
+    ///
+    /// The allocator can only be set once, and only before any such memory
+    /// was allocated. Otherwise, an error is returned.
+    ///
+    /// [`AlignedBytes`]: struct.AlignedBytes.html
+    /// [`AlignedBox`]: struct.AlignedBox.html
+    /// [`BoxExt`]: trait.BoxExt.html
+    /// [`BoxSliceExt`]: trait.BoxSliceExt.html
+    /// [`funcs`]: funcs/index.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::{aligned_zeroed_bytes, set_boxext_allocator};
+    /// use std::alloc::{GlobalAlloc, Layout, System};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// struct Accounting(AtomicUsize);
+    ///
+    /// unsafe impl GlobalAlloc for Accounting {
+    ///     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ///         self.0.fetch_add(layout.size(), Ordering::SeqCst);
+    ///         System.alloc(layout)
+    ///     }
+    ///
+    ///     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    ///         self.0.fetch_sub(layout.size(), Ordering::SeqCst);
+    ///         System.dealloc(ptr, layout)
+    ///     }
+    /// }
+    ///
+    /// static HEAP: Accounting = Accounting(AtomicUsize::new(0));
+    ///
+    /// fn main() {
+    ///     set_boxext_allocator(&HEAP).unwrap();
+    ///     let buf = aligned_zeroed_bytes(4096, 64);
+    ///     assert_eq!(HEAP.0.load(Ordering::SeqCst), 4096);
+    ///     drop(buf);
+    ///     assert_eq!(HEAP.0.load(Ordering::SeqCst), 0);
+    ///     assert!(set_boxext_allocator(&HEAP).is_err());
+    /// }
+    /// ```
+    pub fn set_boxext_allocator(
+        allocator: &'static dyn GlobalAlloc,
+    ) -> Result<(), SetAllocatorError> {
+        STATE
+            .compare_exchange(UNSET, SETTING, Ordering::SeqCst, Ordering::SeqCst)
+            .map_err(|_| SetAllocatorError)?;
+        unsafe {
+            ALLOCATOR = Some(allocator);
+        }
+        STATE.store(SET, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn allocator() -> &'static dyn GlobalAlloc {
+        loop {
+            match STATE.compare_exchange(UNSET, GLOBAL, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) | Err(GLOBAL) => return &Global,
+                Err(SET) => return unsafe { ALLOCATOR.unwrap() },
+                Err(_) => {}
+            }
+        }
+    }
+
+    pub(crate) unsafe fn alloc(layout: Layout) -> *mut u8 {
+        allocator().alloc(layout)
+    }
+
+    pub(crate) unsafe fn alloc_zeroed(layout: Layout) -> *mut u8 {
+        allocator().alloc_zeroed(layout)
+    }
+
+    pub(crate) unsafe fn dealloc(ptr: *mut u8, layout: Layout) {
+        allocator().dealloc(ptr, layout)
+    }
+
+    #[cfg(feature = "os_alloc")]
+    pub(crate) unsafe fn realloc(ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        allocator().realloc(ptr, layout, new_size)
+    }
+}
+
+// Allocates memory for the given layout, zeroed if `zeroed` is true,
+// uninitialized otherwise.
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) unsafe fn try_alloc_layout(layout: Layout, zeroed: bool) -> Result<*mut u8, AllocError> {
+    if layout.size() == 0 {
+        // A dangling, but aligned, pointer.
+        return Ok(layout.align() as *mut u8);
+    }
+    Ok(try_alloc_using(layout, zeroed, alloc, alloc_zeroed)?.as_ptr())
+}
+
+// Allocates memory for the given layout, which must not be zero-sized, with
+// `alloc_zeroed` if `zeroed` is true, `alloc` otherwise. The allocation is
+// charged to the budget, retried once if the OOM hook released memory, and
+// recorded in the allocation statistics.
+#[inline]
+#[track_caller]
+pub(crate) unsafe fn try_alloc_using(
+    layout: Layout,
+    zeroed: bool,
+    alloc: unsafe fn(Layout) -> *mut u8,
+    alloc_zeroed: unsafe fn(Layout) -> *mut u8,
+) -> Result<ptr::NonNull<u8>, AllocError> {
+    charge_budget(layout)?;
+    let alloc_once = || {
+        if zeroed {
+            alloc_zeroed(layout)
+        } else {
+            alloc(layout)
+        }
+    };
+    let mut raw = alloc_once();
+    if raw.is_null() && retry_after_oom(layout) {
+        raw = alloc_once();
+    }
+    record_alloc(layout, zeroed, !raw.is_null());
+    match ptr::NonNull::new(raw) {
+        Some(raw) => Ok(raw),
+        None => {
+            cold();
+            Err(AllocError::alloc_failed(layout))
+        }
+    }
+}
+
+// Resizes memory obtained from `try_alloc_layout` for the given layout,
+// which must not be zero-sized, to `new_size` bytes, which must not be zero.
+// Bytes past the original size are left uninitialized. On failure, the
+// original memory is left untouched.
+#[cfg(feature = "os_alloc")]
+#[track_caller]
+pub(crate) unsafe fn try_realloc_layout(
+    ptr: *mut u8,
+    layout: Layout,
+    new_size: usize,
+) -> Result<*mut u8, AllocError> {
+    Ok(try_realloc_using(ptr, layout, new_size, realloc)?.as_ptr())
+}
+
+// Resizes memory for the given layout, which must not be zero-sized, to
+// `new_size` bytes, which must not be zero, with `realloc`. Growth is
+// charged to the budget, and the reallocation is retried once if the OOM
+// hook released memory, and recorded in the allocation statistics.
+#[cfg(feature = "std")]
+#[inline]
+#[track_caller]
+pub(crate) unsafe fn try_realloc_using(
+    ptr: *mut u8,
+    layout: Layout,
+    new_size: usize,
+    realloc: unsafe fn(*mut u8, Layout, usize) -> *mut u8,
+) -> Result<ptr::NonNull<u8>, AllocError> {
+    let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+    if new_size > layout.size() {
+        charge_budget(Layout::from_size_align_unchecked(
+            new_size - layout.size(),
+            layout.align(),
+        ))?;
+    }
+    let mut raw = realloc(ptr, layout, new_size);
+    if raw.is_null() && retry_after_oom(new_layout) {
+        raw = realloc(ptr, layout, new_size);
+    }
+    record_alloc(new_layout, false, !raw.is_null());
+    match ptr::NonNull::new(raw) {
+        Some(raw) => Ok(raw),
+        None => {
+            cold();
+            Err(AllocError::alloc_failed(new_layout))
+        }
+    }
+}
+
+// Deallocates memory obtained from `try_alloc_layout`.
+#[cfg(feature = "std")]
+pub(crate) unsafe fn dealloc_layout(ptr: *mut u8, layout: Layout) {
+    if layout.size() != 0 {
+        poison(ptr, layout.size());
+        dealloc(ptr, layout);
+    }
+}
+
+// Returns whether an allocation the allocator failed to provide should be
+// retried, after the hook from the `oom` module released memory. Always
+// false without the `oom_hook` feature.
+#[cfg(feature = "oom_hook")]
+pub(crate) use oom::run_hook as retry_after_oom;
+
+#[cfg(not(feature = "oom_hook"))]
+#[inline(always)]
+pub(crate) fn retry_after_oom(_layout: Layout) -> bool {
+    false
+}
+
+// Records a request for `layout` in the current thread's allocation
+// statistics. Does nothing without the `instrument` feature.
+#[cfg(feature = "instrument")]
+pub(crate) use stats::record as record_alloc;
+
+#[cfg(not(feature = "instrument"))]
+#[inline(always)]
+pub(crate) fn record_alloc(_layout: Layout, _zeroed: bool, _succeeded: bool) {}
+
+// Deducts the size of an allocation from the current thread's budget, or
+// returns an error if it doesn't fit. Always succeeds without the `budget`
+// feature.
+#[inline]
+#[track_caller]
+pub(crate) fn charge_budget(layout: Layout) -> Result<(), AllocError> {
+    #[cfg(feature = "budget")]
+    {
+        if !::budget::charge(layout.size()) {
+            return Err(AllocError::budget_exceeded(layout));
+        }
+    }
+    let _ = layout;
+    Ok(())
+}
+
+// Overwrites `len` bytes at `ptr` with `byte`, in a way that the compiler
+// can't optimize away, even if the memory is deallocated right after.
+pub(crate) unsafe fn volatile_fill(ptr: *mut u8, len: usize, byte: u8) {
+    for i in 0..len {
+        ptr::write_volatile(ptr.add(i), byte);
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+// Fills memory that is about to be deallocated with a recognizable pattern
+// in debug builds with the `poison_on_free` feature, making use-after-free
+// easier to spot. Does nothing otherwise.
+#[inline]
+pub(crate) unsafe fn poison(ptr: *mut u8, len: usize) {
+    if cfg!(all(feature = "poison_on_free", debug_assertions)) {
+        volatile_fill(ptr, len, POISON);
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Composable initializers, writing values in place.
+//!
+//! An [`Init<T>`] describes how to initialize a `T` directly in the memory
+//! it is going to live in. Contrary to closures returning a `T`, which
+//! don't guarantee the value isn't built on the stack first, initializers
+//! compose: a struct can be initialized field by field with [`init!`], and
+//! an array element by element with [`array`], each field or element being
+//! itself initialized by an initializer. Large nested structures can thus be
+//! built fully in place, whatever the optimization level.
+//!
+//! Any value is an initializer for its own type, which writes it. [`zeroed`]
+//! zeroes the memory instead, and [`from_fn`] wraps a function writing
+//! through a raw pointer.
+//!
+//! Boxes are created from initializers with [`Box::new_init`].
+//!
+//! [`Init<T>`]: trait.Init.html
+//! [`init!`]: ../macro.init.html
+//! [`array`]: fn.array.html
+//! [`zeroed`]: fn.zeroed.html
+//! [`from_fn`]: fn.from_fn.html
+//! [`Box::new_init`]: ../trait.BoxExt.html#method.new_init
+//!
+//! # Example
+//!
+//! ```
+//! #[macro_use]
+//! extern crate boxext;
+//! use boxext::{init, BoxExt};
+//!
+//! struct Channel {
+//!     id: usize,
+//!     samples: [f32; 4096],
+//!     name: String,
+//! }
+//!
+//! struct Mixer {
+//!     channels: [Channel; 64],
+//!     master: [f32; 4096],
+//! }
+//!
+//! fn main() {
+//! #   #[cfg(feature = "std")]
+//! #   {
+//!     let mixer: Box<Mixer> = Box::new_init(init!(Mixer {
+//!         channels: init::array(|id| init!(Channel {
+//!             id: id,
+//!             samples: init::zeroed(),
+//!             name: format!("channel {}", id),
+//!         })),
+//!         master: init::zeroed(),
+//!     }));
+//!     assert_eq!(mixer.channels[42].id, 42);
+//!     assert_eq!(mixer.channels[42].name, "channel 42");
+//!     assert!(mixer.master.iter().all(|&s| s == 0.0));
+//! #   }
+//! }
+//! ```
+
+use core::marker::PhantomData;
+use core::{mem, ptr};
+use {__EmplaceDone, __EmplaceField, Zero};
+
+/// An initializer for a `T`.
+///
+/// # Safety
+///
+/// When [`init`] returns, the memory it was given must hold a valid `T`. If
+/// it panics, the memory must not hold anything that needs to be dropped.
+///
+/// [`init`]: #tymethod.init
+pub unsafe trait Init<T>: Sized {
+    /// Initializes the memory at `slot`.
+    ///
+    /// # Safety
+    ///
+    /// `slot` must be valid for writes, and properly aligned. Whatever it
+    /// holds is overwritten without being dropped.
+    unsafe fn init(self, slot: *mut T);
+}
+
+unsafe impl<T> Init<T> for T {
+    #[inline]
+    unsafe fn init(self, slot: *mut T) {
+        ptr::write(slot, self);
+    }
+}
+
+/// Initializer zeroing memory.
+///
+/// This is created by the [`zeroed`] function.
+///
+/// [`zeroed`]: fn.zeroed.html
+pub struct Zeroed<T>(PhantomData<fn() -> T>);
+
+/// Returns an initializer zeroing memory.
+pub fn zeroed<T: Zero>() -> Zeroed<T> {
+    Zeroed(PhantomData)
+}
+
+unsafe impl<T: Zero> Init<T> for Zeroed<T> {
+    #[inline]
+    unsafe fn init(self, slot: *mut T) {
+        ptr::write_bytes(slot, 0, 1);
+    }
+}
+
+/// Initializer for arrays, element by element.
+///
+/// This is created by the [`array`] function.
+///
+/// [`array`]: fn.array.html
+pub struct Array<F>(F);
+
+/// Returns an initializer for arrays, initializing each element with the
+/// initializer `f` returns for its index.
+///
+/// Elements are initialized in order. If `f` or an initializer panics, the
+/// elements initialized so far are dropped.
+pub fn array<I, F: FnMut(usize) -> I>(f: F) -> Array<F> {
+    Array(f)
+}
+
+// Drops the elements initialized so far if initializing the next one
+// panics.
+struct ArrayGuard<T> {
+    start: *mut T,
+    len: usize,
+}
+
+impl<T> Drop for ArrayGuard<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.start, self.len)) }
+    }
+}
+
+unsafe impl<T, I, F, const N: usize> Init<[T; N]> for Array<F>
+where
+    I: Init<T>,
+    F: FnMut(usize) -> I,
+{
+    unsafe fn init(mut self, slot: *mut [T; N]) {
+        let mut guard = ArrayGuard {
+            start: slot as *mut T,
+            len: 0,
+        };
+        for i in 0..N {
+            (self.0)(i).init(guard.start.add(i));
+            guard.len += 1;
+        }
+        mem::forget(guard);
+    }
+}
+
+/// Initializer calling a function with a raw pointer.
+///
+/// This is created by the [`from_fn`] function.
+///
+/// [`from_fn`]: fn.from_fn.html
+pub struct FromFn<T, F>(F, PhantomData<fn(*mut T)>);
+
+/// Returns an initializer calling `f` with a pointer to the memory to
+/// initialize.
+///
+/// # Safety
+///
+/// `f` must fully initialize the value, unless it panics, in which case it
+/// must not leave anything that needs to be dropped.
+pub unsafe fn from_fn<T, F: FnOnce(*mut T)>(f: F) -> FromFn<T, F> {
+    FromFn(f, PhantomData)
+}
+
+unsafe impl<T, F: FnOnce(*mut T)> Init<T> for FromFn<T, F> {
+    #[inline]
+    unsafe fn init(self, slot: *mut T) {
+        (self.0)(slot)
+    }
+}
+
+impl __EmplaceDone {
+    /// Initializes a field, and returns a guard dropping it if a later field
+    /// fails to be initialized.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a field of the memory being initialized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the field is not properly aligned, e.g. in a packed struct.
+    pub unsafe fn init<F, I: Init<F>>(&self, ptr: *mut F, init: I) -> __EmplaceField<'_, F> {
+        assert_eq!(
+            ptr as usize % mem::align_of::<F>(),
+            0,
+            "init! doesn't support unaligned fields"
+        );
+        init.init(ptr);
+        self.guard(ptr)
+    }
+}
+
+/// Returns an initializer for a struct, initializing its fields one by one.
+///
+/// The syntax is that of a struct expression, where each field is given an
+/// [`Init`] for its type, which can be a plain value. All the fields must be
+/// given. They are evaluated and initialized in the order they are given,
+/// when the struct is initialized. If a field fails to initialize, the
+/// fields initialized so far are dropped.
+///
+/// Packed structs are not supported.
+///
+/// [`Init`]: init/trait.Init.html
+///
+/// See the [`init`] module for an example.
+///
+/// [`init`]: init/index.html
+#[macro_export]
+macro_rules! init {
+    ($name:path { $($field:tt: $value:expr),* $(,)* }) => {{
+        // Ensure that all the fields are given, once, and that the type is a
+        // struct.
+        #[allow(unreachable_code, unused_variables)]
+        let _check = || -> $name { $name { $($field: loop {}),* } };
+        let init = move |slot: *mut $name| {
+            let done = <$crate::__EmplaceDone as Default>::default();
+            $(
+                let value = $value;
+                let _guard = unsafe { done.init($crate::__addr_of_mut!((*slot).$field), value) };
+            )*
+            done.disarm();
+        };
+        unsafe { $crate::init::from_fn(init) }
+    }};
+}
@@ -0,0 +1,139 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::{cmp, mem, ptr, slice};
+use std::io::{self, ErrorKind, Read};
+use {dealloc_slice, try_new_slice_box, try_realloc_slice, AllocError};
+
+// Size of the first buffer.
+const INITIAL_CAPACITY: usize = 8 * 1024;
+
+// Buffer being filled, freed if reading fails.
+struct Buffer {
+    ptr: *mut u8,
+    capacity: usize,
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe { dealloc_slice(self.ptr, self.capacity) }
+    }
+}
+
+impl Buffer {
+    fn resize(&mut self, capacity: usize) -> Result<(), AllocError> {
+        self.ptr = unsafe { try_realloc_slice(self.ptr, self.capacity, capacity)? };
+        self.capacity = capacity;
+        Ok(())
+    }
+}
+
+fn alloc_error(e: AllocError) -> io::Error {
+    io::Error::new(ErrorKind::OutOfMemory, e)
+}
+
+/// Reads all bytes until EOF into an exactly-sized boxed slice.
+///
+/// This is an alternative to `read_to_end` followed by `into_boxed_slice`
+/// that doesn't panic if memory couldn't be allocated: an error of kind
+/// `OutOfMemory` is returned instead, wrapping an [`AllocError`].
+///
+/// The buffer grows geometrically, and is shrunk to the number of bytes read
+/// at the end. Memory is not zeroed when allocated. Each part of the buffer
+/// is only zeroed right before being handed to `reader`, because `Read`
+/// implementations are allowed to read from the buffer they are given.
+///
+/// Errors of kind `Interrupted` are retried. Other errors from `reader` are
+/// returned, and the bytes read so far are lost.
+///
+/// [`AllocError`]: struct.AllocError.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::read_to_boxed_slice;
+///
+/// fn main() {
+///     let data = vec![42u8; 100_000];
+///     let buf = read_to_boxed_slice(&data[..]).unwrap();
+///     assert_eq!(buf.len(), 100_000);
+///     assert_eq!(&buf[..], &data[..]);
+/// }
+/// ```
+pub fn read_to_boxed_slice<R: Read>(mut reader: R) -> io::Result<Box<[u8]>> {
+    let mut buf = Buffer {
+        ptr: ptr::NonNull::dangling().as_ptr(),
+        capacity: 0,
+    };
+    let mut len = 0;
+    loop {
+        if len == buf.capacity {
+            let capacity = if buf.capacity == 0 {
+                INITIAL_CAPACITY
+            } else {
+                buf.capacity
+                    .checked_mul(2)
+                    .ok_or_else(|| alloc_error(AllocError::capacity_overflow()))?
+            };
+            buf.resize(capacity).map_err(alloc_error)?;
+            unsafe { ptr::write_bytes(buf.ptr.add(len), 0, capacity - len) };
+        }
+        let spare = unsafe { slice::from_raw_parts_mut(buf.ptr.add(len), buf.capacity - len) };
+        match reader.read(spare) {
+            Ok(0) => break,
+            Ok(n) => len += cmp::min(n, spare.len()),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if len != buf.capacity {
+        buf.resize(len).map_err(alloc_error)?;
+    }
+    let result = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buf.ptr, len)) };
+    mem::forget(buf);
+    Ok(result)
+}
+
+/// Reads exactly `len` bytes into a boxed slice.
+///
+/// The memory is allocated zeroed, because `Read` implementations are
+/// allowed to read from the buffer they are given, and filled with
+/// `read_exact`, without any intermediate buffer. For large lengths, zeroed
+/// memory usually comes from the operating system without an explicit
+/// zeroing pass.
+///
+/// As with [`read_to_boxed_slice`], an error of kind `OutOfMemory` is
+/// returned if memory couldn't be allocated. Errors from `read_exact` are
+/// returned as is, e.g. `UnexpectedEof` if `reader` has fewer than `len`
+/// bytes.
+///
+/// [`read_to_boxed_slice`]: fn.read_to_boxed_slice.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::read_exact_boxed;
+/// use std::io::{Cursor, ErrorKind};
+///
+/// fn main() {
+///     let image = vec![0x5au8; 8 << 20];
+///     let mut reader = Cursor::new(&image);
+///     let buf = read_exact_boxed(&mut reader, 8 << 20).unwrap();
+///     assert_eq!(&buf[..], &image[..]);
+///
+///     let err = read_exact_boxed(&mut reader, 1).unwrap_err();
+///     assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+/// }
+/// ```
+pub fn read_exact_boxed<R: Read>(mut reader: R, len: usize) -> io::Result<Box<[u8]>> {
+    let mut buf = unsafe { try_new_slice_box::<u8>(len, true).map_err(alloc_error)? };
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
@@ -0,0 +1,198 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use BoxSliceExt;
+use {BoxExt, Zero};
+
+/// Allocates zeroed memory for a `T`, and leaks it, returning a reference
+/// that lives for the rest of the program.
+///
+/// This is the same as `Box::leak(Box::new_zeroed())`, e.g. for tables
+/// built once and used until the program exits. The memory is never freed.
+///
+/// # Safety
+///
+/// This function is only assumed safe for types implementing the [`Zero`]
+/// trait, and not available otherwise. See the definition of that trait.
+///
+/// [`Zero`]: trait.Zero.html
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::leak_zeroed;
+///
+/// fn main() {
+///     let crc_table: &'static mut [u32; 256] = leak_zeroed();
+///     for (n, entry) in crc_table.iter_mut().enumerate() {
+///         let mut c = n as u32;
+///         for _ in 0..8 {
+///             c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+///         }
+///         *entry = c;
+///     }
+///     let crc_table: &'static [u32; 256] = crc_table;
+///     assert_eq!(crc_table[1], 0x77073096);
+/// }
+/// ```
+#[inline]
+#[track_caller]
+pub fn leak_zeroed<T: Zero>() -> &'static mut T {
+    Box::leak(BoxExt::new_zeroed())
+}
+
+/// Fallible [`leak_zeroed`]
+///
+/// [`leak_zeroed`]: fn.leak_zeroed.html
+///
+/// This returns `None` if memory couldn't be allocated.
+#[inline]
+pub fn try_leak_zeroed<T: Zero>() -> Option<&'static mut T> {
+    BoxExt::try_new_zeroed().map(Box::leak)
+}
+
+/// Allocates memory for a `T`, places the result of `f` into it, and leaks
+/// it, returning a reference that lives for the rest of the program.
+///
+/// This is the same as `Box::leak(Box::new_with(f))`. The memory is never
+/// freed, and the value is never dropped.
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[inline]
+#[track_caller]
+pub fn leak_with<T, F: FnOnce() -> T>(f: F) -> &'static mut T {
+    Box::leak(BoxExt::new_with(f))
+}
+
+/// Fallible [`leak_with`]
+///
+/// [`leak_with`]: fn.leak_with.html
+///
+/// This returns `None` if memory couldn't be allocated, in which case `f`
+/// is not called.
+#[inline]
+pub fn try_leak_with<T, F: FnOnce() -> T>(f: F) -> Option<&'static mut T> {
+    BoxExt::try_new_with(f).map(Box::leak)
+}
+
+/// Allocates a slice of `len` zeroed elements, and leaks it, returning a
+/// reference that lives for the rest of the program.
+///
+/// This is the same as `Box::leak(Box::new_zeroed_slice(len))`, e.g. for
+/// the backing store of an interner. The memory is never freed.
+///
+/// # Safety
+///
+/// This function is only assumed safe for types implementing the [`Zero`]
+/// trait, and not available otherwise. See the definition of that trait.
+///
+/// [`Zero`]: trait.Zero.html
+///
+/// # Panics
+///
+/// Panics if the size of the slice overflows. Calls [`handle_alloc_error`]
+/// if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::leak_zeroed_slice;
+///
+/// // Appends strings to a leaked buffer, handing out references that live
+/// // as long as the program.
+/// struct Arena {
+///     buf: &'static mut [u8],
+/// }
+///
+/// impl Arena {
+///     fn intern(&mut self, s: &str) -> Option<&'static str> {
+///         if s.len() > self.buf.len() {
+///             return None;
+///         }
+///         let buf = std::mem::replace(&mut self.buf, &mut []);
+///         let (head, tail) = buf.split_at_mut(s.len());
+///         head.copy_from_slice(s.as_bytes());
+///         self.buf = tail;
+///         std::str::from_utf8(head).ok()
+///     }
+/// }
+///
+/// fn main() {
+///     let mut arena = Arena { buf: leak_zeroed_slice(1 << 16) };
+///     let hello = arena.intern("hello").unwrap();
+///     let world = arena.intern("world").unwrap();
+///     assert_eq!((hello, world), ("hello", "world"));
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[inline]
+#[track_caller]
+pub fn leak_zeroed_slice<T: Zero>(len: usize) -> &'static mut [T] {
+    Box::leak(BoxSliceExt::new_zeroed_slice(len))
+}
+
+/// Fallible [`leak_zeroed_slice`]
+///
+/// [`leak_zeroed_slice`]: fn.leak_zeroed_slice.html
+///
+/// This returns `None` if memory couldn't be allocated, or if the size of
+/// the slice overflows.
+#[cfg(feature = "std")]
+#[inline]
+pub fn try_leak_zeroed_slice<T: Zero>(len: usize) -> Option<&'static mut [T]> {
+    BoxSliceExt::try_new_zeroed_slice(len).map(Box::leak)
+}
+
+/// Allocates a slice of `len` elements, initialized in place with the
+/// result of `f` called with each index, and leaks it, returning a
+/// reference that lives for the rest of the program.
+///
+/// This is the same as `Box::leak(Box::new_slice_with(len, f))`. The memory
+/// is never freed, and the elements are never dropped.
+///
+/// # Panics
+///
+/// Panics if the size of the slice overflows. Calls [`handle_alloc_error`]
+/// if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[cfg(feature = "std")]
+#[inline]
+#[track_caller]
+pub fn leak_slice_with<T, F: FnMut(usize) -> T>(len: usize, f: F) -> &'static mut [T] {
+    Box::leak(BoxSliceExt::new_slice_with(len, f))
+}
+
+/// Fallible [`leak_slice_with`]
+///
+/// [`leak_slice_with`]: fn.leak_slice_with.html
+///
+/// This returns `None` if memory couldn't be allocated, or if the size of
+/// the slice overflows. `f` is not called in that case.
+#[cfg(feature = "std")]
+#[inline]
+pub fn try_leak_slice_with<T, F: FnMut(usize) -> T>(len: usize, f: F) -> Option<&'static mut [T]> {
+    BoxSliceExt::try_new_slice_with(len, f).map(Box::leak)
+}
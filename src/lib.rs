@@ -14,12 +14,12 @@
 //! current (as of writing) shortcomings from `Box::new`:
 //!
 //! * Since Rust 1.12, constructs such as `Box::new([0; 4096])` first create a
-//! temporary object on the stack before copying it into the newly allocated
-//! space (e.g. [issue #50047]).
+//!   temporary object on the stack before copying it into the newly allocated
+//!   space (e.g. [issue #50047]).
 //!
 //! * Constructs such as `Box::new(some_function_call())` first get the result
-//! from the function call on the stack before copying it into the newly
-//! allocated space.
+//!   from the function call on the stack before copying it into the newly
+//!   allocated space.
 //!
 //! [issue #50047]: https://github.com/rust-lang/rust/issues/50047
 //!
@@ -33,52 +33,704 @@
 //! The crates adds the following helper methods to the `Box` type:
 //!
 //! * [`new_with`], which takes a function or closure returning the object that
-//! will be placed in the Box.
+//!   will be placed in the Box.
 //!
 //! * [`new_zeroed`], which creates an object filled with zeroes, possibly
-//! using [`calloc`]/[`HeapAlloc(..., HEAP_ZERO_MEMORY, ...)`]/
-//! [`mallocx(..., MALLOCX_ZERO)`] under the hood.
+//!   using [`calloc`]/[`HeapAlloc(..., HEAP_ZERO_MEMORY, ...)`]/
+//!   [`mallocx(..., MALLOCX_ZERO)`] under the hood.
 //!
 //! * [`try_new`], [`try_new_with`], and [`try_new_zeroed`], which are equivalent
-//! to `new`, `new_with` and `new_zeroed`, but don't panic on allocation
-//! failure.
+//!   to `new`, `new_with` and `new_zeroed`, but don't panic on allocation
+//!   failure.
+//!
+//! * [`new_zeroed_unchecked`] and [`try_new_zeroed_unchecked`], unsafe
+//!   variants of `new_zeroed` for types that can't implement [`Zero`], such
+//!   as FFI types from other crates.
+//!
+//! * [`new_default`] and [`try_new_default`], which place the default value
+//!   of a type in the Box, using zeroed memory for types implementing
+//!   [`ZeroDefault`] with the `specialization` feature.
+//!
+//! * [`new_init`] and [`try_new_init`], which initialize the Box in place
+//!   with composable initializers from the [`init`] module.
+//!
+//! * [`new_patterned`] and [`try_new_patterned`], which fill the Box with a
+//!   byte pattern, to catch reads of memory that was never written.
+//!
+//! * [`BoxAllocExt`], whose methods are equivalent to the `try_*` ones, but
+//!   return an [`AllocError`] describing the failure instead of `None`.
+//!
+//! * [`set_alloc_error_handler`], to choose what happens when the other
+//!   methods fail to allocate memory, e.g. on embedded targets.
+//!
+//! * [`new_zeroed_slice`] and [`try_new_zeroed_slice`], from [`BoxSliceExt`],
+//!   which create boxed slices of a length chosen at runtime, filled with
+//!   zeroes.
+//!
+//! * [`new_slice_with`] and [`try_new_slice_with`], which initialize each
+//!   element of a boxed slice in place.
+//!
+//! * [`new_filled_slice`] and [`try_new_filled_slice`], which fill a boxed
+//!   slice with clones of a value, in place.
+//!
+//! * [`boxed_slice_from_iter`] and [`try_boxed_slice_from_iter`], which
+//!   collect an iterator of known length into a boxed slice in place.
+//!
+//! * [`BoxSliceResizeExt`], which grows boxed slices, zero-filling the new
+//!   elements, or shrinks them, with `realloc`.
+//!
+//! * [`RcExt`] and [`ArcExt`], which provide `new_with`, `new_zeroed` and
+//!   `new_cyclic_with_ptr` for the `Rc` and `Arc` types, and [`RcSliceExt`] and
+//!   [`ArcSliceExt`], which provide `new_zeroed_slice` and
+//!   `try_new_zeroed_slice` for `Rc<[T]>` and `Arc<[T]>`.
+//!
+//! * [`PinBoxExt`], with `pin_with` and `pin_zeroed`, which create a pinned
+//!   `Box` without a temporary on the stack.
+//!
+//! * [`CloneToBox`], with `clone_boxed`, which clones a value directly into
+//!   a new `Box`.
 //!
 //! [`new_with`]: trait.BoxExt.html#tymethod.new_with
 //! [`new_zeroed`]: trait.BoxExt.html#tymethod.new_zeroed
 //! [`try_new`]: trait.BoxExt.html#tymethod.try_new
 //! [`try_new_with`]: trait.BoxExt.html#tymethod.try_new_with
 //! [`try_new_zeroed`]: trait.BoxExt.html#tymethod.try_new_zeroed
+//! [`new_zeroed_slice`]: trait.BoxSliceExt.html#tymethod.new_zeroed_slice
+//! [`try_new_zeroed_slice`]: trait.BoxSliceExt.html#tymethod.try_new_zeroed_slice
+//! [`new_slice_with`]: trait.BoxSliceExt.html#tymethod.new_slice_with
+//! [`try_new_slice_with`]: trait.BoxSliceExt.html#tymethod.try_new_slice_with
+//! [`new_filled_slice`]: trait.BoxSliceExt.html#tymethod.new_filled_slice
+//! [`try_new_filled_slice`]: trait.BoxSliceExt.html#tymethod.try_new_filled_slice
+//! [`new_default`]: trait.BoxExt.html#method.new_default
+//! [`try_new_default`]: trait.BoxExt.html#method.try_new_default
+//! [`new_init`]: trait.BoxExt.html#method.new_init
+//! [`try_new_init`]: trait.BoxExt.html#method.try_new_init
+//! [`new_zeroed_unchecked`]: trait.BoxExt.html#tymethod.new_zeroed_unchecked
+//! [`try_new_zeroed_unchecked`]: trait.BoxExt.html#tymethod.try_new_zeroed_unchecked
+//! [`new_patterned`]: trait.BoxExt.html#method.new_patterned
+//! [`try_new_patterned`]: trait.BoxExt.html#method.try_new_patterned
+//! [`init`]: init/index.html
+//! [`ZeroDefault`]: trait.ZeroDefault.html
+//! [`BoxAllocExt`]: trait.BoxAllocExt.html
+//! [`AllocError`]: struct.AllocError.html
+//! [`set_alloc_error_handler`]: fn.set_alloc_error_handler.html
+//! [`BoxSliceExt`]: trait.BoxSliceExt.html
+//! [`BoxSliceResizeExt`]: trait.BoxSliceResizeExt.html
+//! [`boxed_slice_from_iter`]: fn.boxed_slice_from_iter.html
+//! [`try_boxed_slice_from_iter`]: fn.try_boxed_slice_from_iter.html
+//! [`RcExt`]: trait.RcExt.html
+//! [`ArcExt`]: trait.ArcExt.html
+//! [`RcSliceExt`]: trait.RcSliceExt.html
+//! [`ArcSliceExt`]: trait.ArcSliceExt.html
+//! [`PinBoxExt`]: trait.PinBoxExt.html
+//! [`CloneToBox`]: trait.CloneToBox.html
 //! [`calloc`]: http://pubs.opengroup.org/onlinepubs/009695399/functions/calloc.html
 //! [`HeapAlloc(..., HEAP_ZERO_MEMORY, ...)`]: https://msdn.microsoft.com/en-us/library/windows/desktop/aa366597(v=vs.85).aspx#HEAP_ZERO_MEMORY
 //! [`mallocx(..., MALLOCX_ZERO)`]: http://jemalloc.net/jemalloc.3.html#MALLOCX_ZERO
 //!
+//! The crate also provides a few types and functions built on top of those
+//! helpers:
+//!
+//! * [`BitBox`], a fixed-size bitset stored in zeroed memory.
+//!
+//! * [`BoxBuilder`], which builds an exact-size boxed slice from multiple
+//!   pieces.
+//!
+//! * [`BoxStrBuilder`], which formats a boxed string of bounded length
+//!   without going through a `String`, and [`boxed_format!`], which formats
+//!   a boxed string of the exact length of its content.
+//!
+//! * [`AlignedBytes`], a zeroed byte buffer with an alignment chosen at
+//!   runtime, created with [`aligned_zeroed_bytes`].
+//!
+//! * [`array_init_boxed`], which initializes a boxed array in place, element
+//!   by element, also available as [`BoxArrayExt::new_array_with`], along
+//!   with a `try_` variant.
+//!
+//! * [`emplace!`], which allocates a struct and writes its fields in place,
+//!   one by one, even in debug builds.
+//!
+//! * [`boxed_dyn!`], which places a value of a concrete type in a Box, and
+//!   returns it as a boxed trait object.
+//!
+//! * [`AllocOptions`], which combines allocation knobs such as zeroing and
+//!   over-alignment, producing an [`AlignedBox`].
+//!
+//! * [`ChunkedInit`], which initializes a boxed slice from multiple threads,
+//!   chunk by chunk.
+//!
+//! * [`OutBox`], which holds uninitialized storage to be filled through a raw
+//!   pointer, e.g. by a C function.
+//!
+//! * [`Slab`], a slab allocator reusing memory for values of similar sizes,
+//!   handing out [`SlabBox`]es.
+//!
+//! * [`BoxPool`], which recycles the allocations of boxes of a given type
+//!   instead of freeing them, to be used from a `thread_local!`.
+//!
+//! * [`PinnedHostBox`], a zeroed, page-aligned, buffer pinned with
+//!   user-provided callbacks, for device-visible staging buffers.
+//!
+//! * [`BoxStorageExt`], which drops or forgets a boxed value while keeping
+//!   its allocation, to be refilled with [`BoxUninitExt`], or reused for a
+//!   value of another type with the same layout. [`take_value`] moves the
+//!   value out, keeping the allocation as a [`RawAlloc`].
+//!
+//! * [`BoxUninitExt`], which allocates a `Box<MaybeUninit<T>>` to be
+//!   initialized in stages, and fills it.
+//!
+//! * [`BoxUninitSliceExt`], which allocates a `Box<[MaybeUninit<T>]>` to be
+//!   filled without being zeroed first, and converts it to a `Box<[T]>`.
+//!
+//! * [`into_c_owned`] and [`from_c_owned`], which hand a boxed value over to
+//!   C code along with its destructor, and take it back.
+//!
+//! * The [`raw`] module, which allocates values behind `NonNull` pointers,
+//!   zeroed or in place, for manual memory management.
+//!
+//! * The [`funcs`] module, with free functions such as
+//!   [`funcs::boxed_zeroed`] and [`funcs::boxed_with`], equivalent to the
+//!   trait methods, for call sites that don't import the traits.
+//!
+//! * [`boxed_c_str`] and [`boxed_c_str_with`], which create a `Box<CStr>`,
+//!   writing the bytes and the NUL terminator directly to the heap.
+//!
+//! * [`leak_zeroed`], [`leak_with`] and [`leak_zeroed_slice`], which allocate
+//!   and leak a value or a slice, returning a `&'static mut` reference, for
+//!   tables built once and used until the program exits.
+//!
+//! * [`VecExt`], which zero-fills the spare capacity of a `Vec`, to hand it
+//!   over to functions writing into it, and creates or grows vectors with
+//!   zeroed memory from the allocator.
+//!
+//! * [`BoxWriter`], a writer of bounded capacity into a boxed byte buffer,
+//!   for building messages without reallocations.
+//!
+//! * [`Matrix2D`], a two-dimensional buffer with dimensions chosen at
+//!   runtime, zeroed with optionally padded rows, or initialized in place.
+//!
+//! * [`FlexBox`], a zeroed header followed by a slice of a length chosen at
+//!   runtime, in a single allocation, like a C struct with a flexible array
+//!   member.
+//!
+//! * [`BoxDstExt`], which allocates dynamically sized types made of a header
+//!   followed by a slice, such as [`HeaderSlice`], as a plain `Box`.
+//!
+//! * [`SecureBox`], a heap allocation whose memory is wiped when it is
+//!   dropped, for secrets such as key material, and [`SecureRc`] and
+//!   [`SecureArc`], its reference-counted counterparts, wiped when the last
+//!   reference is dropped.
+//!
+//! * [`Pod`], a marker for plain old data types, whose boxes convert from
+//!   and to boxed byte slices with [`from_bytes_box`] and
+//!   [`into_bytes_box`].
+//!
+//! * [`ZeroOut`], which resets boxed values and slices to zero in place, to
+//!   reuse their allocation, optionally with writes that can't be optimized
+//!   away.
+//!
+//! [`BitBox`]: struct.BitBox.html
+//! [`BoxBuilder`]: struct.BoxBuilder.html
+//! [`BoxStrBuilder`]: struct.BoxStrBuilder.html
+//! [`boxed_format!`]: macro.boxed_format.html
+//! [`emplace!`]: macro.emplace.html
+//! [`boxed_dyn!`]: macro.boxed_dyn.html
+//! [`AlignedBytes`]: struct.AlignedBytes.html
+//! [`aligned_zeroed_bytes`]: fn.aligned_zeroed_bytes.html
+//! [`array_init_boxed`]: fn.array_init_boxed.html
+//! [`BoxArrayExt::new_array_with`]: trait.BoxArrayExt.html#tymethod.new_array_with
+//! [`AllocOptions`]: struct.AllocOptions.html
+//! [`AlignedBox`]: struct.AlignedBox.html
+//! [`ChunkedInit`]: struct.ChunkedInit.html
+//! [`OutBox`]: struct.OutBox.html
+//! [`Slab`]: struct.Slab.html
+//! [`SlabBox`]: struct.SlabBox.html
+//! [`BoxPool`]: struct.BoxPool.html
+//! [`PinnedHostBox`]: struct.PinnedHostBox.html
+//! [`BoxStorageExt`]: trait.BoxStorageExt.html
+//! [`BoxUninitExt`]: trait.BoxUninitExt.html
+//! [`BoxUninitSliceExt`]: trait.BoxUninitSliceExt.html
+//! [`take_value`]: trait.BoxStorageExt.html#tymethod.take_value
+//! [`RawAlloc`]: struct.RawAlloc.html
+//! [`into_c_owned`]: fn.into_c_owned.html
+//! [`from_c_owned`]: fn.from_c_owned.html
+//! [`raw`]: raw/index.html
+//! [`funcs`]: funcs/index.html
+//! [`funcs::boxed_zeroed`]: funcs/fn.boxed_zeroed.html
+//! [`funcs::boxed_with`]: funcs/fn.boxed_with.html
+//! [`boxed_c_str`]: fn.boxed_c_str.html
+//! [`boxed_c_str_with`]: fn.boxed_c_str_with.html
+//! [`leak_zeroed`]: fn.leak_zeroed.html
+//! [`leak_with`]: fn.leak_with.html
+//! [`leak_zeroed_slice`]: fn.leak_zeroed_slice.html
+//! [`VecExt`]: trait.VecExt.html
+//! [`BoxWriter`]: struct.BoxWriter.html
+//! [`Matrix2D`]: struct.Matrix2D.html
+//! [`FlexBox`]: struct.FlexBox.html
+//! [`BoxDstExt`]: trait.BoxDstExt.html
+//! [`HeaderSlice`]: struct.HeaderSlice.html
+//! [`SecureBox`]: struct.SecureBox.html
+//! [`SecureRc`]: struct.SecureRc.html
+//! [`SecureArc`]: struct.SecureArc.html
+//! [`Pod`]: trait.Pod.html
+//! [`from_bytes_box`]: fn.from_bytes_box.html
+//! [`into_bytes_box`]: fn.into_bytes_box.html
+//! [`ZeroOut`]: trait.ZeroOut.html
+//!
 //! ## Features
 //!
 //! * `std` (enabled by default): Uses libstd. Can be disabled to allow use
-//! with `no_std` code, in which case `allocator_api` needs to be enabled.
+//!   with `no_std` code, in which case `alloc` or `allocator_api` needs to
+//!   be enabled, or `static_pool` on targets without a heap.
+//!
+//! * `alloc`: Implement [`BoxExt`] for the `Box` type from liballoc, without
+//!   requiring libstd. Enabled by `std`.
 //!
 //! * `allocator_api`: Add similar helpers to the `Box` type from the
-//! `allocator_api` crate.
+//!   `allocator_api` crate.
+//!
+//! * `allocator_api2`: Implement [`BoxExt`] for the `Box` type from the
+//!   `allocator-api2` crate, for any allocator implementing `Default`, and
+//!   add [`BoxAllocatorExt`], to allocate with a given allocator. This
+//!   doesn't support the `nightly` feature of `allocator-api2`, with which
+//!   its `Box` is the standard library's.
+//!
+//! * `specialization`: Use the nightly-only specialization feature to
+//!   construct small `Copy` types with a plain move in [`new_with`], and
+//!   types implementing [`ZeroDefault`] with zeroed memory in
+//!   [`new_default`].
+//!
+//! * `custom_allocator`: Add [`set_boxext_allocator`], to choose the
+//!   allocator used for memory the crate deallocates itself. This doesn't
+//!   apply to allocations handed out as a `Box`, such as those from
+//!   [`BoxExt`] or [`BoxSliceExt`], which always use the global allocator,
+//!   since `Box` deallocates through it.
+//!
+//! * `testing`: Add the [`testing`] module, with helpers to write tests
+//!   verifying how memory is obtained from the allocator.
+//!
+//! * `verify_in_place`: Add [`testing::assert_in_place`], which checks that
+//!   [`new_with`] and [`try_new_with`] didn't build large values on the
+//!   stack. This makes those functions slower, and is meant for tests.
+//!
+//! * `budget`: Add the [`budget`] module, to bound the number of bytes the
+//!   crate allocates on a thread.
+//!
+//! * `oom_hook`: Add the [`oom`] module, to register hooks invoked when the
+//!   allocator fails to provide memory, e.g. to drop caches before the
+//!   allocation is retried.
+//!
+//! * `instrument`: Add [`stats`], which returns statistics about the memory
+//!   the crate requested on the current thread, such as the number of
+//!   requests and the number of bytes requested, and [`reset_stats`].
+//!
+//! * `poison_on_free`: In debug builds, fill memory the crate deallocates
+//!   itself with `0xdd` bytes right before deallocating it, making
+//!   use-after-free easier to spot. This doesn't cover values handed out as
+//!   a `Box`, which are deallocated by the standard library.
+//!
+//! * `zeroize`: Implement `Zeroize` from the `zeroize` crate for the crate's
+//!   buffer types and [`SecureBox`], and `ZeroizeOnDrop` for [`SecureBox`],
+//!   [`SecureRc`] and [`SecureArc`]. Also add [`zeroize_box`] and
+//!   [`zeroize_boxed_slice`], to wipe boxes of types implementing [`Zero`].
+//!
+//! * `io`: Add [`read_to_boxed_slice`], to read a stream into an
+//!   exactly-sized boxed slice without panicking on allocation failure, and
+//!   [`read_exact_boxed`], to read a given number of bytes into a boxed
+//!   slice.
+//!
+//! * `os_alloc`: Add [`OsBox`], a zeroed allocation obtained directly from
+//!   the operating system when it is large, avoiding the cost of zeroing
+//!   memory that the allocator may incur, and optionally backed by huge
+//!   pages. Its slices can grow without copying with [`OsBox::grow`]. Also
+//!   add [`SecureBox::new_locked_zeroed`], to lock secrets in physical
+//!   memory, and [`AllocOptions::huge_pages`].
+//!
+//! * `numa`: On Linux, add [`OsBox::new_zeroed_on_node`] and
+//!   [`OsBox::new_zeroed_slice_on_node`], to bind large zeroed allocations
+//!   to a NUMA node, and [`AllocOptions::on_node`]. Implies `os_alloc`.
+//!
+//! * `shm`: Add [`ShmBox`], a zeroed value in a named shared memory segment,
+//!   which other processes can map, and the [`AnyBitPattern`] trait its
+//!   values must implement.
+//!
+//! * `guard_pages`: Add [`GuardedBox`], an allocation surrounded by
+//!   inaccessible guard pages, so that buffer overruns in unsafe code fault
+//!   immediately. This is meant for debugging.
+//!
+//! * `jemalloc`: Add [`assume_global_allocator_is_jemalloc`], which makes
+//!   zeroed boxes allocated with jemalloc's [`mallocx(..., MALLOCX_ZERO)`]
+//!   directly, for programs whose global allocator is `tikv-jemallocator`.
+//!
+//! * `windows_heap`: Add [`assume_global_allocator_is_system`], which makes
+//!   zeroed boxes allocated with [`HeapAlloc(..., HEAP_ZERO_MEMORY, ...)`]
+//!   directly on Windows, for programs whose global allocator is the
+//!   standard library's `System` allocator.
+//!
+//! * `wasm_pages`: On WebAssembly, skip zeroing large zeroed boxes when they
+//!   are allocated in freshly grown linear memory, which is already zeroed.
+//!   This works with any global allocator, but not with shared memory.
+//!
+//! * `static_pool`: Add [`PoolBox`], a value allocated from a fixed-size
+//!   arena declared in a static with [`static_arena!`], for targets without
+//!   a heap. This doesn't require `alloc`.
+//!
+//! * `serde`: Add the [`serde`] module, to deserialize large values directly
+//!   into a `Box`.
+//!
+//! * `bytemuck`: Add [`BoxZeroableExt`], to allocate zeroed boxes of types
+//!   implementing `Zeroable` from the `bytemuck` crate.
+//!
+//! * `libc`: Implement [`Zero`] for the structs from the `libc` crate that
+//!   are conventionally zero-initialized, such as `sockaddr_storage`,
+//!   `stat`, `termios` or `epoll_event`.
+//!
+//! * `windows`: Implement [`Zero`] for the structs from the `windows-sys`
+//!   crate that are conventionally zero-initialized, such as `OVERLAPPED`,
+//!   `WSADATA`, `SECURITY_ATTRIBUTES` or `STARTUPINFOW`.
+//!
+//! [`BoxExt`]: trait.BoxExt.html
+//! [`BoxAllocatorExt`]: trait.BoxAllocatorExt.html
+//! [`set_boxext_allocator`]: fn.set_boxext_allocator.html
+//! [`testing`]: testing/index.html
+//! [`testing::assert_in_place`]: testing/fn.assert_in_place.html
+//! [`budget`]: budget/index.html
+//! [`oom`]: oom/index.html
+//! [`stats`]: fn.stats.html
+//! [`reset_stats`]: fn.reset_stats.html
+//! [`zeroize_box`]: fn.zeroize_box.html
+//! [`zeroize_boxed_slice`]: fn.zeroize_boxed_slice.html
+//! [`read_to_boxed_slice`]: fn.read_to_boxed_slice.html
+//! [`read_exact_boxed`]: fn.read_exact_boxed.html
+//! [`AllocOptions::huge_pages`]: struct.AllocOptions.html#method.huge_pages
+//! [`AllocOptions::on_node`]: struct.AllocOptions.html#method.on_node
+//! [`OsBox`]: struct.OsBox.html
+//! [`OsBox::grow`]: struct.OsBox.html#method.grow
+//! [`OsBox::new_zeroed_on_node`]: struct.OsBox.html#method.new_zeroed_on_node
+//! [`OsBox::new_zeroed_slice_on_node`]: struct.OsBox.html#method.new_zeroed_slice_on_node
+//! [`ShmBox`]: struct.ShmBox.html
+//! [`AnyBitPattern`]: trait.AnyBitPattern.html
+//! [`assume_global_allocator_is_jemalloc`]: fn.assume_global_allocator_is_jemalloc.html
+//! [`assume_global_allocator_is_system`]: fn.assume_global_allocator_is_system.html
+//! [`PoolBox`]: struct.PoolBox.html
+//! [`static_arena!`]: macro.static_arena.html
+//! [`GuardedBox`]: struct.GuardedBox.html
+//! [`SecureBox::new_locked_zeroed`]: struct.SecureBox.html#method.new_locked_zeroed
+//! [`serde`]: serde/index.html
+//! [`BoxZeroableExt`]: trait.BoxZeroableExt.html
+//! [`Zero`]: trait.Zero.html
 
 #![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "specialization", feature(specialization))]
+#![cfg_attr(feature = "specialization", allow(incomplete_features))]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::alloc::{alloc, dealloc, Layout};
+#[cfg(all(
+    feature = "alloc",
+    not(any(feature = "jemalloc", feature = "windows_heap", feature = "wasm_pages"))
+))]
+use alloc::alloc::alloc_zeroed;
+#[cfg(any(feature = "jemalloc", feature = "windows_heap", feature = "wasm_pages"))]
+use native::alloc_zeroed;
+#[cfg(feature = "std")]
+use alloc::alloc::realloc;
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::boxed::Box;
+#[cfg(all(feature = "alloc", boxext_std_new_uninit))]
+use handler::alloc_error_handler;
+#[cfg(feature = "alloc")]
+use heap::{poison, try_alloc_using};
 #[cfg(feature = "std")]
-use std::alloc::{handle_alloc_error, alloc, alloc_zeroed, Layout};
+use heap::try_realloc_using;
 
 #[cfg(feature = "allocator_api")]
 extern crate allocator_api;
 
+#[cfg(feature = "allocator_api2")]
+extern crate allocator_api2;
+
+#[cfg(feature = "zeroize")]
+extern crate zeroize;
+
+#[cfg(feature = "bytemuck")]
+extern crate bytemuck;
+
+#[cfg(any(
+    feature = "libc",
+    all(feature = "os_alloc", unix),
+    all(feature = "shm", unix),
+    all(feature = "guard_pages", unix)
+))]
+extern crate libc;
+
+#[cfg(feature = "serde")]
+extern crate serde as serde_crate;
+
+#[cfg(feature = "jemalloc")]
+extern crate tikv_jemalloc_sys;
+
+#[cfg(all(
+    any(
+        feature = "windows",
+        feature = "os_alloc",
+        feature = "windows_heap",
+        feature = "shm",
+        feature = "guard_pages"
+    ),
+    windows
+))]
+extern crate windows_sys;
+
 #[cfg(feature = "std")]
 extern crate core;
 
-#[cfg(feature = "std")]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+use core::arch;
+use core::cell::{Cell, UnsafeCell};
+use core::marker::{PhantomData, PhantomPinned};
+#[cfg(any(feature = "alloc", feature = "allocator_api"))]
+use core::mem;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::num::Wrapping;
 use core::ptr;
+use core::sync::atomic;
+
+#[cfg(feature = "allocator_api2")]
+mod allocator_api2_box;
+#[cfg(feature = "allocator_api2")]
+pub use allocator_api2_box::*;
 
 #[cfg(feature = "allocator_api")]
 mod allocator_box;
 #[cfg(feature = "allocator_api")]
 pub use allocator_box::*;
 
+#[cfg(feature = "std")]
+mod aligned;
+#[cfg(feature = "std")]
+pub use aligned::*;
+
+#[cfg(feature = "std")]
+mod array;
+#[cfg(feature = "std")]
+pub use array::*;
+
+#[cfg(feature = "std")]
+mod bit_box;
+#[cfg(feature = "std")]
+pub use bit_box::*;
+
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "std")]
+pub use builder::*;
+
+#[cfg(feature = "budget")]
+pub mod budget;
+
+#[cfg(feature = "std")]
+mod chunked;
+#[cfg(feature = "std")]
+pub use chunked::*;
+
+#[cfg(feature = "alloc")]
+mod clone;
+#[cfg(feature = "alloc")]
+pub use clone::*;
+
+mod default;
+#[cfg(feature = "alloc")]
+use default::BoxedDefault;
+pub use default::ZeroDefault;
+
+#[cfg(feature = "alloc")]
+mod dst;
+#[cfg(feature = "alloc")]
+pub use dst::*;
+
+mod emplace;
+pub use emplace::*;
+
+#[cfg(feature = "alloc")]
+mod error;
+#[cfg(feature = "alloc")]
+pub use error::*;
+
+#[cfg(feature = "std")]
+mod fallible;
+#[cfg(feature = "std")]
+pub use fallible::*;
+
+#[cfg(feature = "std")]
+mod ffi;
+#[cfg(feature = "std")]
+pub use ffi::*;
+
+#[cfg(feature = "std")]
+mod flex;
+#[cfg(feature = "std")]
+pub use flex::*;
+
+#[cfg(feature = "alloc")]
+pub mod funcs;
+
+#[cfg(feature = "guard_pages")]
+mod guarded;
+#[cfg(feature = "guard_pages")]
+pub use guarded::*;
+
+mod handler;
+pub use handler::*;
+
+#[cfg(feature = "alloc")]
+mod heap;
+#[cfg(feature = "custom_allocator")]
+pub use heap::{set_boxext_allocator, SetAllocatorError};
+
+pub mod init;
+use init::Init;
+
+#[cfg(feature = "io")]
+mod io;
+#[cfg(feature = "io")]
+pub use io::*;
+
+#[cfg(feature = "alloc")]
+mod leak;
+#[cfg(feature = "alloc")]
+pub use leak::*;
+
+#[cfg(feature = "std")]
+mod matrix;
+#[cfg(feature = "std")]
+pub use matrix::*;
+
+#[cfg(any(feature = "jemalloc", feature = "windows_heap", feature = "wasm_pages"))]
+mod native;
+#[cfg(feature = "jemalloc")]
+pub use native::assume_global_allocator_is_jemalloc;
+#[cfg(feature = "windows_heap")]
+pub use native::assume_global_allocator_is_system;
+
+#[cfg(feature = "oom_hook")]
+pub mod oom;
+
+#[cfg(feature = "std")]
+mod options;
+#[cfg(feature = "std")]
+pub use options::*;
+
+#[cfg(feature = "os_alloc")]
+mod os_box;
+#[cfg(feature = "os_alloc")]
+pub use os_box::*;
+
+#[cfg(feature = "std")]
+mod out_box;
+#[cfg(feature = "std")]
+pub use out_box::*;
+
+#[cfg(feature = "std")]
+mod pin;
+#[cfg(feature = "std")]
+pub use pin::*;
+
+#[cfg(feature = "std")]
+mod pinned;
+#[cfg(feature = "std")]
+pub use pinned::*;
+
+mod pod;
+pub use pod::*;
+
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(feature = "std")]
+pub use pool::*;
+
+#[cfg(feature = "alloc")]
+pub mod raw;
+
+#[cfg(feature = "std")]
+mod rc;
+#[cfg(feature = "std")]
+pub use rc::*;
+
+#[cfg(feature = "std")]
+mod secure;
+#[cfg(feature = "std")]
+pub use secure::*;
+
+#[cfg(feature = "serde")]
+pub mod serde;
+
+#[cfg(feature = "shm")]
+mod shm;
+#[cfg(feature = "shm")]
+pub use shm::*;
+
+#[cfg(feature = "std")]
+mod slab;
+#[cfg(feature = "std")]
+pub use slab::*;
+
+#[cfg(feature = "std")]
+mod slice;
+#[cfg(feature = "std")]
+pub use slice::*;
+
+#[cfg(feature = "static_pool")]
+mod static_pool;
+#[cfg(feature = "static_pool")]
+pub use static_pool::*;
+
+#[cfg(feature = "instrument")]
+mod stats;
+#[cfg(feature = "instrument")]
+pub use stats::*;
+
+#[cfg(feature = "std")]
+mod str_builder;
+#[cfg(feature = "std")]
+pub use str_builder::*;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "std")]
+mod uninit;
+#[cfg(feature = "std")]
+pub use uninit::*;
+
+#[cfg(feature = "std")]
+mod vec;
+#[cfg(feature = "std")]
+pub use vec::*;
+
+#[cfg(all(feature = "std", feature = "zeroize"))]
+mod wipe;
+#[cfg(all(feature = "std", feature = "zeroize"))]
+pub use wipe::*;
+
+#[cfg(feature = "std")]
+mod writer;
+#[cfg(feature = "std")]
+pub use writer::*;
+
+#[cfg(feature = "alloc")]
+mod zero_out;
+#[cfg(feature = "alloc")]
+pub use zero_out::*;
+
+#[cfg(all(feature = "std", feature = "bytemuck"))]
+mod zeroable;
+#[cfg(all(feature = "std", feature = "bytemuck"))]
+pub use zeroable::*;
+
 /// Extensions to the `Box` type
 pub trait BoxExt {
     /// Type contained inside the `Box`.
@@ -132,6 +784,36 @@ pub trait BoxExt {
     ///     assert_eq!(*buf, Foo::default());
     /// }
     /// ```
+    ///
+    /// If `f` panics, the memory is freed without dropping anything.
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxExt;
+    /// use std::panic;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// struct Noisy([u8; 64]);
+    ///
+    /// impl Drop for Noisy {
+    ///     fn drop(&mut self) {
+    ///         DROPPED.fetch_add(1, Ordering::SeqCst);
+    ///     }
+    /// }
+    ///
+    /// fn build() -> Noisy {
+    ///     panic!("failed to build")
+    /// }
+    ///
+    /// fn main() {
+    /// #   #[cfg(feature = "std")]
+    ///     assert!(panic::catch_unwind(|| Box::new_with(build)).is_err());
+    /// #   #[cfg(feature = "std")]
+    ///     assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+    /// }
+    /// ```
     fn new_with<F: FnOnce() -> Self::Inner>(f: F) -> Self;
 
     /// Allocates zeroed memory on the heap.
@@ -147,6 +829,11 @@ pub trait BoxExt {
     /// [`HeapAlloc(..., HEAP_ZERO_MEMORY, ...)`]: https://msdn.microsoft.com/en-us/library/windows/desktop/aa366597(v=vs.85).aspx#HEAP_ZERO_MEMORY
     /// [`mallocx(..., MALLOCX_ZERO)`]: http://jemalloc.net/jemalloc.3.html#MALLOCX_ZERO
     ///
+    /// Since Rust 1.92, `Box` has an inherent `new_zeroed` method returning a
+    /// `Box<MaybeUninit<T>>`, which takes precedence over this one when
+    /// calling `Box::new_zeroed()`. Call it through the trait instead, as in
+    /// `BoxExt::new_zeroed()`.
+    ///
     /// # Example
     ///
     /// ```
@@ -156,7 +843,7 @@ pub trait BoxExt {
     /// fn main() {
     ///     // equivalent to `Box::new([0usize; 32])`
     /// #   #[cfg(feature = "std")]
-    ///     let buf: Box<[usize; 32]> = Box::new_zeroed();
+    ///     let buf: Box<[usize; 32]> = BoxExt::new_zeroed();
     /// #   #[cfg(feature = "std")]
     ///     assert_eq!(*buf, [0usize; 32]);
     /// }
@@ -244,6 +931,36 @@ pub trait BoxExt {
     ///     assert_eq!(*buf, Foo::default());
     /// }
     /// ```
+    ///
+    /// If `f` panics, the memory is freed without dropping anything.
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxExt;
+    /// use std::panic;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// struct Noisy([u8; 64]);
+    ///
+    /// impl Drop for Noisy {
+    ///     fn drop(&mut self) {
+    ///         DROPPED.fetch_add(1, Ordering::SeqCst);
+    ///     }
+    /// }
+    ///
+    /// fn build() -> Noisy {
+    ///     panic!("failed to build")
+    /// }
+    ///
+    /// fn main() {
+    /// #   #[cfg(feature = "std")]
+    ///     assert!(panic::catch_unwind(|| Box::try_new_with(build)).is_err());
+    /// #   #[cfg(feature = "std")]
+    ///     assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+    /// }
+    /// ```
     fn try_new_with<F: FnOnce() -> Self::Inner>(f: F) -> Option<Self>
     where
         Self: Sized;
@@ -280,67 +997,689 @@ pub trait BoxExt {
     where
         Self: Sized,
         Self::Inner: Zero;
-}
-
-#[cfg(feature = "std")]
-unsafe fn try_new_box<T>(zeroed: bool) -> Result<Box<T>, Layout> {
-    let layout = Layout::new::<T>();
-    let raw = if layout.size() == 0 {
-        ptr::NonNull::<T>::dangling().as_ptr()
-    } else if zeroed {
-        alloc_zeroed(layout) as *mut T
-    } else {
-        alloc(layout) as *mut T
-    };
-    if !raw.is_null() {
-        Ok(Box::from_raw(raw))
-    } else {
-        Err(layout)
-    }
-}
 
-#[cfg(feature = "std")]
-unsafe fn new_box<T>(zeroed: bool) -> Box<T> {
-    try_new_box::<T>(zeroed).unwrap_or_else(|l| handle_alloc_error(l))
-}
+    /// Same as [`BoxExt::new_zeroed`], without requiring `Self::Inner` to
+    /// implement [`Zero`].
+    ///
+    /// This is meant for types that can't implement [`Zero`], such as types
+    /// from other crates, e.g. FFI structs from `-sys` crates.
+    ///
+    /// [`BoxExt::new_zeroed`]: #tymethod.new_zeroed
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxExt;
+    ///
+    /// // Stand-in for a struct from a `-sys` crate.
+    /// #[repr(C)]
+    /// struct stat {
+    ///     st_dev: u64,
+    ///     st_ino: u64,
+    ///     st_mode: u32,
+    /// }
+    ///
+    /// fn main() {
+    /// #   #[cfg(feature = "std")]
+    ///     let buf: Box<stat> = unsafe { BoxExt::new_zeroed_unchecked() };
+    /// #   #[cfg(feature = "std")]
+    ///     assert_eq!(buf.st_mode, 0);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// A value full of zeroes must be a valid value of `Self::Inner`. See the
+    /// definition of the [`Zero`] trait.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    unsafe fn new_zeroed_unchecked() -> Self
+    where
+        Self: Sized;
 
-#[cfg(feature = "std")]
-impl<T> BoxExt for Box<T> {
-    type Inner = T;
+    /// Fallible [`Box::new_zeroed_unchecked`]
+    ///
+    /// [`Box::new_zeroed_unchecked`]: #tymethod.new_zeroed_unchecked
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    ///
+    /// # Safety
+    ///
+    /// A value full of zeroes must be a valid value of `Self::Inner`. See the
+    /// definition of the [`Zero`] trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    unsafe fn try_new_zeroed_unchecked() -> Option<Self>
+    where
+        Self: Sized;
 
+    /// Allocates memory on the heap and then places the result of `f` into
+    /// it, if `f` succeeds.
+    ///
+    /// If `f` returns an error, the memory is freed and the error is
+    /// returned.
+    ///
+    /// This doesn't actually allocate if `Self::Inner` is zero-sized.
+    ///
+    /// When building with optimization enabled, this is expected to avoid
+    /// copies, as with [`new_with`].
+    ///
+    /// [`new_with`]: #tymethod.new_with
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxExt;
+    /// use std::num::ParseIntError;
+    ///
+    /// struct Table([u32; 1024]);
+    ///
+    /// fn parse(input: &str) -> Result<Table, ParseIntError> {
+    ///     let mut table = Table([0; 1024]);
+    ///     for (i, field) in input.split(',').enumerate() {
+    ///         table.0[i] = field.parse()?;
+    ///     }
+    ///     Ok(table)
+    /// }
+    ///
+    /// fn main() {
+    /// #   #[cfg(feature = "std")]
+    ///     let table = Box::new_with_fallible(|| parse("1,2,3")).unwrap();
+    /// #   #[cfg(feature = "std")]
+    ///     assert_eq!(table.0[..4], [1, 2, 3, 0]);
+    /// #   #[cfg(feature = "std")]
+    ///     assert!(Box::new_with_fallible(|| parse("1,x")).is_err());
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_with_fallible<E, F: FnOnce() -> Result<Self::Inner, E>>(f: F) -> Result<Self, E>
+    where
+        Self: Sized;
+
+    /// Fallible [`Box::new_with_fallible`]
+    ///
+    /// [`Box::new_with_fallible`]: #tymethod.new_with_fallible
+    ///
+    /// This returns `None` if memory couldn't be allocated, in which case
+    /// `f` is not called.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxExt;
+    ///
+    /// fn main() {
+    /// #   #[cfg(feature = "std")]
+    ///     let buf = Box::try_new_with_fallible(|| "42".parse::<u64>());
+    /// #   #[cfg(feature = "std")]
+    ///     assert_eq!(*buf.unwrap().unwrap(), 42);
+    /// }
+    /// ```
+    fn try_new_with_fallible<E, F: FnOnce() -> Result<Self::Inner, E>>(
+        f: F,
+    ) -> Option<Result<Self, E>>
+    where
+        Self: Sized;
+
+    /// Allocates memory on the heap and lets `f` initialize it in place.
+    ///
+    /// Contrary to [`new_with`], which relies on the optimizer to avoid
+    /// copies, `f` is given a reference to the heap memory, which guarantees
+    /// that the value is built in place, even in debug builds.
+    ///
+    /// If `f` panics, the memory is freed.
+    ///
+    /// [`new_with`]: #tymethod.new_with
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxExt;
+    /// use std::mem::MaybeUninit;
+    /// use std::ptr;
+    ///
+    /// struct Frame {
+    ///     len: usize,
+    ///     data: [u8; 1 << 20],
+    /// }
+    ///
+    /// fn main() {
+    /// #   #[cfg(feature = "std")]
+    ///     let frame: Box<Frame> = unsafe {
+    ///         Box::new_with_ptr(|frame: &mut MaybeUninit<Frame>| {
+    ///             let raw = frame.as_mut_ptr();
+    ///             ptr::addr_of_mut!((*raw).len).write(3);
+    ///             let data = ptr::addr_of_mut!((*raw).data) as *mut u8;
+    ///             ptr::write_bytes(data, 0, 1 << 20);
+    ///             ptr::copy_nonoverlapping(b"abc".as_ptr(), data, 3);
+    ///         })
+    ///     };
+    /// #   #[cfg(feature = "std")]
+    ///     assert_eq!(&frame.data[..frame.len], b"abc");
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// `f` must fully initialize the value, unless it panics.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    unsafe fn new_with_ptr<F: FnOnce(&mut MaybeUninit<Self::Inner>)>(f: F) -> Self
+    where
+        Self: Sized;
+
+    /// Fallible [`Box::new_with_ptr`]
+    ///
+    /// [`Box::new_with_ptr`]: #tymethod.new_with_ptr
+    ///
+    /// This returns `None` if memory couldn't be allocated, in which case
+    /// `f` is not called.
+    ///
+    /// # Safety
+    ///
+    /// `f` must fully initialize the value, unless it panics.
+    unsafe fn try_new_with_ptr<F: FnOnce(&mut MaybeUninit<Self::Inner>)>(f: F) -> Option<Self>
+    where
+        Self: Sized;
+
+    /// Allocates memory on the heap and then places the default value of
+    /// `Self::Inner` into it.
+    ///
+    /// This is equivalent to `new_with(Default::default)`. With the
+    /// `specialization` feature, for types implementing [`ZeroDefault`],
+    /// this gets zeroed memory from the allocator instead, as [`new_zeroed`]
+    /// does.
+    ///
+    /// [`ZeroDefault`]: trait.ZeroDefault.html
+    /// [`new_zeroed`]: #tymethod.new_zeroed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxExt;
+    /// use std::collections::HashMap;
+    ///
+    /// fn main() {
+    /// #   #[cfg(feature = "std")]
+    ///     let counters: Box<[u32; 32]> = BoxExt::new_default();
+    /// #   #[cfg(feature = "std")]
+    ///     assert!(counters.iter().all(|&c| c == 0));
+    /// #   #[cfg(feature = "std")]
+    ///     let map: Box<HashMap<u32, u32>> = BoxExt::new_default();
+    /// #   #[cfg(feature = "std")]
+    ///     assert!(map.is_empty());
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
     #[inline]
-    fn new_with<F: FnOnce() -> T>(f: F) -> Box<T> {
+    #[track_caller]
+    fn new_default() -> Self
+    where
+        Self: Sized,
+        Self::Inner: Default,
+    {
+        Self::new_with(Default::default)
+    }
+
+    /// Fallible [`Box::new_default`]
+    ///
+    /// [`Box::new_default`]: #method.new_default
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    #[inline]
+    fn try_new_default() -> Option<Self>
+    where
+        Self: Sized,
+        Self::Inner: Default,
+    {
+        Self::try_new_with(Default::default)
+    }
+
+    /// Allocates memory on the heap and initializes it in place with `init`.
+    ///
+    /// Contrary to [`new_with`], this guarantees that the value is never
+    /// built on the stack when the initializer is composed of the
+    /// initializers from the [`init`] module.
+    ///
+    /// [`new_with`]: #tymethod.new_with
+    /// [`init`]: init/index.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate boxext;
+    /// use boxext::{init, BoxExt};
+    ///
+    /// struct Page {
+    ///     lines: [[u8; 80]; 1024],
+    ///     count: usize,
+    /// }
+    ///
+    /// fn main() {
+    /// #   #[cfg(feature = "std")]
+    ///     let page: Box<Page> = Box::new_init(init!(Page {
+    ///         lines: init::array(|_| init::array(|_| b' ')),
+    ///         count: 0,
+    ///     }));
+    /// #   #[cfg(feature = "std")]
+    ///     assert_eq!(page.lines[1023][79], b' ');
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[inline]
+    #[track_caller]
+    fn new_init<I: Init<Self::Inner>>(init: I) -> Self
+    where
+        Self: Sized,
+    {
+        unsafe { Self::new_with_ptr(|slot| init.init(slot.as_mut_ptr())) }
+    }
+
+    /// Fallible [`Box::new_init`]
+    ///
+    /// [`Box::new_init`]: #method.new_init
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    #[inline]
+    fn try_new_init<I: Init<Self::Inner>>(init: I) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        unsafe { Self::try_new_with_ptr(|slot| init.init(slot.as_mut_ptr())) }
+    }
+
+    /// Allocates memory on the heap, and fills it with `byte`.
+    ///
+    /// The memory is filled with a single `memset` after allocation. This is
+    /// meant to catch reads of memory that was not written, e.g. in unsafe
+    /// code, with a pattern that stands out, like `0xaa`.
+    ///
+    /// This requires [`Pod`] rather than [`Zero`], since the result must be
+    /// valid for any byte.
+    ///
+    /// [`Pod`]: trait.Pod.html
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxExt;
+    ///
+    /// fn main() {
+    /// #   #[cfg(feature = "std")]
+    ///     let buf: Box<[u32; 1024]> = BoxExt::new_patterned(0xaa);
+    /// #   #[cfg(feature = "std")]
+    ///     assert!(buf.iter().all(|&x| x == 0xaaaa_aaaa));
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[inline]
+    #[track_caller]
+    fn new_patterned(byte: u8) -> Self
+    where
+        Self: Sized,
+        Self::Inner: Pod,
+    {
+        unsafe { Self::new_with_ptr(|slot| ptr::write_bytes(slot.as_mut_ptr(), byte, 1)) }
+    }
+
+    /// Fallible [`Box::new_patterned`]
+    ///
+    /// [`Box::new_patterned`]: #method.new_patterned
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    #[inline]
+    fn try_new_patterned(byte: u8) -> Option<Self>
+    where
+        Self: Sized,
+        Self::Inner: Pod,
+    {
+        unsafe { Self::try_new_with_ptr(|slot| ptr::write_bytes(slot.as_mut_ptr(), byte, 1)) }
+    }
+}
+
+// With the `verify_in_place` feature, records that a `T` is placed on the
+// heap, for `testing::assert_in_place`.
+#[cfg(all(feature = "alloc", not(feature = "verify_in_place")))]
+#[inline(always)]
+fn record_boxed<T>() {}
+
+#[cfg(feature = "verify_in_place")]
+use testing::record_boxed;
+
+// Whether `T` is zero-sized. This being an associated constant, branches
+// depending on it are resolved at compile time, even without optimizations.
+#[cfg(any(feature = "alloc", feature = "allocator_api"))]
+struct IsZst<T>(PhantomData<T>);
+
+#[cfg(any(feature = "alloc", feature = "allocator_api"))]
+impl<T> IsZst<T> {
+    const VALUE: bool = mem::size_of::<T>() == 0;
+}
+
+// Marks the branch it's called from as unlikely to be taken.
+#[cfg(any(feature = "alloc", feature = "allocator_api"))]
+#[cold]
+#[inline(always)]
+fn cold() {}
+
+// Kept out of line, so that it doesn't bloat the functions allocating memory
+// when those are inlined.
+// It reports the location of the allocating call site in the crate's public
+// API, rather than a location inside the crate.
+#[cfg(feature = "alloc")]
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn capacity_overflow() -> ! {
+    panic!("capacity overflow")
+}
+
+// Memory allocated for a `T` that is not initialized yet.
+// A `Box` is only created once the value is in place, and the memory is
+// deallocated if the guard is dropped before that, e.g. when the function
+// computing the value panics.
+#[cfg(feature = "alloc")]
+struct UninitBox<T>(ptr::NonNull<T>);
+
+#[cfg(feature = "alloc")]
+impl<T> UninitBox<T> {
+    // Allocates memory for a `T`, zeroed if `zeroed` is true.
+    #[inline]
+    #[track_caller]
+    fn try_new(zeroed: bool) -> Result<Self, AllocError> {
+        if IsZst::<T>::VALUE {
+            return Ok(UninitBox(ptr::NonNull::dangling()));
+        }
+        let layout = Layout::new::<T>();
+        let raw = unsafe { try_alloc_global(layout, zeroed)? };
+        Ok(UninitBox(raw.cast()))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn new(zeroed: bool) -> Self {
+        #[cfg(boxext_std_new_uninit)]
+        {
+            if let Some(b) = UninitBox::new_std(zeroed) {
+                return b;
+            }
+        }
+        match UninitBox::try_new(zeroed) {
+            Ok(b) => b,
+            Err(e) => e.handle(),
+        }
+    }
+
+    // Allocates memory for a `T` with std's own `Box::new_uninit` or
+    // `Box::new_zeroed`, when they behave the same as the crate's allocation
+    // path: none of the features hooking into it is enabled, the memory comes
+    // from the global allocator, and no handler was set with
+    // `set_alloc_error_handler`, so that failures end up in
+    // `handle_alloc_error` either way. Returns None otherwise.
+    #[inline]
+    #[cfg(boxext_std_new_uninit)]
+    #[allow(clippy::incompatible_msrv)]
+    fn new_std(zeroed: bool) -> Option<Self> {
+        if cfg!(any(
+            feature = "budget",
+            feature = "oom_hook",
+            feature = "instrument",
+            feature = "custom_allocator",
+            feature = "poison_on_free",
+        )) || alloc_error_handler().is_some()
+        {
+            return None;
+        }
+        #[cfg(any(feature = "jemalloc", feature = "windows_heap", feature = "wasm_pages"))]
+        {
+            if native::is_native() {
+                return None;
+            }
+        }
+        let b = if zeroed {
+            #[cfg(boxext_std_new_zeroed)]
+            {
+                Box::<T>::new_zeroed()
+            }
+            #[cfg(not(boxext_std_new_zeroed))]
+            {
+                return None;
+            }
+        } else {
+            Box::<T>::new_uninit()
+        };
+        Some(UninitBox(unsafe {
+            ptr::NonNull::new_unchecked(Box::into_raw(b) as *mut T)
+        }))
+    }
+
+    // Writes the value and turns the memory into a `Box`.
+    #[inline]
+    fn init(self, value: T) -> Box<T> {
         unsafe {
-            let mut b = new_box::<T>(false);
-            ptr::write(b.as_mut(), f());
-            b
+            ptr::write(self.0.as_ptr(), value);
+            self.assume_init()
         }
     }
 
+    // Turns the memory into a `Box`. The memory must hold a valid `T`.
     #[inline]
+    unsafe fn assume_init(self) -> Box<T> {
+        let raw = self.0.as_ptr();
+        mem::forget(self);
+        Box::from_raw(raw)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Drop for UninitBox<T> {
+    fn drop(&mut self) {
+        if !IsZst::<T>::VALUE {
+            unsafe {
+                let layout = Layout::new::<T>();
+                poison(self.0.as_ptr() as *mut u8, layout.size());
+                dealloc(self.0.as_ptr() as *mut u8, layout);
+            }
+        }
+    }
+}
+
+// Allocates memory for the given layout, which must not be zero-sized, from
+// the global allocator. The memory is zeroed if `zeroed` is true,
+// uninitialized otherwise.
+#[cfg(feature = "alloc")]
+#[inline]
+#[track_caller]
+unsafe fn try_alloc_global(layout: Layout, zeroed: bool) -> Result<ptr::NonNull<u8>, AllocError> {
+    try_alloc_using(layout, zeroed, alloc, alloc_zeroed)
+}
+
+// Returns the layout for a slice of `len` elements of type `T`, or None if
+// its size would overflow.
+#[cfg(feature = "std")]
+fn slice_layout<T>(len: usize) -> Option<Layout> {
+    let size = mem::size_of::<T>().checked_mul(len)?;
+    Layout::from_size_align(size, mem::align_of::<T>()).ok()
+}
+
+// Allocates memory for a slice of `len` elements of type `T`, zeroed if
+// `zeroed` is true, uninitialized otherwise.
+#[cfg(feature = "std")]
+#[track_caller]
+unsafe fn try_alloc_slice<T>(len: usize, zeroed: bool) -> Result<*mut T, AllocError> {
+    let layout = slice_layout::<T>(len).ok_or(AllocError::capacity_overflow())?;
+    if IsZst::<T>::VALUE || len == 0 {
+        return Ok(ptr::NonNull::<T>::dangling().as_ptr());
+    }
+    Ok(try_alloc_global(layout, zeroed)?.cast().as_ptr())
+}
+
+// Deallocates memory obtained from `try_alloc_slice`.
+#[cfg(feature = "std")]
+unsafe fn dealloc_slice<T>(raw: *mut T, len: usize) {
+    if !IsZst::<T>::VALUE && len != 0 {
+        let layout = slice_layout::<T>(len).unwrap();
+        poison(raw as *mut u8, layout.size());
+        dealloc(raw as *mut u8, layout);
+    }
+}
+
+// Resizes memory obtained from `try_alloc_slice` for `old_len` elements so
+// that it can hold `new_len` elements. Elements past `old_len` are left
+// uninitialized. On failure, the original memory is left untouched.
+#[cfg(feature = "std")]
+#[track_caller]
+unsafe fn try_realloc_slice<T>(
+    raw: *mut T,
+    old_len: usize,
+    new_len: usize,
+) -> Result<*mut T, AllocError> {
+    if IsZst::<T>::VALUE || old_len == 0 {
+        return try_alloc_slice::<T>(new_len, false);
+    }
+    let old_layout = slice_layout::<T>(old_len).unwrap();
+    let new_layout = slice_layout::<T>(new_len).ok_or(AllocError::capacity_overflow())?;
+    if new_len == 0 {
+        poison(raw as *mut u8, old_layout.size());
+        dealloc(raw as *mut u8, old_layout);
+        return Ok(ptr::NonNull::<T>::dangling().as_ptr());
+    }
+    Ok(try_realloc_using(raw as *mut u8, old_layout, new_layout.size(), realloc)?.cast().as_ptr())
+}
+
+// Creates a new boxed slice of `len` elements of type `T`.
+#[cfg(feature = "std")]
+unsafe fn try_new_slice_box<T>(len: usize, zeroed: bool) -> Result<Box<[T]>, AllocError> {
+    let raw = try_alloc_slice::<T>(len, zeroed)?;
+    Ok(Box::from_raw(ptr::slice_from_raw_parts_mut(raw, len)))
+}
+
+#[cfg(feature = "std")]
+#[track_caller]
+unsafe fn alloc_slice<T>(len: usize, zeroed: bool) -> *mut T {
+    match try_alloc_slice::<T>(len, zeroed) {
+        Ok(raw) => raw,
+        Err(e) => e.handle(),
+    }
+}
+
+#[cfg(feature = "std")]
+#[track_caller]
+unsafe fn new_slice_box<T>(len: usize, zeroed: bool) -> Box<[T]> {
+    Box::from_raw(ptr::slice_from_raw_parts_mut(alloc_slice::<T>(len, zeroed), len))
+}
+
+// Helper for `BoxExt::new_with`, allowing, with the `specialization`
+// feature, to pick a different implementation for some types.
+#[cfg(feature = "alloc")]
+trait BoxedWith: Sized {
+    fn boxed_with<F: FnOnce() -> Self>(f: F) -> Box<Self>;
+}
+
+macro_rules! boxed_with_impl {
+    ($($default:ident)*) => {
+        #[cfg(feature = "alloc")]
+        impl<T> BoxedWith for T {
+            #[inline]
+            #[track_caller]
+            $($default)* fn boxed_with<F: FnOnce() -> T>(f: F) -> Box<T> {
+                UninitBox::new(false).init(f())
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "specialization"))]
+boxed_with_impl!();
+
+#[cfg(feature = "specialization")]
+boxed_with_impl!(default);
+
+// Small `Copy` types are cheap to move, so there's nothing to gain from
+// allocating before calling `f`, while doing so prevents the value from
+// being computed in registers. The memory still comes from `UninitBox`, so
+// that allocation goes through the same hooks either way.
+#[cfg(all(feature = "alloc", feature = "specialization"))]
+impl<T: Copy> BoxedWith for T {
+    #[inline]
+    #[track_caller]
+    fn boxed_with<F: FnOnce() -> T>(f: F) -> Box<T> {
+        if mem::size_of::<T>() <= 2 * mem::size_of::<usize>() {
+            let value = f();
+            UninitBox::new(false).init(value)
+        } else {
+            UninitBox::new(false).init(f())
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> BoxExt for Box<T> {
+    type Inner = T;
+
+    #[inline]
+    #[track_caller]
+    fn new_with<F: FnOnce() -> T>(f: F) -> Box<T> {
+        record_boxed::<T>();
+        T::boxed_with(f)
+    }
+
+    #[inline]
+    #[track_caller]
     fn new_zeroed() -> Box<T>
     where
         T: Zero,
     {
-        unsafe { new_box(true) }
+        unsafe { UninitBox::new(true).assume_init() }
     }
 
     #[inline]
     fn try_new(x: T) -> Option<Self> {
-        unsafe {
-            let mut b = try_new_box::<T>(false).ok()?;
-            ptr::write(b.as_mut(), x);
-            Some(b)
-        }
+        Some(UninitBox::try_new(false).ok()?.init(x))
     }
 
     #[inline]
+    #[cfg_attr(feature = "verify_in_place", track_caller)]
     fn try_new_with<F: FnOnce() -> Self::Inner>(f: F) -> Option<Self> {
-        unsafe {
-            let mut b = try_new_box::<T>(false).ok()?;
-            ptr::write(b.as_mut(), f());
-            Some(b)
-        }
+        record_boxed::<T>();
+        Some(UninitBox::try_new(false).ok()?.init(f()))
     }
 
     #[inline]
@@ -348,45 +1687,371 @@ impl<T> BoxExt for Box<T> {
     where
         Self::Inner: Zero,
     {
-        unsafe { try_new_box::<T>(true).ok() }
+        unsafe { Some(UninitBox::try_new(true).ok()?.assume_init()) }
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn new_zeroed_unchecked() -> Box<T> {
+        UninitBox::new(true).assume_init()
+    }
+
+    #[inline]
+    unsafe fn try_new_zeroed_unchecked() -> Option<Self> {
+        Some(UninitBox::try_new(true).ok()?.assume_init())
+    }
+
+    #[inline]
+    #[track_caller]
+    fn new_with_fallible<E, F: FnOnce() -> Result<T, E>>(f: F) -> Result<Self, E> {
+        let uninit = UninitBox::new(false);
+        Ok(uninit.init(f()?))
+    }
+
+    #[inline]
+    fn try_new_with_fallible<E, F: FnOnce() -> Result<T, E>>(f: F) -> Option<Result<Self, E>> {
+        let uninit = UninitBox::try_new(false).ok()?;
+        Some(f().map(|value| uninit.init(value)))
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn new_with_ptr<F: FnOnce(&mut MaybeUninit<T>)>(f: F) -> Self {
+        let uninit = UninitBox::new(false);
+        f(&mut *(uninit.0.as_ptr() as *mut MaybeUninit<T>));
+        uninit.assume_init()
+    }
+
+    #[inline]
+    unsafe fn try_new_with_ptr<F: FnOnce(&mut MaybeUninit<T>)>(f: F) -> Option<Self> {
+        let uninit = UninitBox::try_new(false).ok()?;
+        f(&mut *(uninit.0.as_ptr() as *mut MaybeUninit<T>));
+        Some(uninit.assume_init())
+    }
+
+    #[inline]
+    #[track_caller]
+    fn new_default() -> Box<T>
+    where
+        T: Default,
+    {
+        T::boxed_default()
+    }
+
+    #[inline]
+    fn try_new_default() -> Option<Self>
+    where
+        T: Default,
+    {
+        T::try_boxed_default()
     }
 }
 
 /// Trait indicating whether a value full of zeroes is valid.
 ///
-/// This trait is used to enable the [`Box::new_zeroed`] method for types where
-/// it's safe to use.
+/// This trait is used to enable the [`Box::new_zeroed`] method for types where
+/// it's safe to use.
+///
+/// [`Box::new_zeroed`]: trait.BoxExt.html#tymethod::new_zeroed
+///
+/// # Safety
+///
+/// Do **not** implement this trait for types where a raw byte array of 0
+/// doesn't represent a valid value for the type. Please double check it is
+/// valid and corresponds to what you want.
+///
+/// # Implementations
+///
+/// `Zero` is implemented for integer and floating point types, `bool`,
+/// `char`, atomic types, raw pointers, tuples of up to 12 elements, and
+/// arrays of any length, whose elements implement `Zero`. It is also
+/// implemented for `Cell<T>`, `UnsafeCell<T>`, `Wrapping<T>` and
+/// `ManuallyDrop<T>` when `T` implements `Zero`, and for `MaybeUninit<T>`
+/// for any `T`. The zero-sized `()`, `PhantomData<T>`, for any `T`, and
+/// `PhantomPinned` implement it too, so that marker fields don't prevent
+/// deriving it.
+///
+/// Finally, it is implemented for `Option`s of references, `NonNull<T>`,
+/// `Box<T>`, and function pointers with up to 12 arguments, for which
+/// `None` is guaranteed to be represented as a null pointer. Function
+/// pointers taking references, such as `fn(&T)`, are generic over the
+/// lifetime of the reference, and are not covered.
+///
+/// On x86 and x86-64, it is implemented for the SSE and AVX vector types,
+/// and on AArch64, for the NEON vector types.
+///
+/// With the `libc` and `windows` features, it is implemented for the structs
+/// from the `libc` and `windows-sys` crates that are conventionally
+/// zero-initialized.
+///
+/// ```
+/// # #[cfg(all(feature = "libc", unix))]
+/// extern crate libc;
+/// extern crate boxext;
+/// use boxext::BoxExt;
+///
+/// fn main() {
+/// #   #[cfg(all(feature = "libc", feature = "std", unix))]
+/// #   {
+///     let addr: Box<libc::sockaddr_storage> = BoxExt::new_zeroed();
+///     assert_eq!(addr.ss_family, 0);
+/// #   }
+/// }
+/// ```
+///
+/// ```
+/// #[macro_use]
+/// extern crate boxext;
+/// use boxext::BoxExt;
+/// use std::cell::Cell;
+/// use std::ptr::NonNull;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// fn main() {
+///     // Room for an Ethernet frame.
+/// #   #[cfg(feature = "std")]
+///     let buf: Box<[u8; 1500]> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert!(buf.iter().all(|&b| b == 0));
+///
+///     // Slots of a lock-free ring buffer.
+/// #   #[cfg(feature = "std")]
+///     let slots: Box<[AtomicU64; 256]> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(slots[0].load(Ordering::Relaxed), 0);
+///
+///     // Per-bucket hit counters.
+/// #   #[cfg(feature = "std")]
+///     let hits: Box<[Cell<u32>; 64]> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     hits[3].set(hits[3].get() + 1);
+///
+///     // SIMD scratch space.
+/// #   #[cfg(all(feature = "std", target_arch = "x86_64"))]
+///     let scratch: Box<[std::arch::x86_64::__m256; 4096]> = BoxExt::new_zeroed();
+/// #   #[cfg(all(feature = "std", target_arch = "x86_64"))]
+///     assert_eq!(scratch.len(), 4096);
+///
+///     // Intrusive list node, with no neighbours or callback yet.
+///     struct Node {
+///         prev: Option<NonNull<Node>>,
+///         next: Option<NonNull<Node>>,
+///         on_remove: Option<fn(usize)>,
+///     }
+///     zero_impl!(struct Node {
+///         prev: Option<NonNull<Node>>,
+///         next: Option<NonNull<Node>>,
+///         on_remove: Option<fn(usize)>,
+///     });
+/// #   #[cfg(feature = "std")]
+///     let node: Box<Node> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert!(node.prev.is_none() && node.next.is_none());
+/// #   #[cfg(feature = "std")]
+///     assert!(node.on_remove.is_none());
+/// }
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{BoxExt, Zero};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Foo(usize);
+///
+/// unsafe impl Zero for Foo {}
+///
+/// fn main() {
+///     // equivalent to `Box::new(Foo(0))`
+/// #   #[cfg(feature = "std")]
+///     let buf: Box<Foo> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(*buf, Foo(0));
+/// }
+/// ```
+///
+/// For convenience, a `boxext_derive` crate is provided that provides a
+/// custom derive for `Zero`. The [`zero_impl!`] macro is an alternative that
+/// doesn't require a procedural macro, and [`unsafe_zero_structs!`]
+/// implements `Zero` for many foreign types at once.
+///
+/// [`zero_impl!`]: macro.zero_impl.html
+/// [`unsafe_zero_structs!`]: macro.unsafe_zero_structs.html
+///
+/// ```
+/// extern crate boxext;
+/// #[macro_use]
+/// extern crate boxext_derive;
+/// use boxext::BoxExt;
+///
+/// #[derive(Zero, Debug, PartialEq)]
+/// struct Foo(usize);
 ///
-/// [`Box::new_zeroed`]: trait.BoxExt.html#tymethod::new_zeroed
+/// fn main() {
+///     // equivalent to `Box::new(Foo(0))`
+/// #   #[cfg(feature = "std")]
+///     let buf: Box<Foo> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(*buf, Foo(0));
+/// }
+/// ```
 ///
-/// # Safety
+/// The derive requires each field type to implement `Zero`, rather than each
+/// generic parameter, so that generic types only get the bounds they need.
 ///
-/// Do **not** implement this trait for types where a raw byte array of 0
-/// doesn't represent a valid value for the type. Please double check it is
-/// valid and corresponds to what you want.
+/// ```
+/// extern crate boxext;
+/// #[macro_use]
+/// extern crate boxext_derive;
+/// use boxext::BoxExt;
 ///
-/// # Examples
+/// #[derive(Zero)]
+/// struct RawSlice<T> {
+///     ptr: *mut T,
+///     len: usize,
+/// }
+///
+/// fn main() {
+///     // `String` doesn't implement `Zero`, but `*mut String` does.
+/// #   #[cfg(feature = "std")]
+///     let buf: Box<RawSlice<String>> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert!(buf.ptr.is_null());
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(buf.len, 0);
+/// }
+/// ```
+///
+/// Marker fields don't add any bounds either.
+///
+/// ```
+/// extern crate boxext;
+/// #[macro_use]
+/// extern crate boxext_derive;
+/// use boxext::BoxExt;
+/// use std::marker::{PhantomData, PhantomPinned};
+///
+/// #[derive(Zero)]
+/// struct Slot<T> {
+///     index: u32,
+///     owner: PhantomData<T>,
+///     _pin: PhantomPinned,
+/// }
+///
+/// fn main() {
+/// #   #[cfg(feature = "std")]
+///     let slot: Box<Slot<String>> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(slot.index, 0);
+/// }
+/// ```
+///
+/// Fields holding values of a generic parameter, directly or in arrays or
+/// tuples, make the implementation require that parameter to implement
+/// `Zero`, as `derive(Clone)` would.
 ///
 /// ```
 /// extern crate boxext;
+/// #[macro_use]
+/// extern crate boxext_derive;
 /// use boxext::{BoxExt, Zero};
 ///
-/// #[derive(Debug, PartialEq)]
-/// struct Foo(usize);
+/// #[derive(Zero)]
+/// struct Buffer<T> {
+///     data: [T; 64],
+///     len: usize,
+/// }
 ///
-/// unsafe impl Zero for Foo {}
+/// # #[cfg(feature = "std")]
+/// fn zeroed_buffer<T: Zero>() -> Box<Buffer<T>> {
+///     BoxExt::new_zeroed()
+/// }
 ///
 /// fn main() {
-///     // equivalent to `Box::new(Foo(0))`
 /// #   #[cfg(feature = "std")]
-///     let buf: Box<Foo> = Box::new_zeroed();
+///     let buf = zeroed_buffer::<f32>();
 /// #   #[cfg(feature = "std")]
-///     assert_eq!(*buf, Foo(0));
+///     assert!(buf.data.iter().all(|&x| x == 0.0) && buf.len == 0);
 /// }
 /// ```
 ///
-/// For convenience, a `boxext_derive` crate is provided that provides a
-/// custom derive for `Zero`.
+/// A `#[repr(transparent)]` wrapper around a type from another crate that
+/// can't implement `Zero` can use `#[zero(unsafe_transparent)]` to derive it
+/// anyways. As with implementing `Zero` manually, this asserts that the
+/// wrapped type is valid when zeroed, which is why the attribute is marked
+/// unsafe.
+///
+/// ```
+/// extern crate boxext;
+/// #[macro_use]
+/// extern crate boxext_derive;
+/// use boxext::BoxExt;
+///
+/// // Stand-in for a type defined in a `-sys` crate.
+/// mod sys {
+///     #[repr(C)]
+///     pub struct timeval {
+///         pub tv_sec: i64,
+///         pub tv_usec: i64,
+///     }
+/// }
+///
+/// #[derive(Zero)]
+/// #[zero(unsafe_transparent)]
+/// #[repr(transparent)]
+/// struct TimeVal(sys::timeval);
+///
+/// fn main() {
+/// #   #[cfg(feature = "std")]
+///     let buf: Box<TimeVal> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(buf.0.tv_sec, 0);
+/// }
+/// ```
+///
+/// A single field whose type can't implement `Zero`, such as an opaque
+/// handle from a `-sys` crate, can be marked `#[zero(unsafe_assume)]`
+/// instead. As with `#[zero(unsafe_transparent)]`, this asserts that the
+/// type of that field is valid when zeroed, while the other fields are still
+/// checked.
+///
+/// ```
+/// extern crate boxext;
+/// #[macro_use]
+/// extern crate boxext_derive;
+/// use boxext::BoxExt;
+///
+/// // Stand-in for a type defined in a `-sys` crate.
+/// mod sys {
+///     #[repr(C)]
+///     pub struct ssl_ctx {
+///         pub handle: *mut u8,
+///     }
+/// }
+///
+/// #[derive(Zero)]
+/// struct Connection {
+///     #[zero(unsafe_assume)]
+///     ctx: sys::ssl_ctx,
+///     fd: i32,
+/// }
+///
+/// fn main() {
+/// #   #[cfg(feature = "std")]
+///     let conn: Box<Connection> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert!(conn.ctx.handle.is_null() && conn.fd == 0);
+/// }
+/// ```
+///
+/// The derive also supports `#[repr(C)]` or `#[repr(<integer>)]` enums,
+/// for which zeroed memory holds the variant with a zero discriminant, and
+/// requires the fields of that variant to implement `Zero`. It supports
+/// `#[repr(C)]` unions as well, for which zeroed memory holds a zeroed value
+/// of the first field, or of the field marked `#[zero]`. That field is
+/// required to implement `Zero`, and to be as large as the union.
 ///
 /// ```
 /// extern crate boxext;
@@ -395,14 +2060,66 @@ impl<T> BoxExt for Box<T> {
 /// use boxext::BoxExt;
 ///
 /// #[derive(Zero, Debug, PartialEq)]
-/// struct Foo(usize);
+/// #[repr(u8)]
+/// enum State {
+///     Running = 1,
+///     Stopped = 0,
+/// }
+///
+/// #[derive(Zero, Clone, Copy)]
+/// #[repr(C)]
+/// union Addr {
+///     v4: [u8; 4],
+///     #[zero]
+///     v6: [u8; 16],
+/// }
+///
+/// #[derive(Zero)]
+/// #[repr(C)]
+/// struct Peer {
+///     state: State,
+///     addr: Addr,
+/// }
 ///
 /// fn main() {
-///     // equivalent to `Box::new(Foo(0))`
 /// #   #[cfg(feature = "std")]
-///     let buf: Box<Foo> = Box::new_zeroed();
+///     let peer: Box<Peer> = BoxExt::new_zeroed();
 /// #   #[cfg(feature = "std")]
-///     assert_eq!(*buf, Foo(0));
+///     assert_eq!(peer.state, State::Stopped);
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(unsafe { peer.addr.v6 }, [0; 16]);
+/// }
+/// ```
+///
+/// The derived implementation refers to the `boxext` crate as `::boxext`.
+/// When it is available under a different path, e.g. because it was renamed
+/// or is re-exported by another crate, the path can be given with
+/// `#[zero(crate = "path")]`.
+///
+/// ```
+/// extern crate boxext as bx;
+/// #[macro_use]
+/// extern crate boxext_derive;
+/// use bx::BoxExt;
+///
+/// macro_rules! header {
+///     ($name:ident) => {
+///         #[derive(Zero)]
+///         #[zero(crate = "::bx")]
+///         struct $name {
+///             len: u32,
+///             flags: u32,
+///         }
+///     };
+/// }
+///
+/// header!(Header);
+///
+/// fn main() {
+/// #   #[cfg(feature = "std")]
+///     let buf: Box<Header> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert_eq!((buf.len, buf.flags), (0, 0));
 /// }
 /// ```
 ///
@@ -420,7 +2137,7 @@ impl<T> BoxExt for Box<T> {
 ///
 /// fn main() {
 ///     // equivalent to `Box::new(Foo(0))`
-///     let buf: Box<Foo> = Box::new_zeroed();
+///     let buf: Box<Foo> = BoxExt::new_zeroed();
 /// }
 /// ```
 ///
@@ -431,45 +2148,62 @@ macro_rules! zero_num_impl {
 }
 
 zero_num_impl! {
-    u8 u16 u32 u64 usize
-    i8 i16 i32 i64 isize
+    u8 u16 u32 u64 u128 usize
+    i8 i16 i32 i64 i128 isize
     f32 f64
+    bool char
 }
 
-unsafe impl<T> Zero for *mut T {}
-
-unsafe impl<T> Zero for *const T {}
+#[cfg(target_has_atomic = "8")]
+zero_num_impl! {
+    atomic::AtomicBool atomic::AtomicU8 atomic::AtomicI8
+}
 
-macro_rules! zero_array_impl {
-    ($($n:expr)+) => {$(
-        unsafe impl<T: Zero> Zero for [T; $n] {}
-    )+};
+#[cfg(target_has_atomic = "16")]
+zero_num_impl! {
+    atomic::AtomicU16 atomic::AtomicI16
 }
 
-zero_array_impl! {
-    1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16
-    17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32
-    33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48
-    49 50 51 52 53 54 55 56 57 58 59 60 61 62 63 64
-    65 66 67 68 69 70 71 72 73 74 75 76 77 78 79 80
-    81 82 83 84 85 86 87 88 89 90 91 92 93 94 95 96
-    97 98 99 100 101 102 103 104 105 106 107 108 109 110 111 112
-    113 114 115 116 117 118 119 120 121 122 123 124 125 126 127 128
-    160 192 200 224 256 384 512 768 1024 2048 4096 8192 16384 32768
+#[cfg(target_has_atomic = "32")]
+zero_num_impl! {
+    atomic::AtomicU32 atomic::AtomicI32
 }
 
-#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
-zero_array_impl! {
-    65536 131072 262144 524288 1048576 2097152 4194304 8388608
-    16777216 33554432 67108864 134217728 268435456 536870912
-    1073741824 2147483648
+#[cfg(target_has_atomic = "64")]
+zero_num_impl! {
+    atomic::AtomicU64 atomic::AtomicI64
 }
 
-#[cfg(target_pointer_width = "64")]
-zero_array_impl! {
-    4294967296
+#[cfg(target_has_atomic = "ptr")]
+zero_num_impl! {
+    atomic::AtomicUsize atomic::AtomicIsize
 }
 
+#[cfg(target_has_atomic = "ptr")]
+unsafe impl<T> Zero for atomic::AtomicPtr<T> {}
+
+unsafe impl<T: Zero> Zero for Cell<T> {}
+
+unsafe impl<T: Zero> Zero for UnsafeCell<T> {}
+
+unsafe impl<T: Zero> Zero for Wrapping<T> {}
+
+unsafe impl<T: Zero> Zero for ManuallyDrop<T> {}
+
+unsafe impl<T> Zero for MaybeUninit<T> {}
+
+unsafe impl<T> Zero for *mut T {}
+
+unsafe impl<T> Zero for *const T {}
+
+unsafe impl<T: Zero, const N: usize> Zero for [T; N] {}
+
+unsafe impl Zero for () {}
+
+unsafe impl<T: ?Sized> Zero for PhantomData<T> {}
+
+unsafe impl Zero for PhantomPinned {}
+
 macro_rules! zero_tuple_impl {
     ($t:ident $($u:ident)+) => {
         zero_tuple_impl!(($t) $($u)+);
@@ -486,3 +2220,274 @@ macro_rules! zero_tuple_impl {
 zero_tuple_impl! {
     A B C D E F G H I J K L
 }
+
+unsafe impl<T: ?Sized> Zero for Option<&T> {}
+
+unsafe impl<T: ?Sized> Zero for Option<&mut T> {}
+
+unsafe impl<T: ?Sized> Zero for Option<ptr::NonNull<T>> {}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized> Zero for Option<Box<T>> {}
+
+macro_rules! zero_fn_impl {
+    (@impl $($t:ident)*) => {
+        unsafe impl<R, $($t),*> Zero for Option<fn($($t),*) -> R> {}
+        unsafe impl<R, $($t),*> Zero for Option<unsafe fn($($t),*) -> R> {}
+        unsafe impl<R, $($t),*> Zero for Option<extern "C" fn($($t),*) -> R> {}
+        unsafe impl<R, $($t),*> Zero for Option<unsafe extern "C" fn($($t),*) -> R> {}
+    };
+    (($($t:ident)*) $u:ident $($v:ident)*) => {
+        zero_fn_impl!(@impl $($t)*);
+        zero_fn_impl!(($($t)* $u) $($v)*);
+    };
+    (($($t:ident)*)) => {
+        zero_fn_impl!(@impl $($t)*);
+    };
+}
+
+zero_fn_impl! {
+    () A B C D E F G H I J K L
+}
+
+#[cfg(target_arch = "x86")]
+zero_num_impl! {
+    arch::x86::__m128 arch::x86::__m128d arch::x86::__m128i
+    arch::x86::__m256 arch::x86::__m256d arch::x86::__m256i
+}
+
+#[cfg(target_arch = "x86_64")]
+zero_num_impl! {
+    arch::x86_64::__m128 arch::x86_64::__m128d arch::x86_64::__m128i
+    arch::x86_64::__m256 arch::x86_64::__m256d arch::x86_64::__m256i
+}
+
+#[cfg(target_arch = "aarch64")]
+zero_num_impl! {
+    arch::aarch64::int8x8_t arch::aarch64::int8x16_t
+    arch::aarch64::int16x4_t arch::aarch64::int16x8_t
+    arch::aarch64::int32x2_t arch::aarch64::int32x4_t
+    arch::aarch64::int64x1_t arch::aarch64::int64x2_t
+    arch::aarch64::uint8x8_t arch::aarch64::uint8x16_t
+    arch::aarch64::uint16x4_t arch::aarch64::uint16x8_t
+    arch::aarch64::uint32x2_t arch::aarch64::uint32x4_t
+    arch::aarch64::uint64x1_t arch::aarch64::uint64x2_t
+    arch::aarch64::float32x2_t arch::aarch64::float32x4_t
+    arch::aarch64::float64x1_t arch::aarch64::float64x2_t
+    arch::aarch64::poly8x8_t arch::aarch64::poly8x16_t
+    arch::aarch64::poly16x4_t arch::aarch64::poly16x8_t
+    arch::aarch64::poly64x1_t arch::aarch64::poly64x2_t
+}
+
+#[cfg(all(feature = "libc", unix))]
+zero_num_impl! {
+    libc::sockaddr libc::sockaddr_in libc::sockaddr_in6 libc::sockaddr_un
+    libc::sockaddr_storage libc::addrinfo libc::msghdr libc::iovec libc::linger
+    libc::stat libc::timeval libc::timespec libc::tm libc::rlimit libc::utsname
+    libc::pollfd libc::fd_set libc::termios libc::winsize
+}
+
+#[cfg(all(feature = "libc", any(target_os = "linux", target_os = "android")))]
+zero_num_impl! {
+    libc::epoll_event libc::sigset_t libc::sigaction libc::statfs
+    libc::itimerspec
+}
+
+#[cfg(all(feature = "windows", windows))]
+zero_num_impl! {
+    windows_sys::Win32::Networking::WinSock::WSADATA
+    windows_sys::Win32::Networking::WinSock::SOCKADDR_STORAGE
+    windows_sys::Win32::Security::SECURITY_ATTRIBUTES
+    windows_sys::Win32::Storage::FileSystem::BY_HANDLE_FILE_INFORMATION
+    windows_sys::Win32::Storage::FileSystem::WIN32_FIND_DATAW
+    windows_sys::Win32::System::IO::OVERLAPPED
+    windows_sys::Win32::System::Threading::PROCESS_INFORMATION
+    windows_sys::Win32::System::Threading::STARTUPINFOEXW
+    windows_sys::Win32::System::Threading::STARTUPINFOW
+}
+
+/// Implements [`Zero`] for a struct, checking that all its fields implement
+/// [`Zero`].
+///
+/// This is an alternative to the custom derive from the `boxext_derive`
+/// crate that doesn't require a procedural macro. The struct definition is
+/// not generated: the macro takes a copy of its fields, and checks at
+/// compile time that they match the actual definition.
+///
+/// [`Zero`]: trait.Zero.html
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate boxext;
+/// use boxext::BoxExt;
+///
+/// struct Header {
+///     len: u32,
+///     tag: [u8; 16],
+/// }
+///
+/// zero_impl!(struct Header { len: u32, tag: [u8; 16] });
+///
+/// struct Pair(u64, f64);
+///
+/// zero_impl!(struct Pair(u64, f64));
+///
+/// fn main() {
+/// #   #[cfg(feature = "std")]
+///     let header: Box<Header> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(header.len, 0);
+/// #   #[cfg(feature = "std")]
+///     let pair: Box<Pair> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(pair.0, 0);
+/// }
+/// ```
+///
+/// Fields that don't implement [`Zero`], or that don't match the struct
+/// definition, are rejected.
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate boxext;
+///
+/// struct Header {
+///     len: u32,
+///     name: String,
+/// }
+///
+/// zero_impl!(struct Header { len: u32, name: String });
+/// //                                         ^ the trait `boxext::Zero` is not implemented for `String`
+///
+/// fn main() {}
+/// ```
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate boxext;
+///
+/// struct Header {
+///     len: u32,
+///     name: &'static str,
+/// }
+///
+/// zero_impl!(struct Header { len: u32 });
+/// //         ^ pattern does not mention field `name`
+///
+/// fn main() {}
+/// ```
+#[macro_export]
+macro_rules! zero_impl {
+    (struct $name:ident;) => {
+        unsafe impl $crate::Zero for $name {}
+
+        const _: () = {
+            #[allow(dead_code)]
+            fn check(v: $name) {
+                let $name = v;
+            }
+        };
+    };
+    (struct $name:ident { $($field:ident: $ty:ty),* $(,)* }) => {
+        unsafe impl $crate::Zero for $name {}
+
+        const _: () = {
+            #[allow(dead_code)]
+            fn check(v: $name) {
+                fn assert_zero<T: $crate::Zero>(_: &T) {}
+                let $name { $($field),* } = v;
+                $(assert_zero::<$ty>(&$field);)*
+            }
+        };
+    };
+    (struct $name:ident($($ty:ty),* $(,)*)) => {
+        unsafe impl $crate::Zero for $name {}
+
+        const _: () = {
+            #[allow(dead_code)]
+            fn check() {
+                fn assert_zero<T: $crate::Zero>() {}
+                let _: fn($($ty),*) -> $name = $name;
+                $(assert_zero::<$ty>();)*
+            }
+        };
+    };
+}
+
+#[doc(hidden)]
+pub use core::mem::size_of as __size_of;
+
+/// Implements [`Zero`] for a list of types, e.g. structs generated by
+/// `bindgen`.
+///
+/// As with implementing [`Zero`] manually, this asserts that all the types
+/// are valid when zeroed, without any check of their fields. Each type can
+/// be followed by `= size` to additionally check its size in bytes at
+/// compile time, which catches bindings generated for the wrong target.
+///
+/// [`Zero`]: trait.Zero.html
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate boxext;
+/// use boxext::BoxExt;
+///
+/// // Stand-in for a module generated by `bindgen`.
+/// mod sys {
+///     #[repr(C)]
+///     pub struct timeval {
+///         pub tv_sec: i64,
+///         pub tv_usec: i64,
+///     }
+///
+///     #[repr(C)]
+///     pub struct pollfd {
+///         pub fd: i32,
+///         pub events: i16,
+///         pub revents: i16,
+///     }
+/// }
+///
+/// unsafe_zero_structs! {
+///     sys::timeval = 16,
+///     sys::pollfd,
+/// }
+///
+/// fn main() {
+/// #   #[cfg(feature = "std")]
+///     let tv: Box<sys::timeval> = BoxExt::new_zeroed();
+/// #   #[cfg(feature = "std")]
+///     assert_eq!(tv.tv_usec, 0);
+/// }
+/// ```
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate boxext;
+///
+/// #[repr(C)]
+/// pub struct timeval {
+///     pub tv_sec: i32,
+///     pub tv_usec: i32,
+/// }
+///
+/// unsafe_zero_structs! {
+///     timeval = 16,
+/// //            ^ expected an array with a size of 16, found one with a size of 8
+/// }
+///
+/// fn main() {}
+/// ```
+#[macro_export]
+macro_rules! unsafe_zero_structs {
+    ($($ty:ty $(= $size:expr)?),* $(,)*) => {
+        $(
+            unsafe impl $crate::Zero for $ty {}
+            $(const _: [(); $size] = [(); $crate::__size_of::<$ty>()];)?
+        )*
+    };
+}
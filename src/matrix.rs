@@ -0,0 +1,280 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::fmt;
+use core::ops::{Index, IndexMut};
+use {new_slice_box, try_new_slice_box, AllocError, BoxSliceExt, Zero};
+
+/// A zeroed two-dimensional buffer with dimensions chosen at runtime.
+///
+/// Elements are stored row after row in a single boxed slice. Each row
+/// starts `stride` elements after the previous one, where the stride is the
+/// number of columns, possibly rounded up with [`new_aligned`], e.g. so that
+/// each row can be processed with SIMD instructions without special-casing
+/// the end of the row.
+///
+/// The memory is allocated zeroed, which usually doesn't require writing
+/// zeroes for large buffers. Alternatively, each element can be initialized
+/// in place with [`new_with`], for types that can't be zeroed.
+///
+/// [`new_aligned`]: #method.new_aligned
+/// [`new_with`]: #method.new_with
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::Matrix2D;
+///
+/// fn main() {
+///     let mut image = Matrix2D::<u8>::new_aligned(480, 643, 16);
+///     assert_eq!(image.stride(), 656);
+///     image[(2, 3)] = 255;
+///     assert_eq!(image.row(2)[3], 255);
+///     assert_eq!(image.row(2).len(), 643);
+///     assert!(image.row(1).iter().all(|&p| p == 0));
+/// }
+/// ```
+pub struct Matrix2D<T> {
+    data: Box<[T]>,
+    rows: usize,
+    cols: usize,
+    stride: usize,
+}
+
+// Returns the stride and the total number of elements.
+fn dimensions(rows: usize, cols: usize, align: usize) -> Result<(usize, usize), AllocError> {
+    assert!(align != 0, "alignment must not be 0");
+    let stride = cols
+        .checked_add(align - 1)
+        .ok_or_else(AllocError::capacity_overflow)?
+        / align
+        * align;
+    let len = rows
+        .checked_mul(stride)
+        .ok_or_else(AllocError::capacity_overflow)?;
+    Ok((stride, len))
+}
+
+impl<T: Zero> Matrix2D<T> {
+    /// Creates a zeroed matrix of `rows` rows and `cols` columns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of elements overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Matrix2D::new_aligned(rows, cols, 1)
+    }
+
+    /// Creates a zeroed matrix of `rows` rows and `cols` columns, with a
+    /// stride rounded up to a multiple of `align` elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is 0 or if the number of elements overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_aligned(rows: usize, cols: usize, align: usize) -> Self {
+        let (stride, len) = match dimensions(rows, cols, align) {
+            Ok(d) => d,
+            Err(e) => e.handle(),
+        };
+        Matrix2D {
+            data: unsafe { new_slice_box(len, true) },
+            rows,
+            cols,
+            stride,
+        }
+    }
+
+    /// Fallible [`Matrix2D::new`]
+    ///
+    /// [`Matrix2D::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the
+    /// number of elements overflows.
+    pub fn try_new(rows: usize, cols: usize) -> Option<Self> {
+        Matrix2D::try_new_aligned(rows, cols, 1)
+    }
+
+    /// Fallible [`Matrix2D::new_aligned`]
+    ///
+    /// [`Matrix2D::new_aligned`]: #method.new_aligned
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the
+    /// number of elements overflows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is 0.
+    pub fn try_new_aligned(rows: usize, cols: usize, align: usize) -> Option<Self> {
+        let (stride, len) = dimensions(rows, cols, align).ok()?;
+        Some(Matrix2D {
+            data: unsafe { try_new_slice_box(len, true).ok()? },
+            rows,
+            cols,
+            stride,
+        })
+    }
+}
+
+impl<T> Matrix2D<T> {
+    /// Creates a matrix of `rows` rows and `cols` columns, and initializes
+    /// each element in place with the result of `f` called with its row and
+    /// column.
+    ///
+    /// Elements are initialized row after row. If `f` panics, the elements
+    /// that were already initialized are dropped and the memory is freed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::Matrix2D;
+    ///
+    /// fn main() {
+    ///     let identity = Matrix2D::new_with(3, 3, |row, col| if row == col { 1.0 } else { 0.0 });
+    ///     assert_eq!(identity.row(1), &[0.0, 1.0, 0.0]);
+    ///
+    ///     let labels = Matrix2D::new_with(2, 2, |row, col| format!("{}{}", row, col));
+    ///     assert_eq!(labels[(1, 0)], "10");
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of elements overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_with<F: FnMut(usize, usize) -> T>(rows: usize, cols: usize, mut f: F) -> Self {
+        let (stride, len) = match dimensions(rows, cols, 1) {
+            Ok(d) => d,
+            Err(e) => e.handle(),
+        };
+        Matrix2D {
+            data: BoxSliceExt::new_slice_with(len, |i| f(i / cols, i % cols)),
+            rows,
+            cols,
+            stride,
+        }
+    }
+
+    /// Fallible [`Matrix2D::new_with`]
+    ///
+    /// [`Matrix2D::new_with`]: #method.new_with
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the
+    /// number of elements overflows. `f` is not called in that case.
+    pub fn try_new_with<F: FnMut(usize, usize) -> T>(
+        rows: usize,
+        cols: usize,
+        mut f: F,
+    ) -> Option<Self> {
+        let (stride, len) = dimensions(rows, cols, 1).ok()?;
+        Some(Matrix2D {
+            data: BoxSliceExt::try_new_slice_with(len, |i| f(i / cols, i % cols))?,
+            rows,
+            cols,
+            stride,
+        })
+    }
+
+    /// Returns the number of rows.
+    #[inline]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the number of columns.
+    #[inline]
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Returns the number of elements between the starts of two consecutive
+    /// rows.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the given row, without its padding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row` is out of bounds.
+    #[inline]
+    pub fn row(&self, row: usize) -> &[T] {
+        assert!(row < self.rows, "row out of bounds");
+        let start = row * self.stride;
+        &self.data[start..start + self.cols]
+    }
+
+    /// Returns the given row, without its padding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row` is out of bounds.
+    #[inline]
+    pub fn row_mut(&mut self, row: usize) -> &mut [T] {
+        assert!(row < self.rows, "row out of bounds");
+        let start = row * self.stride;
+        &mut self.data[start..start + self.cols]
+    }
+
+    /// Returns all the elements, including the padding at the end of rows.
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Returns all the elements, including the padding at the end of rows.
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    /// Turns the matrix into the boxed slice of all its elements, including
+    /// the padding at the end of rows.
+    #[inline]
+    pub fn into_boxed_slice(self) -> Box<[T]> {
+        self.data
+    }
+}
+
+impl<T> Index<(usize, usize)> for Matrix2D<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        &self.row(row)[col]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Matrix2D<T> {
+    #[inline]
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut T {
+        &mut self.row_mut(row)[col]
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Matrix2D<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries((0..self.rows).map(|row| self.row(row)))
+            .finish()
+    }
+}
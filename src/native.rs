@@ -0,0 +1,186 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Zeroed allocations through the native entry points of the allocator
+// backing the global allocator, when it is known. The memory is deallocated
+// by the global allocator, so this has to match what it does exactly. As
+// the crate can't know what the global allocator is, and features can be
+// enabled by any crate in the dependency graph, the native entry points are
+// only used after the program asserted it with one of the `unsafe`
+// functions below.
+
+use alloc::alloc::Layout;
+#[cfg(any(feature = "jemalloc", feature = "windows_heap"))]
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// Whether `assume_global_allocator_is_jemalloc` was called.
+#[cfg(feature = "jemalloc")]
+static JEMALLOC: AtomicBool = AtomicBool::new(false);
+
+// Whether `assume_global_allocator_is_system` was called.
+#[cfg(feature = "windows_heap")]
+static SYSTEM: AtomicBool = AtomicBool::new(false);
+
+/// Allocates zeroed boxes with jemalloc's [`mallocx(..., MALLOCX_ZERO)`]
+/// directly from now on.
+///
+/// [`mallocx(..., MALLOCX_ZERO)`]: http://jemalloc.net/jemalloc.3.html#MALLOCX_ZERO
+///
+/// # Safety
+///
+/// The global allocator of the program must be `tikv-jemallocator`, since
+/// it deallocates the boxes.
+#[cfg(feature = "jemalloc")]
+pub unsafe fn assume_global_allocator_is_jemalloc() {
+    JEMALLOC.store(true, Ordering::Relaxed);
+}
+
+/// Allocates zeroed boxes with [`HeapAlloc(..., HEAP_ZERO_MEMORY, ...)`]
+/// directly from now on, on Windows. This does nothing on other platforms.
+///
+/// [`HeapAlloc(..., HEAP_ZERO_MEMORY, ...)`]: https://msdn.microsoft.com/en-us/library/windows/desktop/aa366597(v=vs.85).aspx#HEAP_ZERO_MEMORY
+///
+/// # Safety
+///
+/// The global allocator of the program must be the standard library's
+/// `System` allocator, since it deallocates the boxes.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{assume_global_allocator_is_system, BoxExt};
+///
+/// fn main() {
+///     // This program doesn't set a global allocator.
+///     unsafe { assume_global_allocator_is_system() };
+///     let buf: Box<[u8; 4096]> = BoxExt::new_zeroed();
+///     assert!(buf.iter().all(|&b| b == 0));
+/// }
+/// ```
+#[cfg(feature = "windows_heap")]
+pub unsafe fn assume_global_allocator_is_system() {
+    SYSTEM.store(true, Ordering::Relaxed);
+}
+
+// Whether `alloc_zeroed` does anything else than the global allocator's
+// `alloc_zeroed`.
+#[inline]
+pub(crate) fn is_native() -> bool {
+    #[cfg(feature = "jemalloc")]
+    {
+        if JEMALLOC.load(Ordering::Relaxed) {
+            return true;
+        }
+    }
+    #[cfg(all(feature = "windows_heap", windows))]
+    {
+        if SYSTEM.load(Ordering::Relaxed) {
+            return true;
+        }
+    }
+    cfg!(all(
+        feature = "wasm_pages",
+        target_arch = "wasm32",
+        not(target_feature = "atomics")
+    ))
+}
+
+#[inline]
+pub(crate) unsafe fn alloc_zeroed(layout: Layout) -> *mut u8 {
+    #[cfg(feature = "jemalloc")]
+    {
+        if JEMALLOC.load(Ordering::Relaxed) {
+            return jemalloc_zeroed(layout);
+        }
+    }
+    #[cfg(all(feature = "windows_heap", windows))]
+    {
+        if SYSTEM.load(Ordering::Relaxed) {
+            return heap_zeroed(layout);
+        }
+    }
+    #[cfg(all(
+        feature = "wasm_pages",
+        target_arch = "wasm32",
+        not(target_feature = "atomics")
+    ))]
+    {
+        wasm_zeroed(layout)
+    }
+    #[cfg(not(all(
+        feature = "wasm_pages",
+        target_arch = "wasm32",
+        not(target_feature = "atomics")
+    )))]
+    {
+        alloc::alloc::alloc_zeroed(layout)
+    }
+}
+
+// jemalloc, through `tikv-jemallocator`, which links the same jemalloc as
+// `tikv-jemalloc-sys`, and can free memory with any alignment flags.
+#[cfg(feature = "jemalloc")]
+#[inline]
+unsafe fn jemalloc_zeroed(layout: Layout) -> *mut u8 {
+    use tikv_jemalloc_sys::{mallocx, MALLOCX_ALIGN, MALLOCX_ZERO};
+
+    mallocx(layout.size(), MALLOCX_ALIGN(layout.align()) | MALLOCX_ZERO) as *mut u8
+}
+
+// The standard library's `System` allocator on Windows, which uses the
+// process heap directly for alignments up to `MIN_ALIGN`, and adds a header
+// for larger alignments, in which case we defer to it.
+#[cfg(all(feature = "windows_heap", windows))]
+#[inline]
+unsafe fn heap_zeroed(layout: Layout) -> *mut u8 {
+    use core::mem;
+    use windows_sys::Win32::System::Memory::{GetProcessHeap, HeapAlloc, HEAP_ZERO_MEMORY};
+
+    // The alignment `HeapAlloc` guarantees.
+    const MIN_ALIGN: usize = mem::size_of::<usize>() * 2;
+
+    if layout.align() > MIN_ALIGN {
+        return alloc::alloc::alloc_zeroed(layout);
+    }
+    let heap = GetProcessHeap();
+    if heap.is_null() {
+        return core::ptr::null_mut();
+    }
+    HeapAlloc(heap, HEAP_ZERO_MEMORY, layout.size()) as *mut u8
+}
+
+// On WebAssembly, linear memory is zeroed when it grows, and never shrinks,
+// so memory above the size of linear memory before allocating has never been
+// written. Large allocations are obtained uninitialized from the global
+// allocator, and only the part below that limit is zeroed. When the
+// allocator grows memory to satisfy the allocation, this skips zeroing
+// entirely. With shared memory, another thread may grow memory and write to
+// it concurrently, so this is disabled.
+#[cfg(all(
+    feature = "wasm_pages",
+    target_arch = "wasm32",
+    not(target_feature = "atomics")
+))]
+#[inline]
+unsafe fn wasm_zeroed(layout: Layout) -> *mut u8 {
+    use core::arch::wasm32::memory_size;
+    use core::{cmp, ptr};
+
+    const PAGE_SIZE: usize = 65536;
+
+    if layout.size() < PAGE_SIZE {
+        return alloc::alloc::alloc_zeroed(layout);
+    }
+    let end = memory_size(0) * PAGE_SIZE;
+    let raw = alloc::alloc::alloc(layout);
+    if !raw.is_null() && (raw as usize) < end {
+        ptr::write_bytes(raw, 0, cmp::min(layout.size(), end - raw as usize));
+    }
+    raw
+}
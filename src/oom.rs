@@ -0,0 +1,174 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Hooks invoked when the allocator fails to provide memory.
+//!
+//! A hook is given the layout of the allocation that failed, and returns
+//! whether the allocation should be retried, e.g. because it released
+//! memory by dropping caches. The allocation is retried once, and only fails
+//! if that attempt fails as well: the `try_*` functions then return `None`,
+//! and the others call [`handle_alloc_error`].
+//!
+//! A hook set with [`scope`] applies to the current thread, and takes
+//! precedence over the global hook set with [`set_hook`]. Hooks are not
+//! invoked for allocations failing while a hook runs on the same thread, nor
+//! for allocations exceeding a [`budget`].
+//!
+//! Hooks cover memory the crate gets from the global allocator, or from the
+//! allocator set with `set_boxext_allocator`. Allocations that don't go
+//! through this crate, such as `Box::new` or `Vec::with_capacity`, are not
+//! covered.
+//!
+//! [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+//! [`scope`]: fn.scope.html
+//! [`set_hook`]: fn.set_hook.html
+//! [`budget`]: ../budget/index.html
+//!
+//! # Example
+//!
+//! ```
+//! extern crate boxext;
+//! use boxext::oom;
+//! use std::sync::Mutex;
+//!
+//! static CACHE: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+//!
+//! fn main() {
+//!     oom::set_hook(Box::new(|_layout| {
+//!         let mut cache = CACHE.lock().unwrap();
+//!         let released = !cache.is_empty();
+//!         cache.clear();
+//!         released
+//!     }));
+//!     // ... `BoxExt::try_new_zeroed` and friends now drop the cache and
+//!     // try again before giving up.
+//! #   drop(oom::take_hook());
+//! }
+//! ```
+
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::mem;
+use std::sync::RwLock;
+
+/// A hook invoked when the allocator fails to provide memory.
+pub type Hook = Box<dyn Fn(Layout) -> bool + Send + Sync>;
+
+static GLOBAL: RwLock<Option<Hook>> = RwLock::new(None);
+
+thread_local! {
+    static SCOPED: Cell<Option<*const (dyn Fn(Layout) -> bool + 'static)>> = const { Cell::new(None) };
+    static RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Sets the global hook, replacing the previous one.
+///
+/// The hook must not call `set_hook` or [`take_hook`], which would
+/// deadlock.
+///
+/// [`take_hook`]: fn.take_hook.html
+pub fn set_hook(hook: Hook) {
+    *GLOBAL.write().unwrap_or_else(|e| e.into_inner()) = Some(hook);
+}
+
+/// Removes the global hook, and returns it.
+pub fn take_hook() -> Option<Hook> {
+    GLOBAL.write().unwrap_or_else(|e| e.into_inner()).take()
+}
+
+// Restores the previous scoped hook when a scope ends, even when unwinding.
+struct RestoreScoped(Option<*const (dyn Fn(Layout) -> bool + 'static)>);
+
+impl Drop for RestoreScoped {
+    fn drop(&mut self) {
+        SCOPED.with(|s| s.set(self.0));
+    }
+}
+
+/// Calls `f` with `hook` as the hook for the current thread.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{oom, BoxExt};
+/// use std::alloc::{GlobalAlloc, Layout, System};
+/// use std::cell::Cell;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+///
+/// // An allocator failing the next large allocation once asked to.
+/// struct Flaky(AtomicBool);
+///
+/// unsafe impl GlobalAlloc for Flaky {
+///     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+///         if layout.size() >= 65536 && self.0.swap(false, Ordering::SeqCst) {
+///             return std::ptr::null_mut();
+///         }
+///         System.alloc(layout)
+///     }
+///
+///     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+///         System.dealloc(ptr, layout)
+///     }
+/// }
+///
+/// #[global_allocator]
+/// static ALLOC: Flaky = Flaky(AtomicBool::new(false));
+///
+/// fn main() {
+///     let hook_calls = Cell::new(0);
+///     ALLOC.0.store(true, Ordering::SeqCst);
+///     let buf = oom::scope(
+///         |_layout| {
+///             hook_calls.set(hook_calls.get() + 1);
+///             true
+///         },
+///         || Box::<[u8; 65536]>::try_new_zeroed(),
+///     );
+///     assert!(buf.is_some());
+///     assert_eq!(hook_calls.get(), 1);
+/// }
+/// ```
+pub fn scope<H, R, F>(hook: H, f: F) -> R
+where
+    H: Fn(Layout) -> bool,
+    F: FnOnce() -> R,
+{
+    let hook: &dyn Fn(Layout) -> bool = &hook;
+    // The pointer doesn't outlive `hook`, since it is reset when the scope
+    // ends.
+    let hook: *const (dyn Fn(Layout) -> bool + 'static) = unsafe { mem::transmute(hook) };
+    let _restore = RestoreScoped(SCOPED.with(|s| s.replace(Some(hook))));
+    f()
+}
+
+// Resets the flag preventing hooks from being invoked recursively, even
+// when unwinding.
+struct Running;
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        RUNNING.with(|r| r.set(false));
+    }
+}
+
+// Invokes the hook for the current thread, or the global hook, and returns
+// whether the allocation should be retried.
+pub(crate) fn run_hook(layout: Layout) -> bool {
+    if RUNNING.with(|r| r.replace(true)) {
+        return false;
+    }
+    let _running = Running;
+    if let Some(hook) = SCOPED.with(Cell::get) {
+        return unsafe { (*hook)(layout) };
+    }
+    match *GLOBAL.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(ref hook) => hook(layout),
+        None => false,
+    }
+}
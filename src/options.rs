@@ -0,0 +1,202 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::cmp;
+use core::mem::{self, MaybeUninit};
+use heap::try_alloc_layout;
+#[cfg(feature = "os_alloc")]
+use os_box::try_alloc_zeroed;
+#[cfg(all(feature = "numa", target_os = "linux"))]
+use os_box::try_map_on_node;
+use std::alloc::Layout;
+use {AlignedBox, AllocError};
+
+/// Options controlling how memory is obtained for a heap allocation.
+///
+/// This allows to combine several allocation knobs in a single call, e.g.
+/// zeroing and over-alignment, and, with the `os_alloc` and `numa`
+/// features, huge pages and NUMA node binding.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::AllocOptions;
+///
+/// fn main() {
+///     let buf = AllocOptions::new().zeroed().align(64).alloc::<[u32; 32]>();
+///     let buf = unsafe { buf.assume_init() };
+///     assert_eq!(buf.align(), 64);
+///     assert_eq!(&*buf as *const _ as usize % 64, 0);
+///     assert_eq!(*buf, [0; 32]);
+///
+///     let buf = AllocOptions::new().align(4096).alloc().write(42u8);
+///     assert_eq!(*buf, 42);
+///     assert_eq!(&*buf as *const _ as usize % 4096, 0);
+/// }
+/// ```
+///
+/// With the `numa` feature, on Linux:
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::AllocOptions;
+///
+/// fn main() {
+/// #   #[cfg(all(feature = "numa", target_os = "linux"))]
+/// #   {
+///     let options = AllocOptions::new().zeroed().align(64).huge_pages().on_node(0);
+///     if let Some(arena) = options.try_alloc::<[u64; 1 << 20]>() {
+///         let arena = unsafe { arena.assume_init() };
+///         assert!(arena.is_mapped());
+///         assert_eq!(arena[12345], 0);
+///     }
+/// #   }
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocOptions {
+    zeroed: bool,
+    align: usize,
+    #[cfg(feature = "os_alloc")]
+    huge_pages: bool,
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    node: Option<usize>,
+}
+
+impl Default for AllocOptions {
+    fn default() -> Self {
+        AllocOptions::new()
+    }
+}
+
+impl AllocOptions {
+    /// Returns the default options: uninitialized memory with the natural
+    /// alignment of the allocated type.
+    pub fn new() -> Self {
+        AllocOptions {
+            zeroed: false,
+            align: 1,
+            #[cfg(feature = "os_alloc")]
+            huge_pages: false,
+            #[cfg(all(feature = "numa", target_os = "linux"))]
+            node: None,
+        }
+    }
+
+    /// Obtains zeroed memory directly from the allocator.
+    pub fn zeroed(mut self) -> Self {
+        self.zeroed = true;
+        self
+    }
+
+    /// Aligns the memory to at least `align` bytes.
+    ///
+    /// `align` must be a power of two. The alignment of the allocated type
+    /// is used instead if it is larger.
+    pub fn align(mut self, align: usize) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Obtains the memory directly from the operating system, backed by
+    /// huge pages when possible, as with [`OsBox::new_zeroed_hugepage`].
+    ///
+    /// Huge pages are only a hint: if they are not available, or if the
+    /// requested alignment is larger than a page, normal pages or the
+    /// allocator are used instead. The memory is zeroed either way.
+    ///
+    /// [`OsBox::new_zeroed_hugepage`]: struct.OsBox.html#method.new_zeroed_hugepage
+    #[cfg(feature = "os_alloc")]
+    pub fn huge_pages(mut self) -> Self {
+        self.huge_pages = true;
+        self
+    }
+
+    /// Obtains the memory directly from the operating system, bound to the
+    /// NUMA node `node`, as with [`OsBox::new_zeroed_on_node`].
+    ///
+    /// Allocation fails if the memory couldn't be bound to the node, e.g.
+    /// because it doesn't exist, or if the requested alignment is larger
+    /// than a page. The memory is zeroed either way.
+    ///
+    /// [`OsBox::new_zeroed_on_node`]: struct.OsBox.html#method.new_zeroed_on_node
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    pub fn on_node(mut self, node: usize) -> Self {
+        self.node = Some(node);
+        self
+    }
+
+    fn layout<T>(&self) -> Option<Layout> {
+        let align = cmp::max(self.align, mem::align_of::<T>());
+        Layout::from_size_align(mem::size_of::<T>(), align).ok()
+    }
+
+    /// Allocates memory for a `T` with the given options.
+    ///
+    /// The memory is either uninitialized, or zeroed if [`zeroed`] was
+    /// used, in which case, for types implementing [`Zero`], it is safe to
+    /// call [`assume_init`] on the result.
+    ///
+    /// This doesn't actually allocate if `T` is zero-sized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the requested alignment is not a power of two, and calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`zeroed`]: #method.zeroed
+    /// [`Zero`]: trait.Zero.html
+    /// [`assume_init`]: struct.AlignedBox.html#method.assume_init
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn alloc<T>(&self) -> AlignedBox<MaybeUninit<T>> {
+        let layout = self.layout::<T>().expect("invalid alignment");
+        unsafe {
+            let (raw, map_size) = match self.try_alloc_layout(layout) {
+                Ok(result) => result,
+                Err(e) => e.handle(),
+            };
+            AlignedBox::from_raw_parts(raw as *mut MaybeUninit<T>, layout, map_size)
+        }
+    }
+
+    /// Fallible [`AllocOptions::alloc`]
+    ///
+    /// [`AllocOptions::alloc`]: #method.alloc
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the
+    /// requested alignment is not a power of two.
+    pub fn try_alloc<T>(&self) -> Option<AlignedBox<MaybeUninit<T>>> {
+        let layout = self.layout::<T>()?;
+        unsafe {
+            let (raw, map_size) = self.try_alloc_layout(layout).ok()?;
+            Some(AlignedBox::from_raw_parts(raw as *mut MaybeUninit<T>, layout, map_size))
+        }
+    }
+
+    // Allocates memory for the given layout, from the backend the options
+    // select. Returns the memory and the size of the mapping it comes from,
+    // which is 0 if it came from the allocator.
+    unsafe fn try_alloc_layout(&self, layout: Layout) -> Result<(*mut u8, usize), AllocError> {
+        #[cfg(all(feature = "numa", target_os = "linux"))]
+        {
+            if let Some(node) = self.node {
+                return try_map_on_node(layout, node, self.huge_pages)?
+                    .map_err(|_| AllocError::alloc_failed(layout));
+            }
+        }
+        #[cfg(feature = "os_alloc")]
+        {
+            if self.huge_pages {
+                return try_alloc_zeroed(layout, true);
+            }
+        }
+        Ok((try_alloc_layout(layout, self.zeroed)?, 0))
+    }
+}
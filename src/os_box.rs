@@ -0,0 +1,987 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use heap::{charge_budget, dealloc_layout, record_alloc, try_alloc_layout, try_realloc_layout};
+use std::alloc::Layout;
+#[cfg(all(feature = "numa", target_os = "linux"))]
+use std::error::Error;
+use std::io;
+use {AllocError, Zero};
+
+// Allocations of at least this size are obtained directly from the OS.
+const MAP_THRESHOLD: usize = 1 << 20;
+
+// Alignment mapped memory is guaranteed to have. Pages are at least this
+// large on all supported platforms.
+const MAP_ALIGN: usize = 4096;
+
+// Rounds `size` up to a multiple of `page_size`, which must be a power of
+// two.
+fn round_up(size: usize, page_size: usize) -> Option<usize> {
+    Some(size.checked_add(page_size - 1)? & !(page_size - 1))
+}
+
+#[cfg(unix)]
+mod sys {
+    use core::ptr;
+    use libc;
+
+    pub const SUPPORTED: bool = true;
+
+    // Size of huge pages requested with `MAP_HUGETLB`, which is the default
+    // on x86-64 and AArch64 with 4 KiB pages.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const HUGE_PAGE_SIZE: usize = 2 << 20;
+
+    unsafe fn mmap(size: usize, flags: i32) -> Option<*mut u8> {
+        let raw = libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+            -1,
+            0,
+        );
+        if raw == libc::MAP_FAILED {
+            None
+        } else {
+            Some(raw as *mut u8)
+        }
+    }
+
+    // Maps at least `size` bytes of anonymous memory, which the kernel
+    // provides zeroed, backed by huge pages if `huge` is true and they are
+    // available. Returns the memory and the size of the mapping.
+    pub unsafe fn map(size: usize, huge: bool) -> Option<(*mut u8, usize)> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if huge {
+                if let Some(huge_size) = super::round_up(size, HUGE_PAGE_SIZE) {
+                    if let Some(raw) = mmap(huge_size, libc::MAP_HUGETLB) {
+                        return Some((raw, huge_size));
+                    }
+                }
+            }
+        }
+        let raw = mmap(size, 0)?;
+        // Without reserved huge pages, ask for transparent huge pages
+        // instead. This is only a hint.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if huge {
+                libc::madvise(raw as *mut libc::c_void, size, libc::MADV_HUGEPAGE);
+            }
+        }
+        let _ = huge;
+        Some((raw, size))
+    }
+
+    // Grows a mapping of `old_size` bytes to `new_size` bytes with
+    // `mremap(MREMAP_MAYMOVE)`, which extends it in place when possible,
+    // and moves its pages elsewhere without copying them otherwise. The
+    // additional memory is zeroed. Returns the new address of the memory.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub unsafe fn remap(ptr: *mut u8, old_size: usize, new_size: usize) -> Option<*mut u8> {
+        let raw = libc::mremap(
+            ptr as *mut libc::c_void,
+            old_size,
+            new_size,
+            libc::MREMAP_MAYMOVE,
+        );
+        if raw == libc::MAP_FAILED {
+            None
+        } else {
+            Some(raw as *mut u8)
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub unsafe fn remap(_ptr: *mut u8, _old_size: usize, _new_size: usize) -> Option<*mut u8> {
+        None
+    }
+
+    pub unsafe fn unmap(ptr: *mut u8, size: usize) {
+        libc::munmap(ptr as *mut libc::c_void, size);
+    }
+
+    pub unsafe fn lock(ptr: *mut u8, size: usize) -> bool {
+        libc::mlock(ptr as *const libc::c_void, size) == 0
+    }
+
+    pub unsafe fn unlock(ptr: *mut u8, size: usize) {
+        libc::munlock(ptr as *const libc::c_void, size);
+    }
+
+    // Binds the memory at `ptr` to the NUMA node `node`, with `mbind`. As
+    // the memory was not touched yet, pages are allocated on that node when
+    // they are first touched.
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    pub unsafe fn bind(ptr: *mut u8, size: usize, node: usize) -> bool {
+        const MPOL_BIND: i32 = 2;
+        // The mask is made of `unsigned long`s, which are as large as
+        // `usize` on Linux.
+        let bits = 8 * ::core::mem::size_of::<usize>();
+        let mut mask = vec![0usize; node / bits + 1];
+        mask[node / bits] |= 1 << (node % bits);
+        // The kernel ignores the last bit of `maxnode`.
+        let maxnode = mask.len() * bits + 1;
+        libc::syscall(
+            libc::SYS_mbind,
+            ptr,
+            size,
+            MPOL_BIND,
+            mask.as_ptr(),
+            maxnode,
+            0u32,
+        ) == 0
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use core::ptr;
+    use windows_sys::Win32::System::Memory::{
+        GetLargePageMinimum, VirtualAlloc, VirtualFree, VirtualLock, VirtualUnlock, MEM_COMMIT,
+        MEM_LARGE_PAGES, MEM_RELEASE, MEM_RESERVE, PAGE_READWRITE,
+    };
+
+    pub const SUPPORTED: bool = true;
+
+    // How many times the requested size is reserved as address space for
+    // normal pages, so that `remap` can commit more of it in place. Address
+    // space is only plentiful on 64-bit targets.
+    #[cfg(target_pointer_width = "64")]
+    const RESERVE_FACTOR: usize = 4;
+    #[cfg(not(target_pointer_width = "64"))]
+    const RESERVE_FACTOR: usize = 1;
+
+    unsafe fn virtual_alloc(size: usize, flags: u32) -> Option<*mut u8> {
+        let raw = VirtualAlloc(ptr::null(), size, MEM_COMMIT | MEM_RESERVE | flags, PAGE_READWRITE);
+        if raw.is_null() {
+            None
+        } else {
+            Some(raw as *mut u8)
+        }
+    }
+
+    // Commits at least `size` bytes of memory, which the kernel provides
+    // zeroed, backed by large pages if `huge` is true and they are available
+    // to the process. Returns the memory and the size of the allocation.
+    pub unsafe fn map(size: usize, huge: bool) -> Option<(*mut u8, usize)> {
+        if huge {
+            let large_page_size = GetLargePageMinimum();
+            if large_page_size != 0 {
+                if let Some(large_size) = super::round_up(size, large_page_size) {
+                    if let Some(raw) = virtual_alloc(large_size, MEM_LARGE_PAGES) {
+                        return Some((raw, large_size));
+                    }
+                }
+            }
+        }
+        if let Some(reserve_size) = size.checked_mul(RESERVE_FACTOR) {
+            if reserve_size > size {
+                let raw = VirtualAlloc(ptr::null(), reserve_size, MEM_RESERVE, PAGE_READWRITE);
+                if !raw.is_null() {
+                    if !VirtualAlloc(raw, size, MEM_COMMIT, PAGE_READWRITE).is_null() {
+                        return Some((raw as *mut u8, size));
+                    }
+                    VirtualFree(raw, 0, MEM_RELEASE);
+                }
+            }
+        }
+        Some((virtual_alloc(size, 0)?, size))
+    }
+
+    // Grows memory committed with `map` from `old_size` bytes to `new_size`
+    // bytes, by committing more of the address space reserved for it. The
+    // additional memory is zeroed. This fails if the reservation is too
+    // small, in which case the memory is left untouched.
+    pub unsafe fn remap(ptr: *mut u8, _old_size: usize, new_size: usize) -> Option<*mut u8> {
+        let raw = VirtualAlloc(ptr as *const _, new_size, MEM_COMMIT, PAGE_READWRITE);
+        if raw.is_null() {
+            None
+        } else {
+            Some(ptr)
+        }
+    }
+
+    pub unsafe fn unmap(ptr: *mut u8, _size: usize) {
+        VirtualFree(ptr as *mut _, 0, MEM_RELEASE);
+    }
+
+    pub unsafe fn lock(ptr: *mut u8, size: usize) -> bool {
+        VirtualLock(ptr as *const _, size) != 0
+    }
+
+    pub unsafe fn unlock(ptr: *mut u8, size: usize) {
+        VirtualUnlock(ptr as *const _, size);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    pub const SUPPORTED: bool = false;
+
+    pub unsafe fn map(_size: usize, _huge: bool) -> Option<(*mut u8, usize)> {
+        None
+    }
+
+    pub unsafe fn remap(_ptr: *mut u8, _old_size: usize, _new_size: usize) -> Option<*mut u8> {
+        unreachable!()
+    }
+
+    pub unsafe fn unmap(_ptr: *mut u8, _size: usize) {
+        unreachable!()
+    }
+
+    pub unsafe fn lock(_ptr: *mut u8, _size: usize) -> bool {
+        unreachable!()
+    }
+
+    pub unsafe fn unlock(_ptr: *mut u8, _size: usize) {
+        unreachable!()
+    }
+}
+
+// Allocates zeroed memory for the given layout, from the OS if it's large
+// enough or if huge pages are requested, and from the allocator otherwise.
+// Returns the memory and the size of the mapping, which is 0 if it came from
+// the allocator.
+#[track_caller]
+pub(crate) unsafe fn try_alloc_zeroed(
+    layout: Layout,
+    huge: bool,
+) -> Result<(*mut u8, usize), AllocError> {
+    let threshold = if huge { 1 } else { MAP_THRESHOLD };
+    if !sys::SUPPORTED || layout.size() < threshold || layout.align() > MAP_ALIGN {
+        return Ok((try_alloc_layout(layout, true)?, 0));
+    }
+    charge_budget(layout)?;
+    let mapping = sys::map(layout.size(), huge);
+    record_alloc(layout, true, mapping.is_some());
+    mapping.ok_or_else(|| AllocError::alloc_failed(layout))
+}
+
+// Maps zeroed memory for the given layout, which must not be zero-sized,
+// and locks it in physical memory. Locking errors are returned as
+// Ok(Err(_)), so that callers can handle allocation errors separately.
+// Returns the memory and the size of the mapping.
+#[track_caller]
+pub(crate) unsafe fn try_map_locked(
+    layout: Layout,
+) -> Result<Result<(*mut u8, usize), io::Error>, AllocError> {
+    if !sys::SUPPORTED {
+        return Ok(Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "memory locking is not supported on this platform",
+        )));
+    }
+    if layout.align() > MAP_ALIGN {
+        return Err(AllocError::alloc_failed(layout));
+    }
+    charge_budget(layout)?;
+    let mapping = sys::map(layout.size(), false);
+    record_alloc(layout, true, mapping.is_some());
+    let (raw, size) = match mapping {
+        Some(mapping) => mapping,
+        None => return Err(AllocError::alloc_failed(layout)),
+    };
+    if !sys::lock(raw, size) {
+        let e = io::Error::last_os_error();
+        sys::unmap(raw, size);
+        return Ok(Err(e));
+    }
+    Ok(Ok((raw, size)))
+}
+
+// Maps zeroed memory for the given layout, backed by huge pages if `huge` is
+// true and they are available, and binds it to the given NUMA node. Binding
+// errors are returned as Ok(Err(_)), so that callers can handle allocation
+// errors separately. Returns the memory and the size of the mapping, which is
+// 0 if the layout is zero-sized.
+#[cfg(all(feature = "numa", target_os = "linux"))]
+#[track_caller]
+pub(crate) unsafe fn try_map_on_node(
+    layout: Layout,
+    node: usize,
+    huge: bool,
+) -> Result<Result<(*mut u8, usize), io::Error>, AllocError> {
+    if layout.size() == 0 {
+        return Ok(Ok((try_alloc_layout(layout, true)?, 0)));
+    }
+    if layout.align() > MAP_ALIGN {
+        return Err(AllocError::alloc_failed(layout));
+    }
+    charge_budget(layout)?;
+    let mapping = sys::map(layout.size(), huge);
+    record_alloc(layout, true, mapping.is_some());
+    let (raw, size) = match mapping {
+        Some(mapping) => mapping,
+        None => return Err(AllocError::alloc_failed(layout)),
+    };
+    if !sys::bind(raw, size, node) {
+        let e = io::Error::last_os_error();
+        sys::unmap(raw, size);
+        return Ok(Err(e));
+    }
+    Ok(Ok((raw, size)))
+}
+
+// Releases memory obtained from `try_alloc_zeroed` or `try_map_on_node`,
+// given the size of the mapping they returned.
+pub(crate) unsafe fn release(ptr: *mut u8, layout: Layout, map_size: usize) {
+    if map_size != 0 {
+        sys::unmap(ptr, map_size);
+    } else {
+        dealloc_layout(ptr, layout);
+    }
+}
+
+// Grows zeroed memory obtained from `try_alloc_zeroed` or `try_map_on_node`
+// for the given layout, given the size of the mapping they returned, to the
+// new layout, which has the same alignment. The additional memory is zeroed.
+// Mappings are grown with `sys::remap` when possible, and moved to a new
+// mapping otherwise. Memory from the allocator is reallocated, or moved to a
+// new mapping if it becomes large enough. Returns the memory and the size of
+// the mapping, as `try_alloc_zeroed` does. On failure, the original memory is
+// left untouched.
+#[track_caller]
+unsafe fn try_grow_zeroed(
+    ptr: *mut u8,
+    layout: Layout,
+    map_size: usize,
+    new_layout: Layout,
+) -> Result<(*mut u8, usize), AllocError> {
+    if new_layout.size() == layout.size() {
+        return Ok((ptr, map_size));
+    }
+    if layout.size() == 0 {
+        // Nothing was allocated yet.
+        return try_alloc_zeroed(new_layout, false);
+    }
+    if map_size == 0
+        && (!sys::SUPPORTED || new_layout.size() < MAP_THRESHOLD || new_layout.align() > MAP_ALIGN)
+    {
+        let raw = try_realloc_layout(ptr, layout, new_layout.size())?;
+        ptr::write_bytes(raw.add(layout.size()), 0, new_layout.size() - layout.size());
+        return Ok((raw, 0));
+    }
+    charge_budget(Layout::from_size_align_unchecked(
+        new_layout.size() - layout.size(),
+        new_layout.align(),
+    ))?;
+    if new_layout.size() <= map_size {
+        // The mapping was rounded up to whole huge pages, and the new size
+        // still fits.
+        return Ok((ptr, map_size));
+    }
+    if map_size != 0 {
+        if let Some(raw) = sys::remap(ptr, map_size, new_layout.size()) {
+            record_alloc(new_layout, true, true);
+            return Ok((raw, new_layout.size()));
+        }
+    }
+    let mapping = sys::map(new_layout.size(), false);
+    record_alloc(new_layout, true, mapping.is_some());
+    let (raw, new_map_size) = match mapping {
+        Some(mapping) => mapping,
+        None => return Err(AllocError::alloc_failed(new_layout)),
+    };
+    ptr::copy_nonoverlapping(ptr, raw, layout.size());
+    release(ptr, layout, map_size);
+    Ok((raw, new_map_size))
+}
+
+// Unlocks and unmaps memory obtained from `try_map_locked`.
+pub(crate) unsafe fn unmap_locked(ptr: *mut u8, size: usize) {
+    sys::unlock(ptr, size);
+    sys::unmap(ptr, size);
+}
+
+/// The error type for [`OsBox::try_new_zeroed_on_node`] and
+/// [`OsBox::try_new_zeroed_slice_on_node`].
+///
+/// [`OsBox::try_new_zeroed_on_node`]: struct.OsBox.html#method.try_new_zeroed_on_node
+/// [`OsBox::try_new_zeroed_slice_on_node`]: struct.OsBox.html#method.try_new_zeroed_slice_on_node
+#[cfg(all(feature = "numa", target_os = "linux"))]
+#[derive(Debug)]
+pub enum NodeAllocError {
+    /// Memory couldn't be allocated.
+    Alloc(AllocError),
+    /// Memory couldn't be bound to the NUMA node, e.g. because the node
+    /// doesn't exist.
+    Bind(io::Error),
+}
+
+#[cfg(all(feature = "numa", target_os = "linux"))]
+impl fmt::Display for NodeAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NodeAllocError::Alloc(ref e) => fmt::Display::fmt(e, f),
+            NodeAllocError::Bind(ref e) => write!(f, "failed to bind memory to node: {}", e),
+        }
+    }
+}
+
+#[cfg(all(feature = "numa", target_os = "linux"))]
+impl Error for NodeAllocError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            NodeAllocError::Alloc(ref e) => Some(e),
+            NodeAllocError::Bind(ref e) => Some(e),
+        }
+    }
+}
+
+/// A zeroed heap allocation, obtained directly from the operating system
+/// when it is large.
+///
+/// Allocations of 1 MiB or more are served by `mmap` on Unix systems, and
+/// `VirtualAlloc` on Windows, which hand out pages that are already zeroed,
+/// and only back them with physical memory when they are first touched.
+/// Smaller allocations go through the global allocator. Huge pages can be
+/// requested with [`OsBox::new_zeroed_hugepage`] and
+/// [`OsBox::new_zeroed_slice_hugepage`].
+///
+/// [`OsBox::new_zeroed_hugepage`]: #method.new_zeroed_hugepage
+/// [`OsBox::new_zeroed_slice_hugepage`]: #method.new_zeroed_slice_hugepage
+///
+/// With the `numa` feature, on Linux, the memory can be bound to a NUMA node
+/// with [`OsBox::new_zeroed_on_node`] and
+/// [`OsBox::new_zeroed_slice_on_node`].
+///
+/// [`OsBox::new_zeroed_on_node`]: #method.new_zeroed_on_node
+/// [`OsBox::new_zeroed_slice_on_node`]: #method.new_zeroed_slice_on_node
+///
+/// This dereferences to `T`. Contrary to a `Box<T>`, it remembers how its
+/// memory was obtained, which is necessary to release it properly.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::OsBox;
+///
+/// fn main() {
+///     let mut grid: OsBox<[u32]> = OsBox::new_zeroed_slice(4 << 20);
+/// #   #[cfg(any(unix, windows))]
+///     assert!(grid.is_mapped());
+///     assert_eq!(grid.len(), 4 << 20);
+///     assert_eq!(grid[12345], 0);
+///     grid[12345] = 1;
+///
+///     let small: OsBox<[u64; 16]> = OsBox::new_zeroed();
+///     assert!(!small.is_mapped());
+///     assert_eq!(*small, [0; 16]);
+/// }
+/// ```
+pub struct OsBox<T: ?Sized> {
+    ptr: NonNull<T>,
+    layout: Layout,
+    // Size of the mapping the memory comes from, or 0 if it comes from the
+    // allocator.
+    map_size: usize,
+}
+
+impl<T: Zero> OsBox<T> {
+    /// Allocates zeroed memory for a `T`.
+    ///
+    /// This doesn't actually allocate if `T` is zero-sized.
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_zeroed() -> Self {
+        match OsBox::try_alloc(false) {
+            Ok(b) => b,
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`OsBox::new_zeroed`]
+    ///
+    /// [`OsBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new_zeroed() -> Option<Self> {
+        OsBox::try_alloc(false).ok()
+    }
+
+    /// Allocates zeroed memory for a `T` from the operating system, backed
+    /// by huge pages when possible.
+    ///
+    /// On Linux, this uses reserved huge pages (`MAP_HUGETLB`) if there are
+    /// enough of them, and falls back to normal pages with a request for
+    /// transparent huge pages (`MADV_HUGEPAGE`) otherwise. On Windows, this
+    /// uses large pages if the process is allowed to, and normal pages
+    /// otherwise. On other Unix systems, this uses normal pages.
+    ///
+    /// Contrary to [`OsBox::new_zeroed`], the memory is obtained from the
+    /// operating system regardless of its size, unless `T` is zero-sized.
+    ///
+    /// [`OsBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_zeroed_hugepage() -> Self {
+        match OsBox::try_alloc(true) {
+            Ok(b) => b,
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`OsBox::new_zeroed_hugepage`]
+    ///
+    /// [`OsBox::new_zeroed_hugepage`]: #method.new_zeroed_hugepage
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new_zeroed_hugepage() -> Option<Self> {
+        OsBox::try_alloc(true).ok()
+    }
+
+    /// Allocates zeroed memory for a `T` from the operating system, bound
+    /// to the NUMA node `node`.
+    ///
+    /// The memory is bound with `mbind(MPOL_BIND)`, so that its pages are
+    /// only ever allocated on that node. This returns an error if the
+    /// memory couldn't be bound, e.g. because the node doesn't exist, or
+    /// because the process is not allowed to use it.
+    ///
+    /// Contrary to [`OsBox::new_zeroed`], the memory is obtained from the
+    /// operating system regardless of its size, unless `T` is zero-sized.
+    ///
+    /// [`OsBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    #[track_caller]
+    pub fn new_zeroed_on_node(node: usize) -> Result<Self, io::Error> {
+        match OsBox::alloc_on_node(node) {
+            Ok(result) => result,
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`OsBox::new_zeroed_on_node`]
+    ///
+    /// [`OsBox::new_zeroed_on_node`]: #method.new_zeroed_on_node
+    ///
+    /// This returns an error if memory couldn't be allocated or bound.
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    #[track_caller]
+    pub fn try_new_zeroed_on_node(node: usize) -> Result<Self, NodeAllocError> {
+        OsBox::alloc_on_node(node)
+            .map_err(NodeAllocError::Alloc)?
+            .map_err(NodeAllocError::Bind)
+    }
+
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    #[track_caller]
+    fn alloc_on_node(node: usize) -> Result<Result<Self, io::Error>, AllocError> {
+        let layout = Layout::new::<T>();
+        let (raw, map_size) = match unsafe { try_map_on_node(layout, node, false)? } {
+            Ok(mapping) => mapping,
+            Err(e) => return Ok(Err(e)),
+        };
+        Ok(Ok(OsBox {
+            ptr: unsafe { NonNull::new_unchecked(raw as *mut T) },
+            layout,
+            map_size,
+        }))
+    }
+
+    fn try_alloc(huge: bool) -> Result<Self, AllocError> {
+        let layout = Layout::new::<T>();
+        let (raw, map_size) = unsafe { try_alloc_zeroed(layout, huge)? };
+        Ok(OsBox {
+            ptr: unsafe { NonNull::new_unchecked(raw as *mut T) },
+            layout,
+            map_size,
+        })
+    }
+}
+
+impl<T: Zero> OsBox<[T]> {
+    /// Allocates a slice of `len` zeroed elements.
+    ///
+    /// This doesn't actually allocate if `len` is 0 or `T` is zero-sized.
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_zeroed_slice(len: usize) -> Self {
+        match OsBox::try_alloc_slice(len, false) {
+            Ok(b) => b,
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`OsBox::new_zeroed_slice`]
+    ///
+    /// [`OsBox::new_zeroed_slice`]: #method.new_zeroed_slice
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the slice overflows.
+    pub fn try_new_zeroed_slice(len: usize) -> Option<Self> {
+        OsBox::try_alloc_slice(len, false).ok()
+    }
+
+    /// Allocates a slice of `len` zeroed elements from the operating system,
+    /// backed by huge pages when possible.
+    ///
+    /// See [`OsBox::new_zeroed_hugepage`].
+    ///
+    /// [`OsBox::new_zeroed_hugepage`]: #method.new_zeroed_hugepage
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::OsBox;
+    ///
+    /// fn main() {
+    ///     let mut weights: OsBox<[f64]> = OsBox::new_zeroed_slice_hugepage(1 << 20);
+    ///     weights[0] = 1.0;
+    ///     assert_eq!(weights.iter().sum::<f64>(), 1.0);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_zeroed_slice_hugepage(len: usize) -> Self {
+        match OsBox::try_alloc_slice(len, true) {
+            Ok(b) => b,
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`OsBox::new_zeroed_slice_hugepage`]
+    ///
+    /// [`OsBox::new_zeroed_slice_hugepage`]: #method.new_zeroed_slice_hugepage
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the slice overflows.
+    pub fn try_new_zeroed_slice_hugepage(len: usize) -> Option<Self> {
+        OsBox::try_alloc_slice(len, true).ok()
+    }
+
+    /// Allocates a slice of `len` zeroed elements from the operating system,
+    /// bound to the NUMA node `node`.
+    ///
+    /// See [`OsBox::new_zeroed_on_node`].
+    ///
+    /// [`OsBox::new_zeroed_on_node`]: #method.new_zeroed_on_node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::OsBox;
+    ///
+    /// fn main() {
+    ///     // One arena per worker, each on the node the worker runs on.
+    ///     let nodes = [0];
+    ///     for &node in &nodes {
+    ///         match OsBox::<[u8]>::new_zeroed_slice_on_node(64 << 20, node) {
+    ///             Ok(mut arena) => {
+    ///                 assert!(arena.is_mapped());
+    ///                 arena[0] = 1;
+    ///             }
+    ///             Err(e) => eprintln!("couldn't bind memory to node {}: {}", node, e),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    #[track_caller]
+    pub fn new_zeroed_slice_on_node(len: usize, node: usize) -> Result<Self, io::Error> {
+        match OsBox::alloc_slice_on_node(len, node) {
+            Ok(result) => result,
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`OsBox::new_zeroed_slice_on_node`]
+    ///
+    /// [`OsBox::new_zeroed_slice_on_node`]: #method.new_zeroed_slice_on_node
+    ///
+    /// This returns an error if memory couldn't be allocated or bound, or
+    /// if the size of the slice overflows.
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    #[track_caller]
+    pub fn try_new_zeroed_slice_on_node(len: usize, node: usize) -> Result<Self, NodeAllocError> {
+        OsBox::alloc_slice_on_node(len, node)
+            .map_err(NodeAllocError::Alloc)?
+            .map_err(NodeAllocError::Bind)
+    }
+
+    /// Grows the slice to `new_len` elements, the new ones being zeroed.
+    ///
+    /// Memory obtained from the operating system is grown without copying
+    /// it when possible: on Linux, with `mremap(MREMAP_MAYMOVE)`, and on
+    /// Windows, by committing more of the address space reserved when the
+    /// memory was allocated. Otherwise, it is copied to a new mapping, which
+    /// is not backed by huge pages nor bound to a NUMA node. Memory obtained
+    /// from the global allocator is reallocated, unless it becomes large
+    /// enough to be obtained from the operating system, in which case it is
+    /// copied to a new mapping.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::OsBox;
+    ///
+    /// fn main() {
+    ///     let mut log: OsBox<[u64]> = OsBox::new_zeroed_slice(4 << 20);
+    ///     log[0] = 1;
+    ///     log[(4 << 20) - 1] = 2;
+    ///     log.grow(64 << 20);
+    ///     assert_eq!(log.len(), 64 << 20);
+    /// #   #[cfg(any(unix, windows))]
+    ///     assert!(log.is_mapped());
+    ///     assert_eq!((log[0], log[(4 << 20) - 1]), (1, 2));
+    ///     assert!(log[4 << 20..].iter().all(|&x| x == 0));
+    ///
+    ///     let mut small: OsBox<[u32]> = OsBox::new_zeroed_slice(4);
+    ///     small.copy_from_slice(&[1, 2, 3, 4]);
+    ///     small.grow(8);
+    ///     assert!(!small.is_mapped());
+    ///     assert_eq!(&small[..], &[1, 2, 3, 4, 0, 0, 0, 0]);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` is smaller than the current length, or if the
+    /// size of the slice overflows. Calls [`handle_alloc_error`] if memory
+    /// couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn grow(&mut self, new_len: usize) {
+        if let Err(e) = self.try_grow(new_len) {
+            e.handle()
+        }
+    }
+
+    /// Fallible [`OsBox::grow`]
+    ///
+    /// [`OsBox::grow`]: #method.grow
+    ///
+    /// This returns an error if memory couldn't be allocated, or if the size
+    /// of the slice overflows, in which case the slice is left untouched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::OsBox;
+    ///
+    /// fn main() {
+    ///     let mut table: OsBox<[u32]> = OsBox::new_zeroed_slice(1 << 18);
+    ///     for (i, x) in table.iter_mut().enumerate() {
+    ///         *x = i as u32;
+    ///     }
+    ///     table.try_grow(3 << 18).unwrap();
+    ///     assert_eq!(table.len(), 3 << 18);
+    ///     assert!(table[..1 << 18].iter().enumerate().all(|(i, &x)| x == i as u32));
+    ///     assert!(table[1 << 18..].iter().all(|&x| x == 0));
+    ///
+    ///     assert!(table.try_grow(usize::max_value()).is_err());
+    ///     assert_eq!(table.len(), 3 << 18);
+    ///     assert_eq!(table[12345], 12345);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` is smaller than the current length.
+    #[track_caller]
+    pub fn try_grow(&mut self, new_len: usize) -> Result<(), AllocError> {
+        let len = self.len();
+        assert!(new_len >= len, "new length is smaller than the current one");
+        let new_layout = match Layout::array::<T>(new_len) {
+            Ok(layout) => layout,
+            Err(_) => return Err(AllocError::capacity_overflow()),
+        };
+        unsafe {
+            let (raw, map_size) = try_grow_zeroed(
+                self.ptr.as_ptr() as *mut u8,
+                self.layout,
+                self.map_size,
+                new_layout,
+            )?;
+            let raw = ptr::slice_from_raw_parts_mut(raw as *mut T, new_len);
+            self.ptr = NonNull::new_unchecked(raw);
+            self.layout = new_layout;
+            self.map_size = map_size;
+        }
+        Ok(())
+    }
+
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    #[track_caller]
+    fn alloc_slice_on_node(len: usize, node: usize) -> Result<Result<Self, io::Error>, AllocError> {
+        let layout = match Layout::array::<T>(len) {
+            Ok(layout) => layout,
+            Err(_) => return Err(AllocError::capacity_overflow()),
+        };
+        let (raw, map_size) = match unsafe { try_map_on_node(layout, node, false)? } {
+            Ok(mapping) => mapping,
+            Err(e) => return Ok(Err(e)),
+        };
+        let raw = ptr::slice_from_raw_parts_mut(raw as *mut T, len);
+        Ok(Ok(OsBox {
+            ptr: unsafe { NonNull::new_unchecked(raw) },
+            layout,
+            map_size,
+        }))
+    }
+
+    fn try_alloc_slice(len: usize, huge: bool) -> Result<Self, AllocError> {
+        let layout = Layout::array::<T>(len).map_err(|_| AllocError::capacity_overflow())?;
+        let (raw, map_size) = unsafe { try_alloc_zeroed(layout, huge)? };
+        let raw = ptr::slice_from_raw_parts_mut(raw as *mut T, len);
+        Ok(OsBox {
+            ptr: unsafe { NonNull::new_unchecked(raw) },
+            layout,
+            map_size,
+        })
+    }
+}
+
+impl<T: ?Sized> OsBox<T> {
+    /// Returns whether the memory was obtained directly from the operating
+    /// system, rather than from the global allocator.
+    #[inline]
+    pub fn is_mapped(&self) -> bool {
+        self.map_size != 0
+    }
+}
+
+impl<T: ?Sized> Deref for OsBox<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for OsBox<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for OsBox<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.ptr.as_ptr());
+            release(self.ptr.as_ptr() as *mut u8, self.layout, self.map_size);
+        }
+    }
+}
+
+unsafe impl<T: ?Sized + Send> Send for OsBox<T> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for OsBox<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for OsBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
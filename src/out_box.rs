@@ -0,0 +1,200 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::fmt;
+use {UninitBox, Zero};
+
+/// Heap storage for a value to be filled through a raw pointer, typically by
+/// a C function taking an out-parameter.
+///
+/// Once filled, the storage is converted to a `Box<T>` with [`assume_init`],
+/// or, for types implementing [`Zero`] and storage created with
+/// [`new_zeroed`], with the safe [`into_box`].
+///
+/// If dropped before conversion, the memory is freed without dropping any
+/// value.
+///
+/// [`assume_init`]: #method.assume_init
+/// [`Zero`]: trait.Zero.html
+/// [`new_zeroed`]: #method.new_zeroed
+/// [`into_box`]: #method.into_box
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::OutBox;
+///
+/// #[repr(C)]
+/// struct Stats {
+///     count: u64,
+///     total: u64,
+/// }
+///
+/// // Stand-in for a C function filling a struct.
+/// unsafe extern "C" fn get_stats(out: *mut Stats) -> i32 {
+///     (*out).count = 3;
+///     (*out).total = 42;
+///     0
+/// }
+///
+/// fn main() {
+///     let mut stats = OutBox::<Stats>::new();
+///     let stats = unsafe {
+///         assert_eq!(get_stats(stats.as_out_ptr()), 0);
+///         stats.assume_init()
+///     };
+///     assert_eq!(stats.count, 3);
+///     assert_eq!(stats.total, 42);
+/// }
+/// ```
+pub struct OutBox<T> {
+    inner: UninitBox<T>,
+    zeroed: bool,
+}
+
+impl<T> Default for OutBox<T> {
+    #[track_caller]
+    fn default() -> Self {
+        OutBox::new()
+    }
+}
+
+impl<T> OutBox<T> {
+    /// Allocates uninitialized storage for a `T`.
+    ///
+    /// This doesn't actually allocate if `T` is zero-sized.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new() -> Self {
+        OutBox {
+            inner: UninitBox::new(false),
+            zeroed: false,
+        }
+    }
+
+    /// Fallible [`OutBox::new`]
+    ///
+    /// [`OutBox::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new() -> Option<Self> {
+        Some(OutBox {
+            inner: UninitBox::try_new(false).ok()?,
+            zeroed: false,
+        })
+    }
+
+    /// Allocates zeroed storage for a `T`.
+    ///
+    /// The storage can then be converted with the safe [`into_box`].
+    ///
+    /// This doesn't actually allocate if `T` is zero-sized.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`into_box`]: #method.into_box
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_zeroed() -> Self
+    where
+        T: Zero,
+    {
+        OutBox {
+            inner: UninitBox::new(true),
+            zeroed: true,
+        }
+    }
+
+    /// Fallible [`OutBox::new_zeroed`]
+    ///
+    /// [`OutBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new_zeroed() -> Option<Self>
+    where
+        T: Zero,
+    {
+        Some(OutBox {
+            inner: UninitBox::try_new(true).ok()?,
+            zeroed: true,
+        })
+    }
+
+    /// Returns a pointer to the storage, to be passed to the function
+    /// filling it.
+    #[inline]
+    pub fn as_out_ptr(&mut self) -> *mut T {
+        self.inner.0.as_ptr()
+    }
+
+    /// Converts to `Box<T>`.
+    ///
+    /// # Safety
+    ///
+    /// As with `MaybeUninit::assume_init`, it is up to the caller to
+    /// guarantee that the storage really holds a valid `T`.
+    #[inline]
+    pub unsafe fn assume_init(self) -> Box<T> {
+        self.inner.assume_init()
+    }
+
+    /// Converts to `Box<T>`.
+    ///
+    /// As the storage started out zeroed, it holds a valid `T` even if
+    /// nothing was written to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage was not created with [`new_zeroed`] or
+    /// [`try_new_zeroed`].
+    ///
+    /// [`new_zeroed`]: #method.new_zeroed
+    /// [`try_new_zeroed`]: #method.try_new_zeroed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::OutBox;
+    ///
+    /// fn main() {
+    ///     let mut out = OutBox::<[u32; 4]>::new_zeroed();
+    ///     unsafe { (*out.as_out_ptr())[1] = 5 };
+    ///     assert_eq!(*out.into_box(), [0, 5, 0, 0]);
+    /// }
+    /// ```
+    #[track_caller]
+    pub fn into_box(self) -> Box<T>
+    where
+        T: Zero,
+    {
+        assert!(self.zeroed, "OutBox storage was not zeroed");
+        unsafe { self.inner.assume_init() }
+    }
+}
+
+unsafe impl<T: Send> Send for OutBox<T> {}
+
+unsafe impl<T: Sync> Sync for OutBox<T> {}
+
+impl<T> fmt::Debug for OutBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OutBox")
+            .field("ptr", &self.inner.0)
+            .field("zeroed", &self.zeroed)
+            .finish()
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::pin::Pin;
+use {BoxExt, Zero};
+
+/// Extensions to the `Box` type, returning pinned boxes.
+///
+/// These are equivalent to the [`BoxExt`] methods followed by
+/// `Box::into_pin`, avoiding the temporary that `Box::pin` creates on the
+/// stack.
+///
+/// [`BoxExt`]: trait.BoxExt.html
+pub trait PinBoxExt: Sized {
+    /// Type contained inside the `Box`.
+    type Inner;
+
+    /// Pinned [`BoxExt::new_with`]
+    ///
+    /// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::PinBoxExt;
+    /// use std::marker::PhantomPinned;
+    /// use std::pin::Pin;
+    ///
+    /// struct State {
+    ///     buf: [u8; 4096],
+    ///     _pinned: PhantomPinned,
+    /// }
+    ///
+    /// fn main() {
+    ///     let state: Pin<Box<State>> = Box::pin_with(|| State {
+    ///         buf: [0; 4096],
+    ///         _pinned: PhantomPinned,
+    ///     });
+    ///     assert_eq!(state.buf[0], 0);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn pin_with<F: FnOnce() -> Self::Inner>(f: F) -> Pin<Self>;
+
+    /// Pinned [`BoxExt::new_zeroed`]
+    ///
+    /// [`BoxExt::new_zeroed`]: trait.BoxExt.html#tymethod.new_zeroed
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn pin_zeroed() -> Pin<Self>
+    where
+        Self::Inner: Zero;
+
+    /// Fallible [`PinBoxExt::pin_with`]
+    ///
+    /// [`PinBoxExt::pin_with`]: #tymethod.pin_with
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    fn try_pin_with<F: FnOnce() -> Self::Inner>(f: F) -> Option<Pin<Self>>;
+
+    /// Fallible [`PinBoxExt::pin_zeroed`]
+    ///
+    /// [`PinBoxExt::pin_zeroed`]: #tymethod.pin_zeroed
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    fn try_pin_zeroed() -> Option<Pin<Self>>
+    where
+        Self::Inner: Zero;
+}
+
+impl<T> PinBoxExt for Box<T> {
+    type Inner = T;
+
+    #[inline]
+    #[track_caller]
+    fn pin_with<F: FnOnce() -> T>(f: F) -> Pin<Self> {
+        Box::into_pin(BoxExt::new_with(f))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn pin_zeroed() -> Pin<Self>
+    where
+        T: Zero,
+    {
+        Box::into_pin(BoxExt::new_zeroed())
+    }
+
+    #[inline]
+    fn try_pin_with<F: FnOnce() -> T>(f: F) -> Option<Pin<Self>> {
+        Some(Box::into_pin(BoxExt::try_new_with(f)?))
+    }
+
+    #[inline]
+    fn try_pin_zeroed() -> Option<Pin<Self>>
+    where
+        T: Zero,
+    {
+        Some(Box::into_pin(BoxExt::try_new_zeroed()?))
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::ops::{Deref, DerefMut};
+use core::{fmt, slice};
+use heap::{dealloc_layout, try_alloc_layout};
+use std::alloc::Layout;
+use std::error::Error;
+use AllocError;
+
+/// Callbacks registering memory with a device or driver, e.g. with
+/// `cudaHostRegister` or `ibv_reg_mr`.
+///
+/// See [`PinnedHostBox`].
+///
+/// [`PinnedHostBox`]: struct.PinnedHostBox.html
+pub trait HostPinner {
+    /// Error returned when memory couldn't be pinned.
+    type Error;
+
+    /// Returns the page size the memory is aligned to.
+    ///
+    /// The default is 4096.
+    fn page_size(&self) -> usize {
+        4096
+    }
+
+    /// Pins the `len` bytes at `ptr`.
+    ///
+    /// `ptr` is aligned to [`page_size`], and `len` is a multiple of it.
+    ///
+    /// [`page_size`]: #method.page_size
+    fn pin(&self, ptr: *mut u8, len: usize) -> Result<(), Self::Error>;
+
+    /// Unpins memory previously pinned with [`pin`].
+    ///
+    /// [`pin`]: #tymethod.pin
+    fn unpin(&self, ptr: *mut u8, len: usize);
+}
+
+/// Error returned by [`PinnedHostBox::try_new_zeroed`].
+///
+/// [`PinnedHostBox::try_new_zeroed`]: struct.PinnedHostBox.html#method.try_new_zeroed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinnedHostError<E> {
+    /// Memory couldn't be allocated.
+    Alloc(AllocError),
+    /// Memory couldn't be pinned.
+    Pin(E),
+}
+
+impl<E: fmt::Display> fmt::Display for PinnedHostError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PinnedHostError::Alloc(ref e) => fmt::Display::fmt(e, f),
+            PinnedHostError::Pin(ref e) => write!(f, "failed to pin memory: {}", e),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for PinnedHostError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            PinnedHostError::Alloc(ref e) => Some(e),
+            PinnedHostError::Pin(ref e) => Some(e),
+        }
+    }
+}
+
+/// A zeroed, page-aligned, byte buffer pinned with user-provided callbacks.
+///
+/// The memory is pinned with [`HostPinner::pin`] when the buffer is created,
+/// and unpinned with [`HostPinner::unpin`] before it is freed. This allows to
+/// manage device-visible staging buffers for GPU or RDMA workloads.
+///
+/// The allocation is rounded up to a multiple of the page size, and that is
+/// what gets pinned, but the buffer dereferences to `[u8]` of the requested
+/// length.
+///
+/// [`HostPinner::pin`]: trait.HostPinner.html#tymethod.pin
+/// [`HostPinner::unpin`]: trait.HostPinner.html#tymethod.unpin
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{HostPinner, PinnedHostBox};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// // Stand-in for e.g. `cudaHostRegister`/`cudaHostUnregister`.
+/// struct Driver {
+///     pinned: AtomicUsize,
+/// }
+///
+/// impl<'a> HostPinner for &'a Driver {
+///     type Error = ();
+///
+///     fn pin(&self, _ptr: *mut u8, len: usize) -> Result<(), ()> {
+///         self.pinned.fetch_add(len, Ordering::SeqCst);
+///         Ok(())
+///     }
+///
+///     fn unpin(&self, _ptr: *mut u8, len: usize) {
+///         self.pinned.fetch_sub(len, Ordering::SeqCst);
+///     }
+/// }
+///
+/// fn main() {
+///     let driver = Driver { pinned: AtomicUsize::new(0) };
+///     let mut buf = PinnedHostBox::new_zeroed(10000, &driver).unwrap();
+///     assert_eq!(buf.len(), 10000);
+///     assert_eq!(buf.as_ptr() as usize % 4096, 0);
+///     assert_eq!(driver.pinned.load(Ordering::SeqCst), 12288);
+///     buf[0] = 1;
+///     drop(buf);
+///     assert_eq!(driver.pinned.load(Ordering::SeqCst), 0);
+/// }
+/// ```
+pub struct PinnedHostBox<P: HostPinner> {
+    ptr: *mut u8,
+    len: usize,
+    layout: Layout,
+    pinner: P,
+}
+
+impl<P: HostPinner> PinnedHostBox<P> {
+    // Allocates and pins the buffer. Pinning errors are returned as
+    // Ok(Err(_)), so that callers can handle allocation errors separately.
+    #[track_caller]
+    fn alloc(len: usize, pinner: P) -> Result<Result<Self, P::Error>, AllocError> {
+        let page_size = pinner.page_size();
+        let size = len
+            .checked_add(page_size - 1)
+            .ok_or(AllocError::capacity_overflow())?
+            / page_size
+            * page_size;
+        let layout = match Layout::from_size_align(size, page_size) {
+            Ok(layout) => layout,
+            Err(_) => return Err(AllocError::capacity_overflow()),
+        };
+        let ptr = unsafe { try_alloc_layout(layout, true)? };
+        if size != 0 {
+            if let Err(e) = pinner.pin(ptr, size) {
+                unsafe { dealloc_layout(ptr, layout) };
+                return Ok(Err(e));
+            }
+        }
+        Ok(Ok(PinnedHostBox {
+            ptr,
+            len,
+            layout,
+            pinner,
+        }))
+    }
+
+    /// Allocates `len` zeroed bytes, aligned to the page size, and pins them.
+    ///
+    /// Returns an error if the memory couldn't be pinned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the page size is not a power of two or if `len` overflows
+    /// when rounded up to it. Calls [`handle_alloc_error`] if memory
+    /// couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_zeroed(len: usize, pinner: P) -> Result<Self, P::Error> {
+        match PinnedHostBox::alloc(len, pinner) {
+            Ok(result) => result,
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`PinnedHostBox::new_zeroed`]
+    ///
+    /// [`PinnedHostBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// This returns an error if memory couldn't be allocated or pinned.
+    #[track_caller]
+    pub fn try_new_zeroed(len: usize, pinner: P) -> Result<Self, PinnedHostError<P::Error>> {
+        PinnedHostBox::alloc(len, pinner)
+            .map_err(PinnedHostError::Alloc)?
+            .map_err(PinnedHostError::Pin)
+    }
+
+    /// Returns the pinner.
+    #[inline]
+    pub fn pinner(&self) -> &P {
+        &self.pinner
+    }
+}
+
+impl<P: HostPinner> Deref for PinnedHostBox<P> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<P: HostPinner> DerefMut for PinnedHostBox<P> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl<P: HostPinner> Drop for PinnedHostBox<P> {
+    fn drop(&mut self) {
+        if self.layout.size() != 0 {
+            self.pinner.unpin(self.ptr, self.layout.size());
+        }
+        unsafe { dealloc_layout(self.ptr, self.layout) }
+    }
+}
+
+unsafe impl<P: HostPinner + Send> Send for PinnedHostBox<P> {}
+
+unsafe impl<P: HostPinner + Sync> Sync for PinnedHostBox<P> {}
+
+impl<P: HostPinner> fmt::Debug for PinnedHostBox<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PinnedHostBox")
+            .field("len", &self.len)
+            .field("align", &self.layout.align())
+            .finish()
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::num::Wrapping;
+#[cfg(feature = "std")]
+use core::{mem, ptr};
+#[cfg(feature = "std")]
+use {BoxUninitSliceExt, UninitBox};
+use Zero;
+
+/// Trait indicating that a type is plain old data: any sequence of bytes
+/// of the right size is a valid value, and a value has no padding bytes.
+///
+/// This allows converting boxes of such types from and to boxed byte
+/// slices, with [`into_bytes_box`] and [`from_bytes_box`].
+///
+/// [`into_bytes_box`]: fn.into_bytes_box.html
+/// [`from_bytes_box`]: fn.from_bytes_box.html
+///
+/// # Safety
+///
+/// Do **not** implement this trait for types that have padding, or for
+/// which some bit patterns are not valid, such as `bool`, `char`,
+/// references, or enums.
+///
+/// # Implementations
+///
+/// `Pod` is implemented for integer and floating point types, and for arrays
+/// and `Wrapping<T>` when `T` implements `Pod`.
+pub unsafe trait Pod: Zero + Copy {}
+
+macro_rules! pod_impl {
+    ($($t:ty)+) => { $(unsafe impl Pod for $t {})+ }
+}
+
+pod_impl! {
+    u8 u16 u32 u64 u128 usize
+    i8 i16 i32 i64 i128 isize
+    f32 f64
+}
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+unsafe impl<T: Pod> Pod for Wrapping<T> {}
+
+/// Converts a boxed value into a boxed slice of its bytes.
+///
+/// When `T` has an alignment of 1, e.g. for arrays of bytes or
+/// `#[repr(C, packed)]` structs, the allocation is reused, without copying.
+/// Otherwise, the bytes are copied to a new allocation, since a boxed byte
+/// slice is deallocated with an alignment of 1, which must match the
+/// alignment it was allocated with.
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{from_bytes_box, into_bytes_box, Pod, Zero};
+///
+/// #[derive(Clone, Copy)]
+/// #[repr(C, packed)]
+/// struct Header {
+///     kind: u8,
+///     len: [u8; 2],
+///     payload: [u8; 4093],
+/// }
+///
+/// unsafe impl Zero for Header {}
+/// unsafe impl Pod for Header {}
+///
+/// fn main() {
+///     let mut packet = vec![0u8; 4096].into_boxed_slice();
+///     packet[0] = 7;
+///     let addr = packet.as_ptr();
+///
+///     let header: Box<Header> = from_bytes_box(packet).ok().unwrap();
+///     assert_eq!(header.kind, 7);
+///     assert_eq!(&*header as *const Header as *const u8, addr);
+///
+///     let bytes = into_bytes_box(header);
+///     assert_eq!(bytes.as_ptr(), addr);
+///
+///     // The length has to match the size of the type.
+///     assert!(from_bytes_box::<Header>(vec![0; 16].into_boxed_slice()).is_err());
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn into_bytes_box<T: Pod>(b: Box<T>) -> Box<[u8]> {
+    let size = mem::size_of::<T>();
+    let raw = Box::into_raw(b);
+    unsafe {
+        if mem::align_of::<T>() == 1 {
+            return Box::from_raw(ptr::slice_from_raw_parts_mut(raw as *mut u8, size));
+        }
+        let b = Box::from_raw(raw);
+        let mut bytes: Box<[mem::MaybeUninit<u8>]> = BoxUninitSliceExt::new_uninit_slice(size);
+        ptr::copy_nonoverlapping(
+            &*b as *const T as *const u8,
+            bytes.as_mut_ptr() as *mut u8,
+            size,
+        );
+        bytes.assume_init_slice()
+    }
+}
+
+/// Converts a boxed slice of bytes into a boxed value.
+///
+/// This returns the boxed slice back if its length is not the size of `T`.
+///
+/// As with [`into_bytes_box`], the allocation is reused when `T` has an
+/// alignment of 1, and the bytes are copied to a new allocation otherwise.
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`into_bytes_box`]: fn.into_bytes_box.html
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn from_bytes_box<T: Pod>(bytes: Box<[u8]>) -> Result<Box<T>, Box<[u8]>> {
+    if bytes.len() != mem::size_of::<T>() {
+        return Err(bytes);
+    }
+    unsafe {
+        if mem::align_of::<T>() == 1 {
+            return Ok(Box::from_raw(Box::into_raw(bytes) as *mut T));
+        }
+        let uninit = UninitBox::<T>::new(false);
+        ptr::copy_nonoverlapping(bytes.as_ptr(), uninit.0.as_ptr() as *mut u8, bytes.len());
+        Ok(uninit.assume_init())
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::cell::RefCell;
+use core::mem::{self, MaybeUninit};
+use core::{fmt, ptr};
+use heap::poison;
+use {BoxStorageExt, BoxUninitExt, IsZst, UninitBox, Zero};
+
+/// A pool of `Box<T>` allocations, recycled instead of being freed.
+///
+/// Boxes handed back with [`recycle`] have their value dropped, and their
+/// memory is kept for later boxes from the same pool, bypassing the
+/// allocator when the same type is allocated and freed over and over. When
+/// the pool is empty, memory is allocated as usual.
+///
+/// The boxes are plain `Box<T>`s, which can be freed normally instead of
+/// being recycled, and any `Box<T>` can be recycled in any pool for the same
+/// type.
+///
+/// A `BoxPool` can't be shared between threads. It is meant to be used from a
+/// `thread_local!`, giving each thread its own pool.
+///
+/// [`recycle`]: #method.recycle
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{BoxPool, Zero};
+///
+/// struct Packet {
+///     len: usize,
+///     data: [u8; 65536],
+/// }
+///
+/// unsafe impl Zero for Packet {}
+///
+/// thread_local! {
+///     static PACKETS: BoxPool<Packet> = const { BoxPool::new() };
+/// }
+///
+/// fn main() {
+///     PACKETS.with(|pool| {
+///         let first = pool.new_zeroed();
+///         let addr = &*first as *const Packet;
+///         pool.recycle(first);
+///
+///         // The memory of the first packet is reused, and zeroed again.
+///         let mut second = pool.new_zeroed();
+///         assert_eq!(&*second as *const Packet, addr);
+///         assert_eq!(second.len, 0);
+///         second.len = 42;
+///         pool.recycle(second);
+///
+///         let third = pool.new_with(|| Packet { len: 1500, data: [0xff; 65536] });
+///         assert_eq!(&*third as *const Packet, addr);
+///         assert_eq!(third.len, 1500);
+///     });
+/// }
+/// ```
+pub struct BoxPool<T> {
+    free: RefCell<Vec<Box<MaybeUninit<T>>>>,
+    limit: usize,
+}
+
+impl<T> Default for BoxPool<T> {
+    fn default() -> Self {
+        BoxPool::new()
+    }
+}
+
+impl<T> BoxPool<T> {
+    /// Creates a new, empty, pool, keeping any number of allocations.
+    ///
+    /// This doesn't allocate until boxes are recycled.
+    pub const fn new() -> Self {
+        BoxPool::with_limit(usize::MAX)
+    }
+
+    /// Creates a new, empty, pool, keeping at most `limit` allocations.
+    ///
+    /// Boxes recycled while the pool is full are freed.
+    pub const fn with_limit(limit: usize) -> Self {
+        BoxPool {
+            free: RefCell::new(Vec::new()),
+            limit,
+        }
+    }
+
+    // Returns recycled memory, if any.
+    fn take(&self) -> Option<Box<MaybeUninit<T>>> {
+        self.free.borrow_mut().pop()
+    }
+
+    /// Places the result of `f` in a recycled allocation, or allocates memory
+    /// for it if there is none.
+    ///
+    /// As with [`BoxExt::new_with`], this is expected to avoid copies when
+    /// building with optimization enabled.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_with<F: FnOnce() -> T>(&self, f: F) -> Box<T> {
+        match self.take() {
+            Some(storage) => storage.write_with(f),
+            None => UninitBox::new(false).init(f()),
+        }
+    }
+
+    /// Zeroes a recycled allocation, or allocates zeroed memory if there is
+    /// none.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_zeroed(&self) -> Box<T>
+    where
+        T: Zero,
+    {
+        match self.take() {
+            Some(storage) => unsafe { zero(storage) },
+            None => unsafe { UninitBox::new(true).assume_init() },
+        }
+    }
+
+    /// Fallible [`BoxPool::new_with`]
+    ///
+    /// [`BoxPool::new_with`]: #method.new_with
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new_with<F: FnOnce() -> T>(&self, f: F) -> Option<Box<T>> {
+        match self.take() {
+            Some(storage) => Some(storage.write_with(f)),
+            None => Some(UninitBox::try_new(false).ok()?.init(f())),
+        }
+    }
+
+    /// Fallible [`BoxPool::new_zeroed`]
+    ///
+    /// [`BoxPool::new_zeroed`]: #method.new_zeroed
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new_zeroed(&self) -> Option<Box<T>>
+    where
+        T: Zero,
+    {
+        match self.take() {
+            Some(storage) => unsafe { Some(zero(storage)) },
+            None => unsafe { Some(UninitBox::try_new(true).ok()?.assume_init()) },
+        }
+    }
+
+    /// Drops the boxed value, and keeps its allocation for later boxes, unless
+    /// the pool is full.
+    pub fn recycle(&self, b: Box<T>) {
+        let mut storage = b.deinit();
+        if IsZst::<T>::VALUE {
+            return;
+        }
+        let mut free = self.free.borrow_mut();
+        if free.len() < self.limit {
+            unsafe { poison(storage.as_mut_ptr() as *mut u8, mem::size_of::<T>()) };
+            free.push(storage);
+        }
+    }
+
+    /// Returns the number of allocations kept in the pool.
+    pub fn cached(&self) -> usize {
+        self.free.borrow().len()
+    }
+
+    /// Frees the allocations kept in the pool.
+    pub fn clear(&self) {
+        self.free.borrow_mut().clear();
+    }
+}
+
+// Zeroes recycled memory and turns it into a `Box`.
+unsafe fn zero<T: Zero>(mut storage: Box<MaybeUninit<T>>) -> Box<T> {
+    ptr::write_bytes(storage.as_mut_ptr(), 0, 1);
+    storage.assume_init_box()
+}
+
+impl<T> fmt::Debug for BoxPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BoxPool")
+            .field("cached", &self.cached())
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Allocation of values behind raw pointers, for manual memory management.
+//!
+//! The functions in this module allocate memory the same way as the `Box`
+//! constructors in this crate, but return a `NonNull<T>` that the caller is
+//! responsible for, e.g. to hand it over to C code. The value is released
+//! with [`dealloc`].
+//!
+//! The pointers are compatible with `Box`: they can be turned into a `Box`
+//! with `Box::from_raw`, and a pointer from `Box::into_raw` can be released
+//! with [`dealloc`].
+//!
+//! [`dealloc`]: fn.dealloc.html
+//!
+//! # Example
+//!
+//! ```
+//! extern crate boxext;
+//! use boxext::raw;
+//! use std::ptr::NonNull;
+//!
+//! #[repr(C)]
+//! struct Context {
+//!     refcount: usize,
+//!     scratch: [u8; 65536],
+//! }
+//!
+//! unsafe impl boxext::Zero for Context {}
+//!
+//! // Functions exported to C code, which only sees an opaque pointer.
+//! extern "C" fn context_new() -> Option<NonNull<Context>> {
+//!     let ctx = raw::try_alloc_zeroed::<Context>()?;
+//!     unsafe { (*ctx.as_ptr()).refcount = 1 };
+//!     Some(ctx)
+//! }
+//!
+//! extern "C" fn context_free(ctx: NonNull<Context>) {
+//!     unsafe { raw::dealloc(ctx) }
+//! }
+//!
+//! fn main() {
+//!     let ctx = context_new().unwrap();
+//!     assert_eq!(unsafe { ctx.as_ref() }.refcount, 1);
+//!     context_free(ctx);
+//! }
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use core::ptr::NonNull;
+use {UninitBox, Zero};
+
+// Turns a `Box` into a `NonNull`.
+fn into_non_null<T>(b: Box<T>) -> NonNull<T> {
+    unsafe { NonNull::new_unchecked(Box::into_raw(b)) }
+}
+
+/// Allocates zeroed memory for a `T`.
+///
+/// This doesn't actually allocate if `T` is zero-sized.
+///
+/// # Safety
+///
+/// This function is only assumed safe for types implementing the [`Zero`]
+/// trait, and not available otherwise. See the definition of that trait.
+///
+/// [`Zero`]: ../trait.Zero.html
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[track_caller]
+pub fn alloc_zeroed<T: Zero>() -> NonNull<T> {
+    into_non_null(unsafe { UninitBox::new(true).assume_init() })
+}
+
+/// Fallible [`alloc_zeroed`]
+///
+/// [`alloc_zeroed`]: fn.alloc_zeroed.html
+///
+/// This returns `None` if memory couldn't be allocated.
+pub fn try_alloc_zeroed<T: Zero>() -> Option<NonNull<T>> {
+    Some(into_non_null(unsafe { UninitBox::try_new(true).ok()?.assume_init() }))
+}
+
+/// Allocates memory for a `T`, and places the result of `f` into it.
+///
+/// As with [`BoxExt::new_with`], this is expected to avoid copies when
+/// building with optimization enabled.
+///
+/// This doesn't actually allocate if `T` is zero-sized.
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`BoxExt::new_with`]: ../trait.BoxExt.html#tymethod.new_with
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[track_caller]
+pub fn alloc_with<T, F: FnOnce() -> T>(f: F) -> NonNull<T> {
+    into_non_null(UninitBox::new(false).init(f()))
+}
+
+/// Fallible [`alloc_with`]
+///
+/// [`alloc_with`]: fn.alloc_with.html
+///
+/// This returns `None` if memory couldn't be allocated, in which case `f`
+/// is not called.
+pub fn try_alloc_with<T, F: FnOnce() -> T>(f: F) -> Option<NonNull<T>> {
+    Some(into_non_null(UninitBox::try_new(false).ok()?.init(f())))
+}
+
+/// Drops the value at `ptr`, and frees its memory.
+///
+/// # Safety
+///
+/// `ptr` must come from one of the functions in this module, or from
+/// `Box::into_raw`, for the same type, and must not have been released yet.
+pub unsafe fn dealloc<T>(ptr: NonNull<T>) {
+    drop(Box::from_raw(ptr.as_ptr()));
+}
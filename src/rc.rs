@@ -0,0 +1,524 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(not(boxext_std_new_uninit))]
+use core::mem;
+use core::mem::MaybeUninit;
+use core::ptr;
+use std::alloc::{dealloc, Layout};
+use std::rc::{self, Rc};
+use std::sync::{self, Arc};
+use {try_alloc_global, Zero};
+
+/// Extensions to the `Rc` type
+///
+/// The reference-counted block is allocated by `Rc` itself, with the value
+/// left uninitialized, and the value is then written in place. As with
+/// [`BoxExt`], this is expected to avoid copies when building with
+/// optimization enabled.
+///
+/// Contrary to [`BoxExt`], there are no fallible variants: `Rc` doesn't
+/// provide a way to handle allocation failure on stable Rust, and building
+/// its block manually would depend on its private layout.
+///
+/// [`BoxExt`]: trait.BoxExt.html
+pub trait RcExt {
+    /// Type contained inside the `Rc`.
+    type Inner;
+
+    /// Allocates memory for a `Rc` and then places the result of `f` into
+    /// it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::RcExt;
+    /// use std::rc::Rc;
+    ///
+    /// fn main() {
+    ///     let table = Rc::new_with(|| [1u32; 4096]);
+    ///     let shared = Rc::clone(&table);
+    ///     assert_eq!(shared[4095], 1);
+    /// }
+    /// ```
+    fn new_with<F: FnOnce() -> Self::Inner>(f: F) -> Self;
+
+    /// Allocates memory for a `Rc` and fills the value with zeroes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::RcExt;
+    /// use std::rc::Rc;
+    ///
+    /// fn main() {
+    ///     let table: Rc<[u64; 4096]> = RcExt::new_zeroed();
+    ///     assert!(table.iter().all(|&x| x == 0));
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for `Self::Inner` types implementing
+    /// the [`Zero`] trait, and not available otherwise. See the definition
+    /// of that trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    fn new_zeroed() -> Self
+    where
+        Self::Inner: Zero;
+
+    /// Allocates memory for a `Rc`, and lets `f` initialize the value in
+    /// place, with a `Weak` pointer to the allocation, for self-referential
+    /// values.
+    ///
+    /// This is the equivalent of `Rc::new_cyclic`, which builds the value
+    /// on the stack before moving it, and of [`BoxExt::new_with_ptr`].
+    ///
+    /// If `f` panics, the memory is freed once the `Weak` pointers `f` may
+    /// have cloned are dropped.
+    ///
+    /// [`BoxExt::new_with_ptr`]: trait.BoxExt.html#tymethod.new_with_ptr
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::RcExt;
+    /// use std::mem::MaybeUninit;
+    /// use std::ptr;
+    /// use std::rc::{Rc, Weak};
+    ///
+    /// struct Node {
+    ///     parent: Weak<Node>,
+    ///     data: [u8; 1 << 16],
+    /// }
+    ///
+    /// fn main() {
+    ///     let root: Rc<Node> = unsafe {
+    ///         Rc::new_cyclic_with_ptr(|me: &Weak<Node>, node: &mut MaybeUninit<Node>| {
+    ///             let raw = node.as_mut_ptr();
+    ///             ptr::addr_of_mut!((*raw).parent).write(me.clone());
+    ///             ptr::addr_of_mut!((*raw).data).write_bytes(0, 1);
+    ///         })
+    ///     };
+    ///     assert!(Rc::ptr_eq(&root.parent.upgrade().unwrap(), &root));
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// `f` must fully initialize the value, unless it panics, and must not
+    /// upgrade the `Weak` pointer, or any of its clones, before returning.
+    unsafe fn new_cyclic_with_ptr<F>(f: F) -> Self
+    where
+        F: FnOnce(&rc::Weak<Self::Inner>, &mut MaybeUninit<Self::Inner>);
+}
+
+/// Extensions to the `Arc` type
+///
+/// The reference-counted block is allocated by `Arc` itself, with the value
+/// left uninitialized, and the value is then written in place. As with
+/// [`BoxExt`], this is expected to avoid copies when building with
+/// optimization enabled.
+///
+/// Contrary to [`BoxExt`], there are no fallible variants: `Arc` doesn't
+/// provide a way to handle allocation failure on stable Rust, and building
+/// its block manually would depend on its private layout.
+///
+/// [`BoxExt`]: trait.BoxExt.html
+pub trait ArcExt {
+    /// Type contained inside the `Arc`.
+    type Inner;
+
+    /// Allocates memory for an `Arc` and then places the result of `f` into
+    /// it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::ArcExt;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// fn main() {
+    ///     let table = Arc::new_with(|| [1u32; 4096]);
+    ///     let shared = Arc::clone(&table);
+    ///     let sum = thread::spawn(move || shared.iter().sum::<u32>());
+    ///     assert_eq!(sum.join().unwrap(), 4096);
+    /// }
+    /// ```
+    fn new_with<F: FnOnce() -> Self::Inner>(f: F) -> Self;
+
+    /// Allocates memory for an `Arc` and fills the value with zeroes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::ArcExt;
+    /// use std::sync::Arc;
+    ///
+    /// fn main() {
+    ///     let table: Arc<[u64; 4096]> = ArcExt::new_zeroed();
+    ///     assert!(table.iter().all(|&x| x == 0));
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for `Self::Inner` types implementing
+    /// the [`Zero`] trait, and not available otherwise. See the definition
+    /// of that trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    fn new_zeroed() -> Self
+    where
+        Self::Inner: Zero;
+
+    /// Allocates memory for an `Arc`, and lets `f` initialize the value in
+    /// place, with a `Weak` pointer to the allocation, for self-referential
+    /// values.
+    ///
+    /// This is the equivalent of `Arc::new_cyclic`, which builds the value
+    /// on the stack before moving it, and of [`BoxExt::new_with_ptr`].
+    ///
+    /// If `f` panics, the memory is freed once the `Weak` pointers `f` may
+    /// have cloned are dropped.
+    ///
+    /// [`BoxExt::new_with_ptr`]: trait.BoxExt.html#tymethod.new_with_ptr
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::ArcExt;
+    /// use std::mem::MaybeUninit;
+    /// use std::ptr;
+    /// use std::sync::{Arc, Weak};
+    ///
+    /// struct Node {
+    ///     parent: Weak<Node>,
+    ///     data: [u8; 1 << 16],
+    /// }
+    ///
+    /// fn main() {
+    ///     let root: Arc<Node> = unsafe {
+    ///         Arc::new_cyclic_with_ptr(|me: &Weak<Node>, node: &mut MaybeUninit<Node>| {
+    ///             let raw = node.as_mut_ptr();
+    ///             ptr::addr_of_mut!((*raw).parent).write(me.clone());
+    ///             ptr::addr_of_mut!((*raw).data).write_bytes(0, 1);
+    ///         })
+    ///     };
+    ///     assert!(Arc::ptr_eq(&root.parent.upgrade().unwrap(), &root));
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// `f` must fully initialize the value, unless it panics, and must not
+    /// upgrade the `Weak` pointer, or any of its clones, before returning.
+    unsafe fn new_cyclic_with_ptr<F>(f: F) -> Self
+    where
+        F: FnOnce(&sync::Weak<Self::Inner>, &mut MaybeUninit<Self::Inner>);
+}
+
+/// Extensions to the `Rc<[T]>` type
+///
+/// The reference-counted block is allocated once by `Rc` itself, for the
+/// exact length of the slice. With Rust 1.92 or newer, the block is obtained
+/// zeroed from the allocator, with `Rc::new_zeroed_slice`. With older
+/// versions, the elements are zeroed in place, since obtaining the block
+/// zeroed would depend on its private layout. Contrary to going through a
+/// `Vec` and `Rc::from`, the slice is never copied.
+///
+/// As `Rc` doesn't provide a way to handle allocation failure on stable
+/// Rust, the fallible variants check that memory for the block can be
+/// allocated before letting `Rc` allocate it. See
+/// [`RcSliceExt::try_new_zeroed_slice`].
+///
+/// [`RcSliceExt::try_new_zeroed_slice`]: #tymethod.try_new_zeroed_slice
+pub trait RcSliceExt {
+    /// Type of the elements of the slice.
+    type Item;
+
+    /// Allocates memory for a `Rc` holding `len` elements, and fills them
+    /// with zeroes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::RcSliceExt;
+    /// use std::rc::Rc;
+    ///
+    /// fn main() {
+    ///     let buf: Rc<[u8]> = RcSliceExt::new_zeroed_slice(1 << 20);
+    ///     assert_eq!(buf.len(), 1 << 20);
+    ///     assert!(buf.iter().all(|&b| b == 0));
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for `Self::Item` types implementing
+    /// the [`Zero`] trait, and not available otherwise. See the definition
+    /// of that trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows.
+    fn new_zeroed_slice(len: usize) -> Self
+    where
+        Self::Item: Zero;
+
+    /// Fallible [`RcSliceExt::new_zeroed_slice`]
+    ///
+    /// [`RcSliceExt::new_zeroed_slice`]: #tymethod.new_zeroed_slice
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the slice overflows.
+    ///
+    /// Whether memory can be allocated is checked by allocating and
+    /// releasing memory for the block, before `Rc` allocates it. If the
+    /// allocation by `Rc` still fails, e.g. because another thread
+    /// allocated memory in the meanwhile, [`handle_alloc_error`] is called.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::RcSliceExt;
+    /// use std::rc::Rc;
+    ///
+    /// fn main() {
+    ///     let buf: Rc<[u32]> = RcSliceExt::try_new_zeroed_slice(1024).unwrap();
+    ///     assert_eq!(buf.len(), 1024);
+    ///     assert!(buf.iter().all(|&x| x == 0));
+    ///
+    ///     let huge: Option<Rc<[u64]>> = RcSliceExt::try_new_zeroed_slice(usize::max_value() / 8);
+    ///     assert!(huge.is_none());
+    /// }
+    /// ```
+    fn try_new_zeroed_slice(len: usize) -> Option<Self>
+    where
+        Self: Sized,
+        Self::Item: Zero;
+}
+
+/// Extensions to the `Arc<[T]>` type
+///
+/// The reference-counted block is allocated once by `Arc` itself, for the
+/// exact length of the slice. With Rust 1.92 or newer, the block is obtained
+/// zeroed from the allocator, with `Arc::new_zeroed_slice`. With older
+/// versions, the elements are zeroed in place, since obtaining the block
+/// zeroed would depend on its private layout. Contrary to going through a
+/// `Vec` and `Arc::from`, the slice is never copied.
+///
+/// As `Arc` doesn't provide a way to handle allocation failure on stable
+/// Rust, the fallible variants check that memory for the block can be
+/// allocated before letting `Arc` allocate it. See
+/// [`ArcSliceExt::try_new_zeroed_slice`].
+///
+/// [`ArcSliceExt::try_new_zeroed_slice`]: #tymethod.try_new_zeroed_slice
+pub trait ArcSliceExt {
+    /// Type of the elements of the slice.
+    type Item;
+
+    /// Allocates memory for an `Arc` holding `len` elements, and fills them
+    /// with zeroes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::ArcSliceExt;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// fn main() {
+    ///     let buf: Arc<[u64]> = ArcSliceExt::new_zeroed_slice(4096);
+    ///     let shared = Arc::clone(&buf);
+    ///     let sum = thread::spawn(move || shared.iter().sum::<u64>());
+    ///     assert_eq!(sum.join().unwrap(), 0);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for `Self::Item` types implementing
+    /// the [`Zero`] trait, and not available otherwise. See the definition
+    /// of that trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows.
+    fn new_zeroed_slice(len: usize) -> Self
+    where
+        Self::Item: Zero;
+
+    /// Fallible [`ArcSliceExt::new_zeroed_slice`]
+    ///
+    /// [`ArcSliceExt::new_zeroed_slice`]: #tymethod.new_zeroed_slice
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the slice overflows.
+    ///
+    /// Whether memory can be allocated is checked by allocating and
+    /// releasing memory for the block, before `Arc` allocates it. If the
+    /// allocation by `Arc` still fails, e.g. because another thread
+    /// allocated memory in the meanwhile, [`handle_alloc_error`] is called.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::ArcSliceExt;
+    /// use std::sync::Arc;
+    ///
+    /// fn main() {
+    ///     let buf: Arc<[u32]> = ArcSliceExt::try_new_zeroed_slice(1024).unwrap();
+    ///     assert_eq!(buf.len(), 1024);
+    ///     assert!(buf.iter().all(|&x| x == 0));
+    ///
+    ///     let huge: Option<Arc<[u64]>> = ArcSliceExt::try_new_zeroed_slice(usize::max_value() / 8);
+    ///     assert!(huge.is_none());
+    /// }
+    /// ```
+    fn try_new_zeroed_slice(len: usize) -> Option<Self>
+    where
+        Self: Sized,
+        Self::Item: Zero;
+}
+
+macro_rules! rc_ext_impl {
+    ($trait:ident for $rc:ident, $weak:ident) => {
+        impl<T> $trait for $rc<T> {
+            type Inner = T;
+
+            #[inline]
+            fn new_with<F: FnOnce() -> T>(f: F) -> Self {
+                let mut rc = $rc::new(MaybeUninit::<T>::uninit());
+                unsafe {
+                    // The `Rc` was just created, so it's not shared.
+                    let raw = $rc::get_mut(&mut rc).unwrap().as_mut_ptr();
+                    ptr::write(raw, f());
+                    $rc::from_raw($rc::into_raw(rc) as *const T)
+                }
+            }
+
+            #[inline]
+            fn new_zeroed() -> Self
+            where
+                T: Zero,
+            {
+                let mut rc = $rc::new(MaybeUninit::<T>::uninit());
+                unsafe {
+                    let raw = $rc::get_mut(&mut rc).unwrap().as_mut_ptr();
+                    ptr::write_bytes(raw, 0, 1);
+                    $rc::from_raw($rc::into_raw(rc) as *const T)
+                }
+            }
+
+            #[inline]
+            unsafe fn new_cyclic_with_ptr<F>(f: F) -> Self
+            where
+                F: FnOnce(&$weak::Weak<T>, &mut MaybeUninit<T>),
+            {
+                let rc = $rc::new(MaybeUninit::<T>::uninit());
+                // `get_mut` can't be used once there are `Weak` pointers, so
+                // write through the raw pointer. Nothing else can access the
+                // value until `f` returns.
+                let raw = $rc::as_ptr(&rc) as *mut MaybeUninit<T>;
+                let weak = $weak::Weak::from_raw(
+                    $weak::Weak::into_raw($rc::downgrade(&rc)) as *const T,
+                );
+                f(&weak, &mut *raw);
+                drop(weak);
+                $rc::from_raw($rc::into_raw(rc) as *const T)
+            }
+        }
+    };
+}
+
+rc_ext_impl!(RcExt for Rc, rc);
+rc_ext_impl!(ArcExt for Arc, sync);
+
+// Checks that memory for a reference-counted block holding `len` elements
+// of type `T` can be allocated, by allocating and releasing memory for the
+// slice and the two reference counts that precede it. Returns None if it
+// can't, or if the size of the block overflows.
+fn probe_slice_block<T>(len: usize) -> Option<()> {
+    let slice = Layout::array::<T>(len).ok()?;
+    let (layout, _) = Layout::new::<[usize; 2]>().extend(slice).ok()?;
+    unsafe {
+        let raw = try_alloc_global(layout, false).ok()?;
+        dealloc(raw.as_ptr(), layout);
+    }
+    Some(())
+}
+
+macro_rules! rc_slice_ext_impl {
+    ($trait:ident for $rc:ident) => {
+        impl<T> $trait for $rc<[T]> {
+            type Item = T;
+
+            #[inline]
+            #[allow(clippy::incompatible_msrv)]
+            fn new_zeroed_slice(len: usize) -> Self
+            where
+                T: Zero,
+            {
+                #[cfg(boxext_std_new_zeroed)]
+                {
+                    unsafe { $rc::<[T]>::new_zeroed_slice(len).assume_init() }
+                }
+                #[cfg(all(boxext_std_new_uninit, not(boxext_std_new_zeroed)))]
+                {
+                    let mut rc = $rc::<[T]>::new_uninit_slice(len);
+                    unsafe {
+                        // The `Rc` was just created, so it's not shared.
+                        let raw = $rc::get_mut(&mut rc).unwrap().as_mut_ptr();
+                        ptr::write_bytes(raw, 0, len);
+                        rc.assume_init()
+                    }
+                }
+                #[cfg(not(boxext_std_new_uninit))]
+                {
+                    // Collecting from an iterator of trusted length
+                    // allocates the block for `len` elements up-front, and
+                    // writes them in place.
+                    (0..len).map(|_| unsafe { mem::zeroed() }).collect()
+                }
+            }
+
+            #[inline]
+            fn try_new_zeroed_slice(len: usize) -> Option<Self>
+            where
+                T: Zero,
+            {
+                probe_slice_block::<T>(len)?;
+                Some(<$rc<[T]> as $trait>::new_zeroed_slice(len))
+            }
+        }
+    };
+}
+
+rc_slice_ext_impl!(RcSliceExt for Rc);
+rc_slice_ext_impl!(ArcSliceExt for Arc);
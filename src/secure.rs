@@ -0,0 +1,652 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::fmt;
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use heap::volatile_fill;
+#[cfg(feature = "os_alloc")]
+use os_box::{try_map_locked, unmap_locked};
+#[cfg(feature = "os_alloc")]
+use std::alloc::Layout;
+#[cfg(feature = "os_alloc")]
+use std::error::Error;
+#[cfg(feature = "os_alloc")]
+use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
+#[cfg(feature = "os_alloc")]
+use AllocError;
+use {ArcExt, BoxExt, RcExt, Zero};
+
+/// Error returned by [`SecureBox::try_new_locked_zeroed`].
+///
+/// [`SecureBox::try_new_locked_zeroed`]: struct.SecureBox.html#method.try_new_locked_zeroed
+#[cfg(feature = "os_alloc")]
+#[derive(Debug)]
+pub enum LockedAllocError {
+    /// Memory couldn't be allocated.
+    Alloc(AllocError),
+    /// Memory couldn't be locked, e.g. because the limit of locked memory
+    /// for the process was reached.
+    Lock(io::Error),
+}
+
+#[cfg(feature = "os_alloc")]
+impl fmt::Display for LockedAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LockedAllocError::Alloc(ref e) => fmt::Display::fmt(e, f),
+            LockedAllocError::Lock(ref e) => write!(f, "failed to lock memory: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "os_alloc")]
+impl Error for LockedAllocError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LockedAllocError::Alloc(ref e) => Some(e),
+            LockedAllocError::Lock(ref e) => Some(e),
+        }
+    }
+}
+
+/// A heap allocation whose memory is overwritten with zeroes when it is
+/// dropped, for secrets such as key material.
+///
+/// The value is dropped first, and its memory is then wiped in a way that
+/// the compiler can't optimize away, before being deallocated. Its `Debug`
+/// implementation doesn't show the value.
+///
+/// Note that this doesn't cover copies of the value made before it was
+/// placed in the box, nor memory the value itself owns. [`SecureBox::new_with`]
+/// and [`SecureBox::new_zeroed`] avoid the former as much as possible.
+///
+/// [`SecureBox::new_with`]: #method.new_with
+/// [`SecureBox::new_zeroed`]: #method.new_zeroed
+///
+/// With the `os_alloc` feature, [`SecureBox::new_locked_zeroed`] additionally
+/// locks the memory in physical memory, so that it is never written to swap.
+///
+/// [`SecureBox::new_locked_zeroed`]: #method.new_locked_zeroed
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::SecureBox;
+///
+/// fn main() {
+///     let mut key: SecureBox<[u8; 32]> = SecureBox::new_zeroed();
+///     key.copy_from_slice(&[0x42; 32]);
+///     assert_eq!(format!("{:?}", key), "SecureBox(..)");
+/// }
+/// ```
+pub struct SecureBox<T> {
+    ptr: NonNull<T>,
+    // Size of the locked mapping the value lives in, or 0 if it lives in a
+    // `Box`.
+    locked_size: usize,
+}
+
+impl<T> SecureBox<T> {
+    /// Allocates memory on the heap and then places `x` into it.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[inline]
+    pub fn new(x: T) -> Self {
+        SecureBox::from_box(Box::new(ManuallyDrop::new(x)))
+    }
+
+    /// Allocates memory on the heap and then places the result of `f` into
+    /// it.
+    ///
+    /// See [`BoxExt::new_with`].
+    ///
+    /// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[inline]
+    #[track_caller]
+    pub fn new_with<F: FnOnce() -> T>(f: F) -> Self {
+        SecureBox::from_box(BoxExt::new_with(|| ManuallyDrop::new(f())))
+    }
+
+    /// Allocates zeroed memory on the heap.
+    ///
+    /// See [`BoxExt::new_zeroed`].
+    ///
+    /// [`BoxExt::new_zeroed`]: trait.BoxExt.html#tymethod.new_zeroed
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[inline]
+    #[track_caller]
+    pub fn new_zeroed() -> Self
+    where
+        T: Zero,
+    {
+        SecureBox::from_box(BoxExt::new_zeroed())
+    }
+
+    /// Fallible [`SecureBox::new`]
+    ///
+    /// [`SecureBox::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    #[inline]
+    pub fn try_new(x: T) -> Option<Self> {
+        BoxExt::try_new(ManuallyDrop::new(x)).map(SecureBox::from_box)
+    }
+
+    /// Fallible [`SecureBox::new_with`]
+    ///
+    /// [`SecureBox::new_with`]: #method.new_with
+    ///
+    /// This returns `None` if memory couldn't be allocated, in which case
+    /// `f` is not called.
+    #[inline]
+    pub fn try_new_with<F: FnOnce() -> T>(f: F) -> Option<Self> {
+        BoxExt::try_new_with(|| ManuallyDrop::new(f())).map(SecureBox::from_box)
+    }
+
+    /// Fallible [`SecureBox::new_zeroed`]
+    ///
+    /// [`SecureBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    #[inline]
+    pub fn try_new_zeroed() -> Option<Self>
+    where
+        T: Zero,
+    {
+        BoxExt::try_new_zeroed().map(SecureBox::from_box)
+    }
+
+    /// Allocates zeroed memory directly from the operating system, and locks
+    /// it in physical memory with `mlock` on Unix systems, or `VirtualLock`
+    /// on Windows, so that it is never written to swap. The memory is
+    /// unlocked after being wiped, when the box is dropped.
+    ///
+    /// This doesn't actually allocate if `T` is zero-sized.
+    ///
+    /// Returns an error if the memory couldn't be locked, e.g. because the
+    /// limit of locked memory for the process (`RLIMIT_MEMLOCK` on Unix
+    /// systems, the minimum working set size on Windows) was reached.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::SecureBox;
+    ///
+    /// fn main() {
+    ///     match SecureBox::<[u8; 32]>::new_locked_zeroed() {
+    ///         Ok(mut key) => {
+    ///             assert!(key.is_locked());
+    ///             key.copy_from_slice(&[0x42; 32]);
+    ///         }
+    ///         Err(e) => eprintln!("couldn't lock memory: {}", e),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[cfg(feature = "os_alloc")]
+    #[track_caller]
+    pub fn new_locked_zeroed() -> Result<Self, io::Error>
+    where
+        T: Zero,
+    {
+        match SecureBox::alloc_locked() {
+            Ok(result) => result,
+            Err(e) => e.handle(),
+        }
+    }
+
+    /// Fallible [`SecureBox::new_locked_zeroed`]
+    ///
+    /// [`SecureBox::new_locked_zeroed`]: #method.new_locked_zeroed
+    ///
+    /// This returns an error if memory couldn't be allocated or locked.
+    #[cfg(feature = "os_alloc")]
+    #[track_caller]
+    pub fn try_new_locked_zeroed() -> Result<Self, LockedAllocError>
+    where
+        T: Zero,
+    {
+        SecureBox::alloc_locked()
+            .map_err(LockedAllocError::Alloc)?
+            .map_err(LockedAllocError::Lock)
+    }
+
+    // Allocates and locks zeroed memory for a `T`. Locking errors are
+    // returned as Ok(Err(_)), so that callers can handle allocation errors
+    // separately.
+    #[cfg(feature = "os_alloc")]
+    #[track_caller]
+    fn alloc_locked() -> Result<Result<Self, io::Error>, AllocError>
+    where
+        T: Zero,
+    {
+        let layout = Layout::new::<T>();
+        if layout.size() == 0 {
+            return Ok(Ok(SecureBox::from_box(BoxExt::new_zeroed())));
+        }
+        let (raw, locked_size) = match unsafe { try_map_locked(layout)? } {
+            Ok(mapping) => mapping,
+            Err(e) => return Ok(Err(e)),
+        };
+        Ok(Ok(SecureBox {
+            ptr: unsafe { NonNull::new_unchecked(raw as *mut T) },
+            locked_size,
+        }))
+    }
+
+    #[inline]
+    fn from_box(b: Box<ManuallyDrop<T>>) -> Self {
+        let raw = Box::into_raw(b) as *mut T;
+        SecureBox {
+            ptr: unsafe { NonNull::new_unchecked(raw) },
+            locked_size: 0,
+        }
+    }
+
+    /// Returns whether the memory is locked in physical memory.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.locked_size != 0
+    }
+}
+
+impl<T> Deref for SecureBox<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for SecureBox<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+// Wipes the memory of the `T` at `raw` when dropped, so that it is wiped
+// even if dropping the value panics. The memory is then released if
+// `release` is set, to the size of the locked mapping it comes from, or 0
+// if it comes from the allocator.
+struct WipeOnDrop<T> {
+    raw: *mut T,
+    release: Option<usize>,
+}
+
+impl<T> Drop for WipeOnDrop<T> {
+    fn drop(&mut self) {
+        unsafe {
+            volatile_fill(self.raw as *mut u8, mem::size_of::<T>(), 0);
+            match self.release {
+                None => {}
+                #[cfg(feature = "os_alloc")]
+                Some(locked_size) if locked_size != 0 => {
+                    unmap_locked(self.raw as *mut u8, locked_size)
+                }
+                Some(_) => drop(Box::from_raw(self.raw as *mut ManuallyDrop<T>)),
+            }
+        }
+    }
+}
+
+impl<T> Drop for SecureBox<T> {
+    fn drop(&mut self) {
+        let raw = self.ptr.as_ptr();
+        let _wipe = WipeOnDrop {
+            raw,
+            release: Some(self.locked_size),
+        };
+        unsafe { ptr::drop_in_place(raw) }
+    }
+}
+
+unsafe impl<T: Send> Send for SecureBox<T> {}
+
+unsafe impl<T: Sync> Sync for SecureBox<T> {}
+
+impl<T> fmt::Debug for SecureBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SecureBox(..)")
+    }
+}
+
+// The value shared by a `SecureRc` or a `SecureArc`, which is wiped when the
+// last reference is dropped.
+//
+// The tag comes first, and `Inline` is 0, so that all zeroes is a valid,
+// zeroed, `Inline` value.
+#[repr(u8)]
+enum Secret<T> {
+    // Stored in the reference-counted block itself.
+    Inline(MaybeUninit<T>),
+    // Stored in a locked mapping, which `SecureBox` wipes.
+    #[cfg_attr(not(feature = "os_alloc"), allow(dead_code))]
+    Locked(SecureBox<T>),
+}
+
+unsafe impl<T: Zero> Zero for Secret<T> {}
+
+impl<T> Secret<T> {
+    #[inline]
+    fn get(&self) -> &T {
+        match *self {
+            Secret::Inline(ref value) => unsafe { &*value.as_ptr() },
+            Secret::Locked(ref value) => value,
+        }
+    }
+
+    #[inline]
+    fn get_mut(&mut self) -> &mut T {
+        match *self {
+            Secret::Inline(ref mut value) => unsafe { &mut *value.as_mut_ptr() },
+            Secret::Locked(ref mut value) => value,
+        }
+    }
+}
+
+impl<T> Drop for Secret<T> {
+    fn drop(&mut self) {
+        if let Secret::Inline(ref mut value) = *self {
+            let _wipe = WipeOnDrop {
+                raw: value.as_mut_ptr(),
+                release: None,
+            };
+            unsafe { ptr::drop_in_place(value.as_mut_ptr()) }
+        }
+    }
+}
+
+macro_rules! secure_rc_impl {
+    ($(#[$attr:meta])* $name:ident, $rc:ident, $ext:ident) => {
+        $(#[$attr])*
+        pub struct $name<T> {
+            inner: $rc<Secret<T>>,
+        }
+
+        impl<T> $name<T> {
+            /// Allocates memory for the reference-counted block and then
+            /// places `x` into it.
+            #[inline]
+            pub fn new(x: T) -> Self {
+                $name::new_with(|| x)
+            }
+
+            /// Allocates memory for the reference-counted block and then
+            /// places the result of `f` into it.
+            ///
+            #[doc = concat!("See [`", stringify!($ext), "::new_with`].")]
+            ///
+            #[doc = concat!("[`", stringify!($ext), "::new_with`]: trait.", stringify!($ext), ".html#tymethod.new_with")]
+            #[inline]
+            pub fn new_with<F: FnOnce() -> T>(f: F) -> Self {
+                $name {
+                    inner: $ext::new_with(|| Secret::Inline(MaybeUninit::new(f()))),
+                }
+            }
+
+            /// Allocates memory for the reference-counted block and fills
+            /// the value with zeroes.
+            ///
+            #[doc = concat!("See [`", stringify!($ext), "::new_zeroed`].")]
+            ///
+            #[doc = concat!("[`", stringify!($ext), "::new_zeroed`]: trait.", stringify!($ext), ".html#tymethod.new_zeroed")]
+            ///
+            /// # Safety
+            ///
+            /// This method is only assumed safe for types implementing the
+            /// [`Zero`] trait, and not available otherwise. See the
+            /// definition of that trait.
+            ///
+            /// [`Zero`]: trait.Zero.html
+            #[inline]
+            pub fn new_zeroed() -> Self
+            where
+                T: Zero,
+            {
+                $name {
+                    inner: $ext::new_zeroed(),
+                }
+            }
+
+            /// Allocates zeroed memory directly from the operating system,
+            /// and locks it in physical memory, as with
+            /// [`SecureBox::new_locked_zeroed`]. The memory is wiped,
+            /// unlocked and unmapped when the last reference is dropped.
+            ///
+            /// The value lives in its own mapping, separate from the
+            /// reference-counted block.
+            ///
+            /// Returns an error if the memory couldn't be locked.
+            ///
+            /// [`SecureBox::new_locked_zeroed`]: struct.SecureBox.html#method.new_locked_zeroed
+            ///
+            /// # Safety
+            ///
+            /// This method is only assumed safe for types implementing the
+            /// [`Zero`] trait, and not available otherwise. See the
+            /// definition of that trait.
+            ///
+            /// [`Zero`]: trait.Zero.html
+            ///
+            /// # Panics
+            ///
+            /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+            ///
+            /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+            #[cfg(feature = "os_alloc")]
+            #[track_caller]
+            pub fn new_locked_zeroed() -> Result<Self, io::Error>
+            where
+                T: Zero,
+            {
+                SecureBox::new_locked_zeroed().map($name::from_secure_box)
+            }
+
+            #[doc = concat!("Fallible [`", stringify!($name), "::new_locked_zeroed`]")]
+            ///
+            #[doc = concat!("[`", stringify!($name), "::new_locked_zeroed`]: #method.new_locked_zeroed")]
+            ///
+            /// This returns an error if memory for the value couldn't be
+            /// allocated or locked. Allocating the reference-counted block
+            /// itself can't fail gracefully.
+            #[cfg(feature = "os_alloc")]
+            #[track_caller]
+            pub fn try_new_locked_zeroed() -> Result<Self, LockedAllocError>
+            where
+                T: Zero,
+            {
+                SecureBox::try_new_locked_zeroed().map($name::from_secure_box)
+            }
+
+            #[cfg(feature = "os_alloc")]
+            #[inline]
+            fn from_secure_box(b: SecureBox<T>) -> Self {
+                $name {
+                    inner: $ext::new_with(|| Secret::Locked(b)),
+                }
+            }
+
+            /// Returns a mutable reference to the value, if there are no
+            /// other references to it.
+            ///
+            #[doc = concat!("See `", stringify!($rc), "::get_mut`.")]
+            #[inline]
+            pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+                $rc::get_mut(&mut this.inner).map(Secret::get_mut)
+            }
+
+            /// Returns whether two pointers point to the same value.
+            #[inline]
+            pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+                $rc::ptr_eq(&this.inner, &other.inner)
+            }
+
+            /// Returns whether the memory is locked in physical memory.
+            #[inline]
+            pub fn is_locked(this: &Self) -> bool {
+                match *this.inner {
+                    Secret::Inline(_) => false,
+                    Secret::Locked(ref value) => value.is_locked(),
+                }
+            }
+        }
+
+        impl<T> Clone for $name<T> {
+            #[inline]
+            fn clone(&self) -> Self {
+                $name {
+                    inner: $rc::clone(&self.inner),
+                }
+            }
+        }
+
+        impl<T> Deref for $name<T> {
+            type Target = T;
+
+            #[inline]
+            fn deref(&self) -> &T {
+                self.inner.get()
+            }
+        }
+
+        impl<T> fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(concat!(stringify!($name), "(..)"))
+            }
+        }
+    };
+}
+
+secure_rc_impl! {
+    /// A single-threaded reference-counted pointer whose memory is
+    /// overwritten with zeroes when the last reference is dropped, for
+    /// secrets shared within a thread.
+    ///
+    /// This is the `Rc` counterpart of [`SecureBox`]. The value is
+    /// constructed in place in the reference-counted block, as with
+    /// [`RcExt`], and is dropped and wiped, in a way that the compiler can't
+    /// optimize away, when the last reference is dropped. With the
+    /// `os_alloc` feature, [`SecureRc::new_locked_zeroed`] additionally locks
+    /// the memory in physical memory.
+    ///
+    /// Contrary to `Rc`, there are no weak references, and the value can't
+    /// be moved out.
+    ///
+    /// [`SecureBox`]: struct.SecureBox.html
+    /// [`RcExt`]: trait.RcExt.html
+    /// [`SecureRc::new_locked_zeroed`]: #method.new_locked_zeroed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::SecureRc;
+    ///
+    /// fn main() {
+    ///     let mut key: SecureRc<[u8; 32]> = SecureRc::new_zeroed();
+    ///     SecureRc::get_mut(&mut key).unwrap().copy_from_slice(&[0x42; 32]);
+    ///     let shared = key.clone();
+    ///     assert!(SecureRc::get_mut(&mut key).is_none());
+    ///     assert_eq!(shared[31], 0x42);
+    ///     assert_eq!(format!("{:?}", shared), "SecureRc(..)");
+    /// }
+    /// ```
+    SecureRc, Rc, RcExt
+}
+
+secure_rc_impl! {
+    /// A thread-safe reference-counted pointer whose memory is overwritten
+    /// with zeroes when the last reference is dropped, for secrets shared
+    /// between threads, such as session keys.
+    ///
+    /// This is the `Arc` counterpart of [`SecureBox`]. The value is
+    /// constructed in place in the reference-counted block, as with
+    /// [`ArcExt`], and is dropped and wiped, in a way that the compiler can't
+    /// optimize away, when the last reference is dropped. With the
+    /// `os_alloc` feature, [`SecureArc::new_locked_zeroed`] additionally
+    /// locks the memory in physical memory.
+    ///
+    /// Contrary to `Arc`, there are no weak references, and the value can't
+    /// be moved out.
+    ///
+    /// [`SecureBox`]: struct.SecureBox.html
+    /// [`ArcExt`]: trait.ArcExt.html
+    /// [`SecureArc::new_locked_zeroed`]: #method.new_locked_zeroed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::SecureArc;
+    /// use std::thread;
+    ///
+    /// fn main() {
+    ///     let key = SecureArc::new_with(|| [0x42u8; 32]);
+    ///     let shared = key.clone();
+    ///     let sum = thread::spawn(move || shared.iter().map(|&b| b as u32).sum::<u32>());
+    ///     assert_eq!(sum.join().unwrap(), 0x42 * 32);
+    ///     assert!(!SecureArc::is_locked(&key));
+    ///
+    /// #   #[cfg(feature = "os_alloc")]
+    /// #   {
+    ///     match SecureArc::<[u8; 32]>::new_locked_zeroed() {
+    ///         Ok(mut key) => {
+    ///             assert!(SecureArc::is_locked(&key));
+    ///             SecureArc::get_mut(&mut key).unwrap()[0] = 1;
+    ///         }
+    ///         Err(e) => eprintln!("couldn't lock memory: {}", e),
+    ///     }
+    /// #   }
+    /// }
+    /// ```
+    SecureArc, Arc, ArcExt
+}
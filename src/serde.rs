@@ -0,0 +1,116 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Deserialization of large values directly into a `Box`.
+//!
+//! `Box::new(T::deserialize(deserializer)?)` builds the value on the stack
+//! before moving it to the heap, which, in debug builds, can overflow the
+//! stack for large values. The helpers in this module instead allocate
+//! zeroed memory first, and then deserialize the value in place, with
+//! `Deserialize::deserialize_in_place`.
+//!
+//! serde implements `deserialize_in_place` for arrays and tuples, and
+//! `#[derive(Deserialize)]` implements it for structs when the
+//! `deserialize_in_place` feature of `serde_derive` is enabled. Other types
+//! fall back to deserializing on the stack and moving the value in place.
+//!
+//! The module can be used with `#[serde(with = "boxext::serde")]` on fields
+//! of type `Box<T>`.
+//!
+//! # Example
+//!
+//! ```
+//! #[macro_use]
+//! extern crate boxext;
+//! #[macro_use]
+//! extern crate serde_derive;
+//! extern crate serde_json;
+//!
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     id: u32,
+//!     weights: [[f32; 32]; 32],
+//! }
+//!
+//! zero_impl!(struct Config { id: u32, weights: [[f32; 32]; 32] });
+//!
+//! #[derive(Deserialize)]
+//! struct State {
+//!     #[serde(with = "boxext::serde")]
+//!     config: Box<Config>,
+//! }
+//!
+//! fn main() {
+//!     let row = format!("[{}]", vec!["0.5"; 32].join(","));
+//!     let config = format!("{{\"id\":7,\"weights\":[{}]}}", vec![row; 32].join(","));
+//!     let state = format!("{{\"config\":{}}}", config);
+//!
+//!     let mut de = serde_json::Deserializer::from_str(&config);
+//!     let config: Box<Config> = boxext::serde::deserialize_boxed(&mut de).unwrap();
+//!     assert_eq!(config.id, 7);
+//!     assert_eq!(config.weights[31][31], 0.5);
+//!
+//!     let state: State = serde_json::from_str(&state).unwrap();
+//!     assert_eq!(state.config.weights[0][0], 0.5);
+//! }
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use serde_crate::{Deserialize, Deserializer, Serialize, Serializer};
+use {BoxExt, Zero};
+
+/// Deserializes a value directly into a `Box`.
+///
+/// See the [module documentation](index.html).
+///
+/// # Safety
+///
+/// This function is only assumed safe for types implementing the [`Zero`]
+/// trait, and not available otherwise. See the definition of that trait.
+///
+/// [`Zero`]: ../trait.Zero.html
+///
+/// # Panics
+///
+/// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[track_caller]
+pub fn deserialize_boxed<'de, T, D>(deserializer: D) -> Result<Box<T>, D::Error>
+where
+    T: Deserialize<'de> + Zero,
+    D: Deserializer<'de>,
+{
+    let mut boxed: Box<T> = BoxExt::new_zeroed();
+    T::deserialize_in_place(deserializer, &mut boxed)?;
+    Ok(boxed)
+}
+
+/// Same as [`deserialize_boxed`], for use with `#[serde(with = ...)]`.
+///
+/// [`deserialize_boxed`]: fn.deserialize_boxed.html
+#[inline]
+#[track_caller]
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Box<T>, D::Error>
+where
+    T: Deserialize<'de> + Zero,
+    D: Deserializer<'de>,
+{
+    deserialize_boxed(deserializer)
+}
+
+/// Serializes the boxed value, for use with `#[serde(with = ...)]`.
+#[inline]
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + Serialize,
+    S: Serializer,
+{
+    value.serialize(serializer)
+}
@@ -0,0 +1,452 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::marker::PhantomData;
+use core::num::Wrapping;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::sync::atomic;
+use core::{cmp, fmt, mem};
+use std::io;
+use Zero;
+
+/// Trait indicating that any sequence of bytes of the right size is a valid
+/// value of a type.
+///
+/// This is required for the values of a [`ShmBox`], since other processes
+/// can write arbitrary bytes to them.
+///
+/// [`ShmBox`]: struct.ShmBox.html
+///
+/// # Safety
+///
+/// Do **not** implement this trait for types for which some bit patterns
+/// are not valid, such as `bool`, `char`, references, or enums, or for types
+/// containing such types.
+///
+/// # Implementations
+///
+/// `AnyBitPattern` is implemented for integer and floating point types,
+/// atomic integer types, and for arrays and `Wrapping<T>` when `T`
+/// implements `AnyBitPattern`.
+pub unsafe trait AnyBitPattern: Zero {}
+
+macro_rules! any_bit_pattern_impl {
+    ($($t:ty)+) => { $(unsafe impl AnyBitPattern for $t {})+ }
+}
+
+any_bit_pattern_impl! {
+    u8 u16 u32 u64 u128 usize
+    i8 i16 i32 i64 i128 isize
+    f32 f64
+}
+
+#[cfg(target_has_atomic = "8")]
+any_bit_pattern_impl! {
+    atomic::AtomicU8 atomic::AtomicI8
+}
+
+#[cfg(target_has_atomic = "16")]
+any_bit_pattern_impl! {
+    atomic::AtomicU16 atomic::AtomicI16
+}
+
+#[cfg(target_has_atomic = "32")]
+any_bit_pattern_impl! {
+    atomic::AtomicU32 atomic::AtomicI32
+}
+
+#[cfg(target_has_atomic = "64")]
+any_bit_pattern_impl! {
+    atomic::AtomicU64 atomic::AtomicI64
+}
+
+#[cfg(target_has_atomic = "ptr")]
+any_bit_pattern_impl! {
+    atomic::AtomicUsize atomic::AtomicIsize
+}
+
+unsafe impl<T: AnyBitPattern, const N: usize> AnyBitPattern for [T; N] {}
+
+unsafe impl<T: AnyBitPattern> AnyBitPattern for Wrapping<T> {}
+
+// Alignment mapped memory is guaranteed to have. Pages are at least this
+// large on all supported platforms.
+const MAP_ALIGN: usize = 4096;
+
+fn invalid_input(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[cfg(any(all(unix, not(target_os = "android")), windows))]
+fn too_small() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "shared memory segment is too small",
+    )
+}
+
+#[cfg(all(unix, not(target_os = "android")))]
+mod sys {
+    use core::{mem, ptr};
+    use libc;
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::io::RawFd;
+
+    // The name of the segment, which is removed when the mapping is closed
+    // if this process created it.
+    pub struct Segment {
+        name: CString,
+        owner: bool,
+    }
+
+    fn c_name(name: &str) -> io::Result<CString> {
+        CString::new(name).map_err(|_| super::invalid_input("name contains a NUL byte"))
+    }
+
+    unsafe fn map(fd: RawFd, size: usize) -> io::Result<*mut u8> {
+        let raw = libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        );
+        if raw == libc::MAP_FAILED {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(raw as *mut u8)
+        }
+    }
+
+    // Creates a segment of `size` bytes, which the kernel provides zeroed,
+    // and maps it.
+    pub unsafe fn create(name: &str, size: usize) -> io::Result<(*mut u8, Segment)> {
+        let name = c_name(name)?;
+        let fd = libc::shm_open(
+            name.as_ptr(),
+            libc::O_CREAT | libc::O_EXCL | libc::O_RDWR,
+            0o600,
+        );
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let result = if libc::ftruncate(fd, size as libc::off_t) == 0 {
+            map(fd, size)
+        } else {
+            Err(io::Error::last_os_error())
+        };
+        libc::close(fd);
+        match result {
+            Ok(raw) => Ok((raw, Segment { name, owner: true })),
+            Err(e) => {
+                libc::shm_unlink(name.as_ptr());
+                Err(e)
+            }
+        }
+    }
+
+    // Maps an existing segment of at least `size` bytes.
+    pub unsafe fn open(name: &str, size: usize) -> io::Result<(*mut u8, Segment)> {
+        let name = c_name(name)?;
+        let fd = libc::shm_open(name.as_ptr(), libc::O_RDWR, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut stat: libc::stat = mem::zeroed();
+        let result = if libc::fstat(fd, &mut stat) != 0 {
+            Err(io::Error::last_os_error())
+        } else if (stat.st_size as u64) < size as u64 {
+            Err(super::too_small())
+        } else {
+            map(fd, size)
+        };
+        libc::close(fd);
+        Ok((result?, Segment { name, owner: false }))
+    }
+
+    pub unsafe fn close(ptr: *mut u8, size: usize, segment: &Segment) {
+        libc::munmap(ptr as *mut libc::c_void, size);
+        if segment.owner {
+            libc::shm_unlink(segment.name.as_ptr());
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use core::{mem, ptr};
+    use std::io;
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, GetLastError, ERROR_ALREADY_EXISTS, HANDLE, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::System::Memory::{
+        CreateFileMappingW, MapViewOfFile, OpenFileMappingW, UnmapViewOfFile, VirtualQuery,
+        FILE_MAP_ALL_ACCESS, MEMORY_BASIC_INFORMATION, MEMORY_MAPPED_VIEW_ADDRESS,
+        PAGE_READWRITE,
+    };
+
+    // The file mapping, which is destroyed when its last handle is closed.
+    pub struct Segment(HANDLE);
+
+    fn wide_name(name: &str) -> io::Result<Vec<u16>> {
+        if name.contains('\0') {
+            return Err(super::invalid_input("name contains a NUL byte"));
+        }
+        Ok(name.encode_utf16().chain(Some(0)).collect())
+    }
+
+    unsafe fn map(handle: HANDLE, size: usize) -> io::Result<*mut u8> {
+        let view = MapViewOfFile(handle, FILE_MAP_ALL_ACCESS, 0, 0, size);
+        if view.Value.is_null() {
+            let e = io::Error::last_os_error();
+            CloseHandle(handle);
+            Err(e)
+        } else {
+            Ok(view.Value as *mut u8)
+        }
+    }
+
+    // Creates a mapping of `size` bytes backed by the paging file, which
+    // the kernel provides zeroed, and maps it.
+    pub unsafe fn create(name: &str, size: usize) -> io::Result<(*mut u8, Segment)> {
+        let name = wide_name(name)?;
+        let size64 = size as u64;
+        let handle = CreateFileMappingW(
+            INVALID_HANDLE_VALUE,
+            ptr::null(),
+            PAGE_READWRITE,
+            (size64 >> 32) as u32,
+            size64 as u32,
+            name.as_ptr(),
+        );
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        if GetLastError() == ERROR_ALREADY_EXISTS {
+            CloseHandle(handle);
+            return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+        }
+        Ok((map(handle, size)?, Segment(handle)))
+    }
+
+    // Maps an existing mapping of at least `size` bytes.
+    pub unsafe fn open(name: &str, size: usize) -> io::Result<(*mut u8, Segment)> {
+        let name = wide_name(name)?;
+        let handle = OpenFileMappingW(FILE_MAP_ALL_ACCESS, 0, name.as_ptr());
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        // Map the whole mapping, whose size is only known from the view.
+        let raw = map(handle, 0)?;
+        let mut info: MEMORY_BASIC_INFORMATION = mem::zeroed();
+        VirtualQuery(
+            raw as *const _,
+            &mut info,
+            mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+        );
+        let segment = Segment(handle);
+        if info.RegionSize < size {
+            close(raw, size, &segment);
+            return Err(super::too_small());
+        }
+        Ok((raw, segment))
+    }
+
+    pub unsafe fn close(ptr: *mut u8, _size: usize, segment: &Segment) {
+        UnmapViewOfFile(MEMORY_MAPPED_VIEW_ADDRESS {
+            Value: ptr as *mut _,
+        });
+        CloseHandle(segment.0);
+    }
+}
+
+#[cfg(not(any(all(unix, not(target_os = "android")), windows)))]
+mod sys {
+    use std::io;
+
+    pub struct Segment;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "shared memory is not supported on this platform",
+        )
+    }
+
+    pub unsafe fn create(_name: &str, _size: usize) -> io::Result<(*mut u8, Segment)> {
+        Err(unsupported())
+    }
+
+    pub unsafe fn open(_name: &str, _size: usize) -> io::Result<(*mut u8, Segment)> {
+        Err(unsupported())
+    }
+
+    pub unsafe fn close(_ptr: *mut u8, _size: usize, _segment: &Segment) {
+        unreachable!()
+    }
+}
+
+/// A zeroed value in a named shared memory segment, which other processes
+/// can map.
+///
+/// The segment is created with `shm_open` on Unix systems, and is a file
+/// mapping backed by the paging file on Windows. In both cases, the memory
+/// is zeroed by the operating system. Other processes map the same segment
+/// with [`ShmBox::open`].
+///
+/// On Unix systems, names should start with a `/` and contain no other `/`.
+/// On Windows, names can be prefixed with `Global\` or `Local\`.
+///
+/// The segment is unmapped when the `ShmBox` is dropped. On Unix systems,
+/// the name of a segment is removed when the `ShmBox` that created it is
+/// dropped, and the memory is freed once all the processes unmapped it. On
+/// Windows, the segment is freed once all the processes closed it.
+///
+/// Contrary to other boxes, the value is not dropped, since other processes
+/// may still use it.
+///
+/// This only dereferences to `&T`: as other processes can modify the value
+/// at any time, it can only be modified through atomic types or other
+/// interior mutability. For the same reason, `T` must implement
+/// [`AnyBitPattern`].
+///
+/// [`AnyBitPattern`]: trait.AnyBitPattern.html
+///
+/// [`ShmBox::open`]: #method.open
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::ShmBox;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// fn main() {
+///     let name = format!("/boxext-doc-{}", std::process::id());
+/// #   #[cfg(any(all(unix, not(target_os = "android")), windows))]
+/// #   {
+///     let stats: ShmBox<[AtomicU64; 1024]> = ShmBox::new_zeroed(&name);
+///     stats[3].fetch_add(1, Ordering::SeqCst);
+///
+///     // Typically in another process.
+///     let view: ShmBox<[AtomicU64; 1024]> = unsafe { ShmBox::open(&name) }.unwrap();
+///     assert_eq!(view[3].load(Ordering::SeqCst), 1);
+/// #   }
+/// }
+/// ```
+pub struct ShmBox<T: AnyBitPattern> {
+    ptr: NonNull<T>,
+    size: usize,
+    segment: sys::Segment,
+    marker: PhantomData<T>,
+}
+
+impl<T: AnyBitPattern> ShmBox<T> {
+    // Size of the mapping. Mappings can't be empty.
+    fn map_size() -> usize {
+        cmp::max(mem::size_of::<T>(), 1)
+    }
+
+    fn check_align() -> io::Result<()> {
+        if mem::align_of::<T>() > MAP_ALIGN {
+            Err(invalid_input("alignment is too large for shared memory"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn from_raw(raw: *mut u8, segment: sys::Segment) -> Self {
+        ShmBox {
+            ptr: unsafe { NonNull::new_unchecked(raw as *mut T) },
+            size: Self::map_size(),
+            segment,
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates a shared memory segment named `name`, holding a zeroed `T`.
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the segment couldn't be created, e.g. because a segment
+    /// with the same name exists.
+    #[track_caller]
+    pub fn new_zeroed(name: &str) -> Self {
+        match ShmBox::try_new_zeroed(name) {
+            Ok(b) => b,
+            Err(e) => panic!("couldn't create shared memory segment {}: {}", name, e),
+        }
+    }
+
+    /// Fallible [`ShmBox::new_zeroed`]
+    ///
+    /// [`ShmBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// This returns an error if the segment couldn't be created, e.g.
+    /// because a segment with the same name exists.
+    pub fn try_new_zeroed(name: &str) -> io::Result<Self> {
+        Self::check_align()?;
+        let (raw, segment) = unsafe { sys::create(name, Self::map_size())? };
+        Ok(ShmBox::from_raw(raw, segment))
+    }
+
+    /// Maps an existing shared memory segment named `name`, e.g. created by
+    /// another process with [`ShmBox::new_zeroed`].
+    ///
+    /// This returns an error if the segment doesn't exist, couldn't be
+    /// mapped, or is smaller than a `T`.
+    ///
+    /// [`ShmBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// # Safety
+    ///
+    /// The segment must have been created for a `T`, or a type with the same
+    /// layout, and other processes must only modify it through atomic
+    /// accesses.
+    pub unsafe fn open(name: &str) -> io::Result<Self> {
+        Self::check_align()?;
+        let (raw, segment) = sys::open(name, Self::map_size())?;
+        Ok(ShmBox::from_raw(raw, segment))
+    }
+}
+
+impl<T: AnyBitPattern> Deref for ShmBox<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: AnyBitPattern> Drop for ShmBox<T> {
+    fn drop(&mut self) {
+        unsafe { sys::close(self.ptr.as_ptr() as *mut u8, self.size, &self.segment) }
+    }
+}
+
+unsafe impl<T: AnyBitPattern + Send> Send for ShmBox<T> {}
+
+unsafe impl<T: AnyBitPattern + Sync> Sync for ShmBox<T> {}
+
+impl<T: AnyBitPattern + fmt::Debug> fmt::Debug for ShmBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,371 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::{cmp, fmt, mem, ptr};
+use heap::{dealloc_layout, poison, try_alloc_layout};
+use std::alloc::Layout;
+use std::sync::Mutex;
+use {AllocError, IsZst, Zero};
+
+// Sizes of the smallest and largest size classes. Each size class is a power
+// of two.
+const MIN_CLASS_SHIFT: usize = 4;
+const MAX_CLASS_SHIFT: usize = 12;
+const NUM_CLASSES: usize = MAX_CLASS_SHIFT - MIN_CLASS_SHIFT + 1;
+
+// Size of the chunks slots are carved from.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Marker for values too large for any size class, which are allocated
+// directly.
+const LARGE: usize = usize::MAX;
+
+// Returns the index of the size class for values of type `T`.
+fn class_for<T>() -> usize {
+    let size = cmp::max(mem::size_of::<T>(), mem::align_of::<T>());
+    let shift = cmp::max(
+        size.next_power_of_two().trailing_zeros() as usize,
+        MIN_CLASS_SHIFT,
+    );
+    if shift > MAX_CLASS_SHIFT {
+        LARGE
+    } else {
+        shift - MIN_CLASS_SHIFT
+    }
+}
+
+fn class_size(class: usize) -> usize {
+    1 << (class + MIN_CLASS_SHIFT)
+}
+
+fn chunk_layout(class: usize) -> Layout {
+    Layout::from_size_align(CHUNK_SIZE, class_size(class)).unwrap()
+}
+
+struct Class {
+    // Head of the list of free slots. Each free slot holds a pointer to the
+    // next one.
+    free: *mut u8,
+    chunks: Vec<*mut u8>,
+}
+
+/// A slab allocator, grouping allocations of similar sizes.
+///
+/// Values are placed in slots of power-of-two size classes, from 16 to 4096
+/// bytes, carved from larger chunks. Freed slots are reused for later
+/// allocations of the same size class, which is cheaper than going through
+/// the allocator when the same sizes are allocated over and over. Chunks are
+/// only released when the `Slab` itself is dropped.
+///
+/// Values larger than the largest size class are allocated directly.
+///
+/// Values are allocated in a `Slab` with the [`SlabBox`] constructors.
+///
+/// [`SlabBox`]: struct.SlabBox.html
+pub struct Slab {
+    classes: Mutex<[Class; NUM_CLASSES]>,
+}
+
+impl Default for Slab {
+    fn default() -> Self {
+        Slab::new()
+    }
+}
+
+impl Slab {
+    /// Creates a new, empty, slab allocator.
+    ///
+    /// This doesn't allocate until values are allocated in it.
+    pub fn new() -> Self {
+        Slab {
+            classes: Mutex::new(Default::default()),
+        }
+    }
+
+    // Returns a slot for a value of type `T`.
+    fn alloc<T>(&self) -> Result<*mut T, AllocError> {
+        if IsZst::<T>::VALUE {
+            return Ok(ptr::NonNull::dangling().as_ptr());
+        }
+        let class = class_for::<T>();
+        if class == LARGE {
+            return unsafe { try_alloc_layout(Layout::new::<T>(), false).map(|p| p as *mut T) };
+        }
+        let mut classes = self.classes.lock().unwrap();
+        let c = &mut classes[class];
+        if c.free.is_null() {
+            let chunk = unsafe { try_alloc_layout(chunk_layout(class), false)? };
+            c.chunks.push(chunk);
+            let size = class_size(class);
+            for offset in (0..CHUNK_SIZE).step_by(size).rev() {
+                unsafe {
+                    let slot = chunk.add(offset);
+                    ptr::write(slot as *mut *mut u8, c.free);
+                    c.free = slot;
+                }
+            }
+        }
+        let slot = c.free;
+        c.free = unsafe { ptr::read(slot as *mut *mut u8) };
+        Ok(slot as *mut T)
+    }
+
+    // Puts back a slot obtained from `alloc`.
+    unsafe fn release<T>(&self, slot: *mut T) {
+        if IsZst::<T>::VALUE {
+            return;
+        }
+        let class = class_for::<T>();
+        if class == LARGE {
+            return dealloc_layout(slot as *mut u8, Layout::new::<T>());
+        }
+        let slot = slot as *mut u8;
+        poison(slot, class_size(class));
+        let mut classes = self.classes.lock().unwrap();
+        let c = &mut classes[class];
+        ptr::write(slot as *mut *mut u8, c.free);
+        c.free = slot;
+    }
+}
+
+impl Default for Class {
+    fn default() -> Self {
+        Class {
+            free: ptr::null_mut(),
+            chunks: Vec::new(),
+        }
+    }
+}
+
+impl Drop for Slab {
+    fn drop(&mut self) {
+        let classes = self.classes.get_mut().unwrap_or_else(|e| e.into_inner());
+        for (class, c) in classes.iter().enumerate() {
+            for &chunk in &c.chunks {
+                unsafe { dealloc_layout(chunk, chunk_layout(class)) }
+            }
+        }
+    }
+}
+
+unsafe impl Send for Slab {}
+
+unsafe impl Sync for Slab {}
+
+impl fmt::Debug for Slab {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Slab").finish()
+    }
+}
+
+// A slot that is returned to the slab if dropped before being filled, e.g.
+// when the function computing the value panics.
+struct Slot<'a, T> {
+    slab: &'a Slab,
+    ptr: *mut T,
+}
+
+impl<'a, T> Slot<'a, T> {
+    fn init(self, value: T) -> SlabBox<'a, T> {
+        unsafe {
+            ptr::write(self.ptr, value);
+            self.assume_init()
+        }
+    }
+
+    unsafe fn assume_init(self) -> SlabBox<'a, T> {
+        let result = SlabBox {
+            slab: self.slab,
+            ptr: self.ptr,
+            marker: PhantomData,
+        };
+        mem::forget(self);
+        result
+    }
+}
+
+impl<'a, T> Drop for Slot<'a, T> {
+    fn drop(&mut self) {
+        unsafe { self.slab.release(self.ptr) }
+    }
+}
+
+/// A value allocated in a [`Slab`].
+///
+/// This dereferences to `T`. When dropped, the value is dropped and its slot
+/// is made available for later allocations.
+///
+/// [`Slab`]: struct.Slab.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{Slab, SlabBox};
+///
+/// struct Request {
+///     id: u64,
+///     headers: [u8; 200],
+/// }
+///
+/// fn main() {
+///     let slab = Slab::new();
+///     let first = SlabBox::new_with(&slab, || Request { id: 1, headers: [0; 200] });
+///     let addr = &*first as *const Request;
+///     drop(first);
+///
+///     // The slot of the first request is reused.
+///     let second = SlabBox::new_with(&slab, || Request { id: 2, headers: [0; 200] });
+///     assert_eq!(&*second as *const Request, addr);
+///     assert_eq!(second.id, 2);
+///
+///     let zeroed: SlabBox<[u64; 8]> = SlabBox::new_zeroed(&slab);
+///     assert_eq!(*zeroed, [0; 8]);
+/// }
+/// ```
+pub struct SlabBox<'a, T> {
+    slab: &'a Slab,
+    ptr: *mut T,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T> SlabBox<'a, T> {
+    #[track_caller]
+    fn slot(slab: &'a Slab) -> Slot<'a, T> {
+        match slab.alloc::<T>() {
+            Ok(ptr) => Slot { slab, ptr },
+            Err(e) => e.handle(),
+        }
+    }
+
+    fn try_slot(slab: &'a Slab) -> Option<Slot<'a, T>> {
+        Some(Slot {
+            slab,
+            ptr: slab.alloc::<T>().ok()?,
+        })
+    }
+
+    /// Places `x` in the slab.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new(slab: &'a Slab, x: T) -> Self {
+        SlabBox::slot(slab).init(x)
+    }
+
+    /// Places the result of `f` in the slab.
+    ///
+    /// As with [`BoxExt::new_with`], this is expected to avoid copies when
+    /// building with optimization enabled.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_with<F: FnOnce() -> T>(slab: &'a Slab, f: F) -> Self {
+        SlabBox::slot(slab).init(f())
+    }
+
+    /// Places a zeroed value in the slab.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new_zeroed(slab: &'a Slab) -> Self
+    where
+        T: Zero,
+    {
+        let slot = SlabBox::slot(slab);
+        unsafe {
+            // Slots are reused, so they need to be zeroed explicitly.
+            ptr::write_bytes(slot.ptr, 0, 1);
+            slot.assume_init()
+        }
+    }
+
+    /// Fallible [`SlabBox::new`]
+    ///
+    /// [`SlabBox::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new(slab: &'a Slab, x: T) -> Option<Self> {
+        Some(SlabBox::try_slot(slab)?.init(x))
+    }
+
+    /// Fallible [`SlabBox::new_with`]
+    ///
+    /// [`SlabBox::new_with`]: #method.new_with
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new_with<F: FnOnce() -> T>(slab: &'a Slab, f: F) -> Option<Self> {
+        Some(SlabBox::try_slot(slab)?.init(f()))
+    }
+
+    /// Fallible [`SlabBox::new_zeroed`]
+    ///
+    /// [`SlabBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new_zeroed(slab: &'a Slab) -> Option<Self>
+    where
+        T: Zero,
+    {
+        let slot = SlabBox::try_slot(slab)?;
+        unsafe {
+            ptr::write_bytes(slot.ptr, 0, 1);
+            Some(slot.assume_init())
+        }
+    }
+}
+
+impl<'a, T> Deref for SlabBox<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<'a, T> DerefMut for SlabBox<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.ptr }
+    }
+}
+
+impl<'a, T> Drop for SlabBox<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.ptr);
+            self.slab.release(self.ptr);
+        }
+    }
+}
+
+unsafe impl<'a, T: Send> Send for SlabBox<'a, T> {}
+
+unsafe impl<'a, T: Sync> Sync for SlabBox<'a, T> {}
+
+impl<'a, T: fmt::Debug> fmt::Debug for SlabBox<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,525 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::{iter, mem, ptr};
+use {new_slice_box, try_new_slice_box, try_realloc_slice, AllocError, BoxBuilder, Zero};
+
+/// Extensions to the `Box<[T]>` type
+pub trait BoxSliceExt: Sized {
+    /// Type of the elements of the slice.
+    type Item;
+
+    /// Allocates a slice of `len` zeroed elements.
+    ///
+    /// This doesn't actually allocate if `len` is 0 or `Self::Item` is
+    /// zero-sized.
+    ///
+    /// This will get zeroed memory directly from the allocator, instead of
+    /// writing zeroes after allocating, as `vec![0; len]` followed by
+    /// `into_boxed_slice` would for most types.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxSliceExt;
+    ///
+    /// fn main() {
+    ///     let samples = 48000;
+    ///     let buf: Box<[f32]> = BoxSliceExt::new_zeroed_slice(samples);
+    ///     assert_eq!(buf.len(), samples);
+    ///     assert!(buf.iter().all(|&s| s == 0.0));
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for `Self::Item` types implementing
+    /// the [`Zero`] trait, and not available otherwise. See the definition
+    /// of that trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_zeroed_slice(len: usize) -> Self
+    where
+        Self::Item: Zero;
+
+    /// Fallible [`BoxSliceExt::new_zeroed_slice`]
+    ///
+    /// [`BoxSliceExt::new_zeroed_slice`]: #tymethod.new_zeroed_slice
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the slice overflows.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxSliceExt;
+    ///
+    /// fn main() {
+    ///     let buf: Option<Box<[u8]>> = BoxSliceExt::try_new_zeroed_slice(4096);
+    ///     assert_eq!(&buf.unwrap()[..], &[0; 4096][..]);
+    ///
+    ///     let buf: Option<Box<[u64]>> = BoxSliceExt::try_new_zeroed_slice(usize::MAX);
+    ///     assert!(buf.is_none());
+    /// }
+    /// ```
+    fn try_new_zeroed_slice(len: usize) -> Option<Self>
+    where
+        Self::Item: Zero;
+
+    /// Same as [`BoxSliceExt::new_zeroed_slice`], without requiring
+    /// `Self::Item` to implement [`Zero`].
+    ///
+    /// [`BoxSliceExt::new_zeroed_slice`]: #tymethod.new_zeroed_slice
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Safety
+    ///
+    /// A value full of zeroes must be a valid value of `Self::Item`. See the
+    /// definition of the [`Zero`] trait.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    unsafe fn new_zeroed_slice_unchecked(len: usize) -> Self;
+
+    /// Fallible [`BoxSliceExt::new_zeroed_slice_unchecked`]
+    ///
+    /// [`BoxSliceExt::new_zeroed_slice_unchecked`]: #tymethod.new_zeroed_slice_unchecked
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the slice overflows.
+    ///
+    /// # Safety
+    ///
+    /// A value full of zeroes must be a valid value of `Self::Item`. See the
+    /// definition of the [`Zero`] trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    unsafe fn try_new_zeroed_slice_unchecked(len: usize) -> Option<Self>;
+
+    /// Allocates a slice of `len` elements and initializes each of them in
+    /// place with the result of `f` called with the element index.
+    ///
+    /// This doesn't actually allocate if `len` is 0 or `Self::Item` is
+    /// zero-sized.
+    ///
+    /// If `f` panics, the elements that were already initialized are dropped
+    /// and the memory is freed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxSliceExt;
+    ///
+    /// fn main() {
+    ///     let names: Box<[String]> = Box::new_slice_with(3, |i| format!("item{}", i));
+    ///     assert_eq!(&*names, ["item0", "item1", "item2"]);
+    /// }
+    /// ```
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxSliceExt;
+    /// use std::panic;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// struct Noisy;
+    ///
+    /// impl Drop for Noisy {
+    ///     fn drop(&mut self) {
+    ///         DROPPED.fetch_add(1, Ordering::SeqCst);
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let result = panic::catch_unwind(|| {
+    ///         Box::<[Noisy]>::new_slice_with(10, |i| if i == 4 { panic!() } else { Noisy })
+    ///     });
+    ///     assert!(result.is_err());
+    ///     // The 4 elements initialized before the panic were dropped.
+    ///     assert_eq!(DROPPED.load(Ordering::SeqCst), 4);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_slice_with<F: FnMut(usize) -> Self::Item>(len: usize, f: F) -> Self;
+
+    /// Fallible [`BoxSliceExt::new_slice_with`]
+    ///
+    /// [`BoxSliceExt::new_slice_with`]: #tymethod.new_slice_with
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the slice overflows. `f` is not called in that case.
+    fn try_new_slice_with<F: FnMut(usize) -> Self::Item>(len: usize, f: F) -> Option<Self>;
+
+    /// Allocates a slice of `len` elements, all clones of `value`.
+    ///
+    /// Each element is written in place. The last one is `value` itself,
+    /// moved instead of cloned, and `value` is dropped if `len` is 0. This is
+    /// equivalent to `vec![value; len].into_boxed_slice()`, without going
+    /// through a `Vec`.
+    ///
+    /// This doesn't actually allocate if `len` is 0 or `Self::Item` is
+    /// zero-sized.
+    ///
+    /// If cloning panics, the elements that were already written are dropped
+    /// and the memory is freed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxSliceExt;
+    ///
+    /// fn main() {
+    ///     let rows: Box<[Vec<u8>]> = BoxSliceExt::new_filled_slice(3, vec![0; 16]);
+    ///     assert_eq!(rows.len(), 3);
+    ///     assert!(rows.iter().all(|row| row == &[0; 16]));
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the slice overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_filled_slice(len: usize, value: Self::Item) -> Self
+    where
+        Self::Item: Clone;
+
+    /// Fallible [`BoxSliceExt::new_filled_slice`]
+    ///
+    /// [`BoxSliceExt::new_filled_slice`]: #tymethod.new_filled_slice
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the slice overflows. `value` is dropped in that case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxSliceExt;
+    ///
+    /// fn main() {
+    ///     let buf: Option<Box<[String]>> = BoxSliceExt::try_new_filled_slice(usize::MAX, String::new());
+    ///     assert!(buf.is_none());
+    /// }
+    /// ```
+    fn try_new_filled_slice(len: usize, value: Self::Item) -> Option<Self>
+    where
+        Self::Item: Clone;
+}
+
+// Fills `builder` with the results of `f`, and returns the boxed slice.
+fn fill<T, F: FnMut(usize) -> T>(mut builder: BoxBuilder<T>, f: F) -> Box<[T]> {
+    let len = builder.capacity();
+    let _ = builder.extend((0..len).map(f));
+    match builder.finish() {
+        Ok(b) => b,
+        Err(_) => unreachable!(),
+    }
+}
+
+// Fills `builder` with clones of `value`, moving `value` itself in the last
+// slot, and returns the boxed slice.
+fn fill_with_value<T: Clone>(mut builder: BoxBuilder<T>, value: T) -> Box<[T]> {
+    let len = builder.capacity();
+    if len > 0 {
+        let _ = builder.extend(iter::repeat(&value).take(len - 1).cloned());
+        let _ = builder.push(value);
+    }
+    match builder.finish() {
+        Ok(b) => b,
+        Err(_) => unreachable!(),
+    }
+}
+
+// Fills `builder` with the elements of `iter`, which must yield exactly as
+// many elements as the builder capacity, and returns the boxed slice.
+#[track_caller]
+fn fill_from_iter<T, I: Iterator<Item = T>>(mut builder: BoxBuilder<T>, mut iter: I) -> Box<[T]> {
+    let len = builder.capacity();
+    let _ = builder.extend(iter.by_ref().take(len));
+    if !builder.is_full() || iter.next().is_some() {
+        panic!("iterator yielded a different number of elements than its length");
+    }
+    match builder.finish() {
+        Ok(b) => b,
+        Err(_) => unreachable!(),
+    }
+}
+
+/// Collects the elements of an iterator of known length into a boxed slice.
+///
+/// Contrary to `iter.collect::<Vec<_>>().into_boxed_slice()`, this allocates
+/// memory for exactly `len()` elements upfront, and writes the elements in
+/// place as they are yielded, without going through a `Vec`.
+///
+/// If the iterator panics, the elements yielded so far are dropped and the
+/// memory is freed.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::boxed_slice_from_iter;
+///
+/// fn main() {
+///     let names = ["alice", "bob"];
+///     let upper: Box<[String]> = boxed_slice_from_iter(names.iter().map(|n| n.to_uppercase()));
+///     assert_eq!(&upper[..], &["ALICE", "BOB"]);
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Panics if the iterator yields a different number of elements than its
+/// `len()`, or if the size of the slice overflows. Calls
+/// [`handle_alloc_error`] if memory couldn't be allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[track_caller]
+pub fn boxed_slice_from_iter<I>(iter: I) -> Box<[I::Item]>
+where
+    I: IntoIterator,
+    I::IntoIter: ExactSizeIterator,
+{
+    let iter = iter.into_iter();
+    fill_from_iter(BoxBuilder::new(iter.len()), iter)
+}
+
+/// Fallible [`boxed_slice_from_iter`]
+///
+/// [`boxed_slice_from_iter`]: fn.boxed_slice_from_iter.html
+///
+/// This returns `None` if memory couldn't be allocated, or if the size of
+/// the slice overflows. The iterator is not consumed in that case.
+///
+/// # Panics
+///
+/// Panics if the iterator yields a different number of elements than its
+/// `len()`.
+#[track_caller]
+pub fn try_boxed_slice_from_iter<I>(iter: I) -> Option<Box<[I::Item]>>
+where
+    I: IntoIterator,
+    I::IntoIter: ExactSizeIterator,
+{
+    let iter = iter.into_iter();
+    Some(fill_from_iter(BoxBuilder::try_new(iter.len())?, iter))
+}
+
+impl<T> BoxSliceExt for Box<[T]> {
+    type Item = T;
+
+    #[inline]
+    #[track_caller]
+    fn new_zeroed_slice(len: usize) -> Self
+    where
+        T: Zero,
+    {
+        unsafe { new_slice_box(len, true) }
+    }
+
+    #[inline]
+    fn try_new_zeroed_slice(len: usize) -> Option<Self>
+    where
+        T: Zero,
+    {
+        unsafe { try_new_slice_box(len, true).ok() }
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn new_zeroed_slice_unchecked(len: usize) -> Self {
+        new_slice_box(len, true)
+    }
+
+    #[inline]
+    unsafe fn try_new_zeroed_slice_unchecked(len: usize) -> Option<Self> {
+        try_new_slice_box(len, true).ok()
+    }
+
+    #[inline]
+    #[track_caller]
+    fn new_slice_with<F: FnMut(usize) -> T>(len: usize, f: F) -> Self {
+        fill(BoxBuilder::new(len), f)
+    }
+
+    #[inline]
+    fn try_new_slice_with<F: FnMut(usize) -> T>(len: usize, f: F) -> Option<Self> {
+        Some(fill(BoxBuilder::try_new(len)?, f))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn new_filled_slice(len: usize, value: T) -> Self
+    where
+        T: Clone,
+    {
+        fill_with_value(BoxBuilder::new(len), value)
+    }
+
+    #[inline]
+    fn try_new_filled_slice(len: usize, value: T) -> Option<Self>
+    where
+        T: Clone,
+    {
+        Some(fill_with_value(BoxBuilder::try_new(len)?, value))
+    }
+}
+
+/// Resizing of `Box<[T]>` in place, with `realloc`
+///
+/// Contrary to going through a `Vec`, the boxed slice never holds more memory
+/// than its length requires.
+pub trait BoxSliceResizeExt {
+    /// Type of the elements of the slice.
+    type Item;
+
+    /// Grows the slice to `new_len` elements, filling the new ones with
+    /// zeroes.
+    ///
+    /// The memory is reallocated, which may or may not move the elements.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::{BoxSliceExt, BoxSliceResizeExt};
+    ///
+    /// fn main() {
+    ///     let mut buf: Box<[u32]> = BoxSliceExt::new_slice_with(4, |i| i as u32 + 1);
+    ///     buf.grow_zeroed(8);
+    ///     assert_eq!(&buf[..], &[1, 2, 3, 4, 0, 0, 0, 0]);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for `Self::Item` types implementing
+    /// the [`Zero`] trait, and not available otherwise. See the definition
+    /// of that trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` is smaller than the current length, or if the
+    /// size of the slice overflows. Calls [`handle_alloc_error`] if memory
+    /// couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn grow_zeroed(&mut self, new_len: usize)
+    where
+        Self::Item: Zero;
+
+    /// Fallible [`BoxSliceResizeExt::grow_zeroed`]
+    ///
+    /// [`BoxSliceResizeExt::grow_zeroed`]: #tymethod.grow_zeroed
+    ///
+    /// This returns an error if memory couldn't be allocated, or if the size
+    /// of the slice overflows, in which case the slice is left untouched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` is smaller than the current length.
+    fn try_grow_zeroed(&mut self, new_len: usize) -> Result<(), AllocError>
+    where
+        Self::Item: Zero;
+
+    /// Shrinks the slice to `new_len` elements, dropping the others.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxSliceResizeExt;
+    ///
+    /// fn main() {
+    ///     let mut names: Box<[String]> = vec!["a".to_string(), "b".to_string()].into_boxed_slice();
+    ///     names.shrink(1);
+    ///     assert_eq!(&names[..], &["a"]);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` is larger than the current length. Calls
+    /// [`handle_alloc_error`] if memory couldn't be reallocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn shrink(&mut self, new_len: usize);
+}
+
+impl<T> BoxSliceResizeExt for Box<[T]> {
+    type Item = T;
+
+    #[track_caller]
+    fn grow_zeroed(&mut self, new_len: usize)
+    where
+        T: Zero,
+    {
+        if let Err(e) = self.try_grow_zeroed(new_len) {
+            e.handle()
+        }
+    }
+
+    #[track_caller]
+    fn try_grow_zeroed(&mut self, new_len: usize) -> Result<(), AllocError>
+    where
+        T: Zero,
+    {
+        let len = self.len();
+        assert!(new_len >= len, "new length is smaller than the current one");
+        unsafe {
+            let raw = try_realloc_slice(self.as_mut_ptr(), len, new_len)?;
+            ptr::write_bytes(raw.add(len), 0, new_len - len);
+            // The old box was consumed by the reallocation.
+            ptr::write(self, Box::from_raw(ptr::slice_from_raw_parts_mut(raw, new_len)));
+        }
+        Ok(())
+    }
+
+    #[track_caller]
+    fn shrink(&mut self, new_len: usize) {
+        let len = self.len();
+        assert!(new_len <= len, "new length is larger than the current one");
+        // Leave an empty slice in place while the elements are dropped, in
+        // case one of their destructors panics.
+        let raw = Box::into_raw(mem::take(self)) as *mut T;
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(raw.add(new_len), len - new_len));
+            match try_realloc_slice(raw, len, new_len) {
+                Ok(raw) => *self = Box::from_raw(ptr::slice_from_raw_parts_mut(raw, new_len)),
+                Err(e) => e.handle(),
+            }
+        }
+    }
+}
@@ -0,0 +1,418 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::alloc::Layout;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use Zero;
+
+// Size of the blocks the arena is divided into, which is also the alignment
+// of its storage.
+const BLOCK_SIZE: usize = 16;
+
+const BITS: usize = 8 * mem::size_of::<usize>();
+
+#[doc(hidden)]
+pub const fn __bitmap_words(size: usize) -> usize {
+    (size / BLOCK_SIZE + BITS - 1) / BITS
+}
+
+// Memory for a `StaticArena`, with its bitmap of used blocks. `W` is
+// `__bitmap_words(N)`.
+#[doc(hidden)]
+#[repr(C, align(16))]
+pub struct __ArenaStorage<const N: usize, const W: usize> {
+    data: UnsafeCell<[MaybeUninit<u8>; N]>,
+    bitmap: [AtomicUsize; W],
+}
+
+unsafe impl<const N: usize, const W: usize> Sync for __ArenaStorage<N, W> {}
+
+impl<const N: usize, const W: usize> __ArenaStorage<N, W> {
+    #[doc(hidden)]
+    #[allow(clippy::declare_interior_mutable_const)]
+    pub const fn new() -> Self {
+        const FREE: AtomicUsize = AtomicUsize::new(0);
+        __ArenaStorage {
+            data: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            bitmap: [FREE; W],
+        }
+    }
+}
+
+/// A fixed-size memory arena in a static, from which [`PoolBox`]es are
+/// allocated, for targets without a heap.
+///
+/// An arena is declared with [`static_arena!`]. It is divided into blocks
+/// of 16 bytes, and each [`PoolBox`] takes as many contiguous blocks as the
+/// size of its value requires, which are returned to the arena when it is
+/// dropped.
+///
+/// The arena is protected by a spin lock, which requires atomic
+/// compare-and-swap. Allocating or dropping a [`PoolBox`] from an interrupt
+/// handler while the interrupted code holds the lock deadlocks.
+///
+/// [`PoolBox`]: struct.PoolBox.html
+/// [`static_arena!`]: macro.static_arena.html
+pub struct StaticArena {
+    base: *mut u8,
+    blocks: usize,
+    bitmap: &'static [AtomicUsize],
+    lock: AtomicBool,
+}
+
+unsafe impl Sync for StaticArena {}
+
+impl StaticArena {
+    #[doc(hidden)]
+    pub const fn __new<const N: usize, const W: usize>(
+        storage: &'static __ArenaStorage<N, W>,
+    ) -> Self {
+        StaticArena {
+            base: storage.data.get() as *mut u8,
+            blocks: N / BLOCK_SIZE,
+            bitmap: &storage.bitmap,
+            lock: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the size of the arena, in bytes.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.blocks * BLOCK_SIZE
+    }
+
+    fn with_lock<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        while self
+            .lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        let result = f();
+        self.lock.store(false, Ordering::Release);
+        result
+    }
+
+    fn is_used(&self, block: usize) -> bool {
+        self.bitmap[block / BITS].load(Ordering::Relaxed) & (1 << (block % BITS)) != 0
+    }
+
+    fn set_used(&self, start: usize, count: usize, used: bool) {
+        for block in start..start + count {
+            let word = &self.bitmap[block / BITS];
+            let bit = 1 << (block % BITS);
+            let value = word.load(Ordering::Relaxed);
+            word.store(if used { value | bit } else { value & !bit }, Ordering::Relaxed);
+        }
+    }
+
+    // Finds `count` contiguous free blocks, the first of which is aligned
+    // to `align`, and marks them as used. Returns the index of the first
+    // block.
+    fn alloc_blocks(&self, count: usize, align: usize) -> Option<usize> {
+        let misalignment = self.base as usize % align;
+        let first = if misalignment == 0 {
+            0
+        } else {
+            (align - misalignment) / BLOCK_SIZE
+        };
+        let step = align / BLOCK_SIZE;
+        self.with_lock(|| {
+            let mut start = first;
+            while start.checked_add(count)? <= self.blocks {
+                match (start..start + count).rev().find(|&b| self.is_used(b)) {
+                    None => {
+                        self.set_used(start, count, true);
+                        return Some(start);
+                    }
+                    // Skip to the first aligned block after the used one.
+                    Some(used) => start += (used - start) / step * step + step,
+                }
+            }
+            None
+        })
+    }
+
+    fn free_blocks(&self, start: usize, count: usize) {
+        self.with_lock(|| self.set_used(start, count, false))
+    }
+
+    // Allocates memory for the given layout. Returns the memory and the
+    // index of its first block.
+    fn alloc(&self, layout: Layout) -> Option<(*mut u8, usize)> {
+        if layout.size() == 0 {
+            // A dangling, but aligned, pointer.
+            return Some((layout.align() as *mut u8, 0));
+        }
+        let count = blocks_for(layout.size());
+        let align = if layout.align() > BLOCK_SIZE {
+            layout.align()
+        } else {
+            BLOCK_SIZE
+        };
+        let start = self.alloc_blocks(count, align)?;
+        Some((unsafe { self.base.add(start * BLOCK_SIZE) }, start))
+    }
+}
+
+impl fmt::Debug for StaticArena {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StaticArena")
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+fn blocks_for(size: usize) -> usize {
+    size / BLOCK_SIZE + (size % BLOCK_SIZE != 0) as usize
+}
+
+/// Declares a [`StaticArena`] of the given size, in bytes, for use with
+/// [`PoolBox`].
+///
+/// [`StaticArena`]: struct.StaticArena.html
+/// [`PoolBox`]: struct.PoolBox.html
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate boxext;
+/// use boxext::PoolBox;
+///
+/// static_arena!(ARENA, 64 * 1024);
+///
+/// fn main() {
+///     let samples: PoolBox<[i16; 4096]> = PoolBox::new_zeroed(&ARENA);
+///     assert_eq!(samples[0], 0);
+/// }
+/// ```
+#[macro_export]
+macro_rules! static_arena {
+    ($vis:vis $name:ident, $size:expr) => {
+        $vis static $name: $crate::StaticArena = {
+            static STORAGE: $crate::__ArenaStorage<
+                { $size },
+                { $crate::__bitmap_words($size) },
+            > = $crate::__ArenaStorage::new();
+            $crate::StaticArena::__new(&STORAGE)
+        };
+    };
+}
+
+/// A value allocated from a [`StaticArena`], which is returned to the arena
+/// when it is dropped.
+///
+/// This works like a `Box`, without requiring a heap, e.g. on
+/// microcontrollers. The constructors panic when the arena doesn't have
+/// enough room left, and the fallible ones return `None`.
+///
+/// [`StaticArena`]: struct.StaticArena.html
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate boxext;
+/// use boxext::PoolBox;
+///
+/// static_arena!(ARENA, 4096);
+///
+/// struct Frame {
+///     len: usize,
+///     data: [u8; 1024],
+/// }
+///
+/// fn main() {
+///     let frames: [PoolBox<Frame>; 3] = [
+///         PoolBox::new_with(&ARENA, || Frame { len: 0, data: [0; 1024] }),
+///         PoolBox::new_with(&ARENA, || Frame { len: 0, data: [0; 1024] }),
+///         PoolBox::new_with(&ARENA, || Frame { len: 0, data: [0; 1024] }),
+///     ];
+///     assert!(PoolBox::try_new(&ARENA, [0u8; 1024]).is_none());
+///     drop(frames);
+///     let frame = PoolBox::try_new(&ARENA, [0u8; 1024]).unwrap();
+///     assert_eq!(frame.len(), 1024);
+/// }
+/// ```
+pub struct PoolBox<T> {
+    ptr: NonNull<T>,
+    arena: &'static StaticArena,
+    start: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T> PoolBox<T> {
+    /// Allocates memory from `arena`, and places `x` into it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `arena` doesn't have enough room left.
+    #[track_caller]
+    pub fn new(arena: &'static StaticArena, x: T) -> Self {
+        PoolBox::new_with(arena, || x)
+    }
+
+    /// Fallible [`PoolBox::new`]
+    ///
+    /// [`PoolBox::new`]: #method.new
+    ///
+    /// This returns `None` if `arena` doesn't have enough room left.
+    pub fn try_new(arena: &'static StaticArena, x: T) -> Option<Self> {
+        PoolBox::try_new_with(arena, || x)
+    }
+
+    /// Allocates memory from `arena`, and places the result of `f` into it.
+    ///
+    /// As with [`BoxExt::new_with`], this is expected to avoid copies when
+    /// building with optimization enabled.
+    ///
+    /// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+    ///
+    /// # Panics
+    ///
+    /// Panics if `arena` doesn't have enough room left.
+    #[track_caller]
+    pub fn new_with<F: FnOnce() -> T>(arena: &'static StaticArena, f: F) -> Self {
+        match PoolBox::try_new_with(arena, f) {
+            Some(b) => b,
+            None => exhausted(arena, Layout::new::<T>()),
+        }
+    }
+
+    /// Fallible [`PoolBox::new_with`]
+    ///
+    /// [`PoolBox::new_with`]: #method.new_with
+    ///
+    /// This returns `None` if `arena` doesn't have enough room left, in
+    /// which case `f` is not called.
+    pub fn try_new_with<F: FnOnce() -> T>(arena: &'static StaticArena, f: F) -> Option<Self> {
+        let uninit = PoolBox::<MaybeUninit<T>>::try_alloc(arena)?;
+        unsafe {
+            ptr::write(uninit.ptr.as_ptr() as *mut T, f());
+            Some(uninit.assume_init())
+        }
+    }
+
+    /// Allocates zeroed memory for a `T` from `arena`.
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for types implementing the [`Zero`]
+    /// trait, and not available otherwise. See the definition of that
+    /// trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `arena` doesn't have enough room left.
+    #[track_caller]
+    pub fn new_zeroed(arena: &'static StaticArena) -> Self
+    where
+        T: Zero,
+    {
+        match PoolBox::try_new_zeroed(arena) {
+            Some(b) => b,
+            None => exhausted(arena, Layout::new::<T>()),
+        }
+    }
+
+    /// Fallible [`PoolBox::new_zeroed`]
+    ///
+    /// [`PoolBox::new_zeroed`]: #method.new_zeroed
+    ///
+    /// This returns `None` if `arena` doesn't have enough room left.
+    pub fn try_new_zeroed(arena: &'static StaticArena) -> Option<Self>
+    where
+        T: Zero,
+    {
+        let uninit = PoolBox::<MaybeUninit<T>>::try_alloc(arena)?;
+        unsafe {
+            // The memory may have been used by a previous `PoolBox`.
+            ptr::write_bytes(uninit.ptr.as_ptr(), 0, 1);
+            Some(uninit.assume_init())
+        }
+    }
+}
+
+impl<T> PoolBox<MaybeUninit<T>> {
+    fn try_alloc(arena: &'static StaticArena) -> Option<Self> {
+        let (raw, start) = arena.alloc(Layout::new::<T>())?;
+        Some(PoolBox {
+            ptr: unsafe { NonNull::new_unchecked(raw as *mut MaybeUninit<T>) },
+            arena,
+            start,
+            marker: PhantomData,
+        })
+    }
+
+    unsafe fn assume_init(self) -> PoolBox<T> {
+        let result = PoolBox {
+            ptr: self.ptr.cast(),
+            arena: self.arena,
+            start: self.start,
+            marker: PhantomData,
+        };
+        mem::forget(self);
+        result
+    }
+}
+
+#[cold]
+#[track_caller]
+fn exhausted(arena: &StaticArena, layout: Layout) -> ! {
+    panic!(
+        "static arena of {} bytes has no room left for {} bytes",
+        arena.capacity(),
+        layout.size()
+    )
+}
+
+impl<T> Deref for PoolBox<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for PoolBox<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for PoolBox<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.ptr.as_ptr()) };
+        let size = mem::size_of::<T>();
+        if size != 0 {
+            self.arena.free_blocks(self.start, blocks_for(size));
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for PoolBox<T> {}
+
+unsafe impl<T: Sync> Sync for PoolBox<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for PoolBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::cmp;
+
+thread_local! {
+    static STATS: Cell<AllocStats> = const {
+        Cell::new(AllocStats {
+            allocations: 0,
+            zeroed_allocations: 0,
+            bytes_requested: 0,
+            failures: 0,
+            largest_request: 0,
+        })
+    };
+}
+
+/// Statistics about the memory the crate requested on a thread.
+///
+/// This covers memory the crate gets from the global allocator, from the
+/// allocator set with `set_boxext_allocator`, or from the operating system,
+/// whether for values handed out as a `Box` or for memory it owns itself.
+/// Zero-sized requests, and requests rejected before reaching the allocator,
+/// e.g. because of a [`budget`], are not counted. Reallocations are counted
+/// as requests for their new size.
+///
+/// [`budget`]: budget/index.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    /// Number of requests.
+    pub allocations: usize,
+    /// Number of requests for zeroed memory.
+    pub zeroed_allocations: usize,
+    /// Total number of bytes requested.
+    pub bytes_requested: u64,
+    /// Number of requests that failed.
+    pub failures: usize,
+    /// Size of the largest request, in bytes.
+    pub largest_request: usize,
+}
+
+/// Returns the allocation statistics of the current thread.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::BoxSliceExt;
+///
+/// fn main() {
+///     boxext::reset_stats();
+///     let buf: Box<[u8]> = BoxSliceExt::new_zeroed_slice(1 << 20);
+///     let stats = boxext::stats();
+///     assert_eq!(stats.allocations, 1);
+///     assert_eq!(stats.zeroed_allocations, 1);
+///     assert_eq!(stats.bytes_requested, buf.len() as u64);
+///     assert_eq!(stats.largest_request, buf.len());
+///     assert_eq!(stats.failures, 0);
+/// }
+/// ```
+pub fn stats() -> AllocStats {
+    STATS.with(Cell::get)
+}
+
+/// Resets the allocation statistics of the current thread.
+pub fn reset_stats() {
+    STATS.with(|s| s.set(AllocStats::default()));
+}
+
+// Records a request for `layout` in the current thread's statistics.
+pub(crate) fn record(layout: Layout, zeroed: bool, succeeded: bool) {
+    STATS.with(|s| {
+        let mut stats = s.get();
+        stats.allocations += 1;
+        if zeroed {
+            stats.zeroed_allocations += 1;
+        }
+        stats.bytes_requested += layout.size() as u64;
+        if !succeeded {
+            stats.failures += 1;
+        }
+        stats.largest_request = cmp::max(stats.largest_request, layout.size());
+        s.set(stats);
+    });
+}
@@ -0,0 +1,317 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::{fmt, mem, ptr, slice, str};
+use std::error::Error;
+use {alloc_slice, dealloc_slice, try_alloc_slice, try_realloc_slice};
+
+/// Error returned by [`BoxStrBuilder::finish`] when the formatted content
+/// didn't fit in the builder.
+///
+/// [`BoxStrBuilder::finish`]: struct.BoxStrBuilder.html#method.finish
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StrCapacityError {
+    capacity: usize,
+    required: usize,
+}
+
+impl StrCapacityError {
+    /// Returns the capacity of the builder.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the length the builder would have needed to hold everything
+    /// that was written to it.
+    ///
+    /// As formatting stops at the first write that doesn't fit, this may be
+    /// lower than the length of the complete formatted content.
+    pub fn required(&self) -> usize {
+        self.required
+    }
+}
+
+impl fmt::Display for StrCapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "string of at least {} bytes doesn't fit in a capacity of {} bytes",
+            self.required, self.capacity
+        )
+    }
+}
+
+impl Error for StrCapacityError {}
+
+/// Builds a boxed string of at most a given length.
+///
+/// The memory for the maximum length is allocated upfront and formatted
+/// content is written directly into it through the `fmt::Write` trait.
+/// Once done, [`finish`] shrinks the allocation to the actual length of the
+/// content.
+///
+/// Writes that don't fit in the builder fail with `fmt::Error`, and
+/// [`finish`] then returns an error indicating how much room was needed.
+///
+/// [`finish`]: #method.finish
+///
+/// # Examples
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::BoxStrBuilder;
+/// use std::fmt::Write;
+///
+/// fn main() {
+///     let mut key = BoxStrBuilder::new(32);
+///     write!(key, "user:{}:session:{}", 42, 7).unwrap();
+///     let key: Box<str> = key.finish().unwrap();
+///     assert_eq!(&*key, "user:42:session:7");
+///
+///     let mut key = BoxStrBuilder::new(8);
+///     assert!(write!(key, "user:{}", 123456).is_err());
+///     let err = key.finish().unwrap_err();
+///     assert_eq!(err.capacity(), 8);
+///     assert_eq!(err.required(), 11);
+/// }
+/// ```
+pub struct BoxStrBuilder {
+    ptr: *mut u8,
+    len: usize,
+    capacity: usize,
+    required: usize,
+}
+
+impl BoxStrBuilder {
+    /// Creates a new builder for a boxed string of at most `capacity` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new(capacity: usize) -> Self {
+        BoxStrBuilder {
+            ptr: unsafe { alloc_slice::<u8>(capacity, false) },
+            len: 0,
+            capacity,
+            required: 0,
+        }
+    }
+
+    /// Fallible [`BoxStrBuilder::new`]
+    ///
+    /// [`BoxStrBuilder::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new(capacity: usize) -> Option<Self> {
+        Some(BoxStrBuilder {
+            ptr: unsafe { try_alloc_slice::<u8>(capacity, false).ok()? },
+            len: 0,
+            capacity,
+            required: 0,
+        })
+    }
+
+    /// Returns the length of the content written so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether nothing was written yet.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the maximum length of the content.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the content written so far.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        unsafe { str::from_utf8_unchecked(slice::from_raw_parts(self.ptr, self.len)) }
+    }
+
+    /// Turns the builder into a boxed string.
+    ///
+    /// The allocation is shrunk to the length of the content. If some
+    /// content didn't fit in the builder, an error is returned instead.
+    #[track_caller]
+    pub fn finish(self) -> Result<Box<str>, StrCapacityError> {
+        if self.required > self.capacity {
+            return Err(StrCapacityError {
+                capacity: self.capacity,
+                required: self.required,
+            });
+        }
+        let (raw, len) = (self.ptr, self.len);
+        let capacity = self.capacity;
+        mem::forget(self);
+        unsafe {
+            let raw = if len != capacity {
+                match try_realloc_slice(raw, capacity, len) {
+                    Ok(raw) => raw,
+                    Err(e) => e.handle(),
+                }
+            } else {
+                raw
+            };
+            let bytes: Box<[u8]> = Box::from_raw(ptr::slice_from_raw_parts_mut(raw, len));
+            Ok(Box::from_raw(Box::into_raw(bytes) as *mut str))
+        }
+    }
+}
+
+impl fmt::Write for BoxStrBuilder {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.required += s.len();
+        if self.required > self.capacity {
+            return Err(fmt::Error);
+        }
+        unsafe {
+            ptr::copy_nonoverlapping(s.as_ptr(), self.ptr.add(self.len), s.len());
+        }
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+unsafe impl Send for BoxStrBuilder {}
+
+unsafe impl Sync for BoxStrBuilder {}
+
+impl Drop for BoxStrBuilder {
+    fn drop(&mut self) {
+        unsafe {
+            dealloc_slice(self.ptr, self.capacity);
+        }
+    }
+}
+
+impl fmt::Debug for BoxStrBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BoxStrBuilder")
+            .field("content", &self.as_str())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+// Counts the bytes written to it.
+struct Measure(usize);
+
+impl fmt::Write for Measure {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+// Formats `args` into a builder created by `new` with the exact length of
+// the formatted content. This formats twice: once to measure the length,
+// and once to write the content. Returns None if either fails, or if the
+// second pass doesn't produce the same length.
+fn format_exact<F>(args: fmt::Arguments, new: F) -> Option<Box<str>>
+where
+    F: FnOnce(usize) -> Option<BoxStrBuilder>,
+{
+    use core::fmt::Write;
+
+    let mut measure = Measure(0);
+    measure.write_fmt(args).ok()?;
+    let mut builder = new(measure.0)?;
+    builder.write_fmt(args).ok()?;
+    if builder.len() != measure.0 {
+        return None;
+    }
+    builder.finish().ok()
+}
+
+/// Formats `args` directly into a boxed string of the exact length of the
+/// formatted content.
+///
+/// Contrary to `format!(...).into_boxed_str()`, this doesn't go through a
+/// `String` that may need to grow, and then shrink. To achieve that, the
+/// arguments are formatted twice: once to measure the length of the
+/// content, and once to write it.
+///
+/// See also the [`boxed_format!`] macro.
+///
+/// [`boxed_format!`]: macro.boxed_format.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::boxed_format;
+///
+/// fn main() {
+///     let name: Box<str> = boxed_format(format_args!("worker-{}", 3));
+///     assert_eq!(&*name, "worker-3");
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Panics if a formatting trait implementation returns an error, as
+/// `format!` does. Calls [`handle_alloc_error`] if memory couldn't be
+/// allocated.
+///
+/// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+#[track_caller]
+pub fn boxed_format(args: fmt::Arguments) -> Box<str> {
+    match format_exact(args, |len| Some(BoxStrBuilder::new(len))) {
+        Some(s) => s,
+        // Let `format!` deal with formatting trait implementations that
+        // fail or are not consistent.
+        None => ::std::fmt::format(args).into_boxed_str(),
+    }
+}
+
+/// Fallible [`boxed_format`]
+///
+/// [`boxed_format`]: fn.boxed_format.html
+///
+/// This returns `None` if memory couldn't be allocated, or if a formatting
+/// trait implementation returns an error or doesn't produce the same output
+/// when called twice.
+pub fn try_boxed_format(args: fmt::Arguments) -> Option<Box<str>> {
+    format_exact(args, BoxStrBuilder::try_new)
+}
+
+/// Creates a `Box<str>` using interpolation of runtime expressions, like
+/// `format!`, but without going through a `String`.
+///
+/// See [`boxed_format`].
+///
+/// [`boxed_format`]: fn.boxed_format.html
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate boxext;
+///
+/// fn main() {
+///     let (user, session) = (42, 7);
+///     let key: Box<str> = boxed_format!("user:{}:session:{}", user, session);
+///     assert_eq!(&*key, "user:42:session:7");
+/// }
+/// ```
+#[macro_export]
+macro_rules! boxed_format {
+    ($($arg:tt)*) => {
+        $crate::boxed_format(format_args!($($arg)*))
+    };
+}
@@ -0,0 +1,401 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers for testing code using this crate.
+
+#[cfg(feature = "verify_in_place")]
+use core::cell::Cell;
+#[cfg(feature = "verify_in_place")]
+use core::panic::Location;
+use core::slice;
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "verify_in_place")]
+use core::{cmp, mem, ptr};
+use std::alloc::{GlobalAlloc, Layout, System};
+#[cfg(feature = "verify_in_place")]
+use std::panic;
+use std::process;
+#[cfg(feature = "verify_in_place")]
+use std::thread;
+
+// Maximum number of live blocks obtained via `alloc_zeroed` that
+// `CountingAlloc` keeps track of.
+const ZEROED_SLOTS: usize = 1024;
+
+/// Numbers of calls to each of the allocator functions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocCounts {
+    /// Number of calls to `alloc`.
+    pub alloc: usize,
+    /// Number of calls to `alloc_zeroed`.
+    pub alloc_zeroed: usize,
+    /// Number of calls to `realloc`.
+    pub realloc: usize,
+    /// Number of calls to `dealloc`.
+    pub dealloc: usize,
+}
+
+/// A `GlobalAlloc` wrapper counting calls to the wrapped allocator.
+///
+/// This is meant to be used as `#[global_allocator]` in tests, to verify
+/// that code keeps obtaining its memory through the expected allocator
+/// functions, e.g. that large zeroed buffers come from `alloc_zeroed`
+/// rather than from `alloc` followed by a memset.
+///
+/// The counts are process-wide, so concurrently running tests may affect
+/// each other's counts.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::BoxExt;
+/// use boxext::testing::CountingAlloc;
+/// use std::alloc::System;
+///
+/// #[global_allocator]
+/// static ALLOC: CountingAlloc<System> = CountingAlloc::new(System);
+///
+/// fn main() {
+///     let before = ALLOC.counts();
+///     let buf: Box<[u8; 65536]> = BoxExt::new_zeroed();
+///     let after = ALLOC.counts();
+///     assert_eq!(after.alloc_zeroed - before.alloc_zeroed, 1);
+///     assert_eq!(after.alloc - before.alloc, 0);
+///     ALLOC.assert_zeroed(&*buf);
+///
+///     let buf = Box::new_with(|| [0u8; 65536]);
+///     assert!(!ALLOC.is_zeroed(&*buf));
+/// }
+/// ```
+pub struct CountingAlloc<A = System> {
+    inner: A,
+    alloc: AtomicUsize,
+    alloc_zeroed: AtomicUsize,
+    realloc: AtomicUsize,
+    dealloc: AtomicUsize,
+    zeroed: [AtomicUsize; ZEROED_SLOTS],
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+impl<A> CountingAlloc<A> {
+    /// Wraps the given allocator.
+    pub const fn new(inner: A) -> Self {
+        CountingAlloc {
+            inner,
+            alloc: AtomicUsize::new(0),
+            alloc_zeroed: AtomicUsize::new(0),
+            realloc: AtomicUsize::new(0),
+            dealloc: AtomicUsize::new(0),
+            zeroed: [EMPTY_SLOT; ZEROED_SLOTS],
+        }
+    }
+
+    /// Returns the numbers of calls to each of the allocator functions so
+    /// far.
+    pub fn counts(&self) -> AllocCounts {
+        AllocCounts {
+            alloc: self.alloc.load(Ordering::SeqCst),
+            alloc_zeroed: self.alloc_zeroed.load(Ordering::SeqCst),
+            realloc: self.realloc.load(Ordering::SeqCst),
+            dealloc: self.dealloc.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Returns whether `ptr` points to the start of a live block that was
+    /// obtained via `alloc_zeroed`.
+    ///
+    /// Only a limited number of live zeroed blocks are tracked, so this may
+    /// return false for some when many of them are alive at the same time.
+    pub fn is_zeroed<T: ?Sized>(&self, ptr: *const T) -> bool {
+        let ptr = ptr as *const u8 as usize;
+        self.zeroed.iter().any(|slot| slot.load(Ordering::SeqCst) == ptr)
+    }
+
+    /// Panics if `ptr` doesn't point to the start of a live block that was
+    /// obtained via `alloc_zeroed`.
+    ///
+    /// See [`is_zeroed`].
+    ///
+    /// [`is_zeroed`]: #method.is_zeroed
+    pub fn assert_zeroed<T: ?Sized>(&self, ptr: *const T) {
+        assert!(
+            self.is_zeroed(ptr),
+            "{:p} was not obtained via alloc_zeroed",
+            ptr as *const u8
+        );
+    }
+
+    fn track_zeroed(&self, ptr: *mut u8) {
+        for slot in self.zeroed.iter() {
+            if slot
+                .compare_exchange(0, ptr as usize, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    fn untrack_zeroed(&self, ptr: *mut u8) {
+        for slot in self.zeroed.iter() {
+            if slot
+                .compare_exchange(ptr as usize, 0, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.alloc.fetch_add(1, Ordering::SeqCst);
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.alloc_zeroed.fetch_add(1, Ordering::SeqCst);
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            self.track_zeroed(ptr);
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.realloc.fetch_add(1, Ordering::SeqCst);
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            self.untrack_zeroed(ptr);
+        }
+        new_ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.dealloc.fetch_add(1, Ordering::SeqCst);
+        self.untrack_zeroed(ptr);
+        self.inner.dealloc(ptr, layout)
+    }
+}
+
+/// A `GlobalAlloc` wrapper verifying that memory returned by `alloc_zeroed`
+/// is actually zeroed.
+///
+/// [`new_zeroed`] and the other zeroing helpers rely on `alloc_zeroed`
+/// returning zeroed memory for their safety. This is meant to be used as
+/// `#[global_allocator]` in debug builds or tests, to catch custom
+/// allocators breaking that contract. When it happens, a message is printed
+/// on stderr and the process is aborted.
+///
+/// Checking every byte of every zeroed allocation defeats the purpose of
+/// obtaining zeroed memory cheaply, so this is not meant to be used in
+/// production.
+///
+/// [`new_zeroed`]: ../trait.BoxExt.html#tymethod.new_zeroed
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::BoxExt;
+/// use boxext::testing::ZeroCheckAlloc;
+/// use std::alloc::System;
+///
+/// #[global_allocator]
+/// static ALLOC: ZeroCheckAlloc<System> = ZeroCheckAlloc::new(System);
+///
+/// fn main() {
+///     let buf: Box<[u64; 4096]> = BoxExt::new_zeroed();
+///     assert!(buf.iter().all(|&x| x == 0));
+/// }
+/// ```
+pub struct ZeroCheckAlloc<A = System> {
+    inner: A,
+}
+
+impl<A> ZeroCheckAlloc<A> {
+    /// Wraps the given allocator.
+    pub const fn new(inner: A) -> Self {
+        ZeroCheckAlloc { inner }
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for ZeroCheckAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            let bytes = slice::from_raw_parts(ptr, layout.size());
+            if let Some(offset) = bytes.iter().position(|&b| b != 0) {
+                // Panicking is not allowed in an allocator.
+                eprintln!(
+                    "alloc_zeroed returned non-zeroed memory at {:p} (offset {} of {} bytes)",
+                    ptr,
+                    offset,
+                    layout.size()
+                );
+                process::abort();
+            }
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.inner.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout)
+    }
+}
+
+// Values smaller than this are not checked: the stack they would be built
+// on can't be told apart from the stack used by ordinary function calls.
+#[cfg(feature = "verify_in_place")]
+const MIN_CHECKED_SIZE: usize = 64 * 1024;
+
+// Depth of the stack probed by `assert_in_place`.
+#[cfg(feature = "verify_in_place")]
+const PROBE_DEPTH: usize = 256 * 1024;
+
+// Stack right below the probing function, left alone for its own use.
+#[cfg(feature = "verify_in_place")]
+const PROBE_GAP: usize = 4096;
+
+// Size of the stack of the thread `assert_in_place` runs `f` on. Only its
+// upper `PROBE_DEPTH` bytes are probed.
+#[cfg(feature = "verify_in_place")]
+const THREAD_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+#[cfg(feature = "verify_in_place")]
+const PROBE_PATTERN: usize = usize::MAX / 0xff * 0xa5;
+
+#[cfg(feature = "verify_in_place")]
+thread_local! {
+    // Size and location of the largest value placed on the heap with
+    // `new_with` or `try_new_with`.
+    static BOXED: Cell<Option<(usize, &'static Location<'static>)>> = const { Cell::new(None) };
+}
+
+// Paints the stack below the caller's frame, which is assumed to grow
+// downwards, or returns how deep it was used since it was painted. This must
+// only be called near the top of a thread spawned with a stack of
+// `THREAD_STACK_SIZE` bytes, so that the probed memory is known to belong to
+// that stack.
+#[cfg(feature = "verify_in_place")]
+#[inline(never)]
+fn stack_probe(paint: bool) -> usize {
+    let marker = 0u8;
+    let top = &marker as *const u8 as usize & !(mem::align_of::<usize>() - 1);
+    let words = (PROBE_DEPTH - PROBE_GAP) / mem::size_of::<usize>();
+    let bottom = (top - PROBE_DEPTH) as *mut usize;
+    for i in 0..words {
+        unsafe {
+            if paint {
+                ptr::write_volatile(bottom.add(i), PROBE_PATTERN);
+            } else if ptr::read_volatile(bottom.add(i)) != PROBE_PATTERN {
+                return PROBE_DEPTH - i * mem::size_of::<usize>();
+            }
+        }
+    }
+    0
+}
+
+// Calls `f` with a frame below the caller's, so that whatever `f` puts on
+// the stack ends up in the probed area.
+#[cfg(feature = "verify_in_place")]
+#[inline(never)]
+fn call<R, F: FnOnce() -> R>(f: F) -> R {
+    f()
+}
+
+// Records that a value of type `T` is placed on the heap, for
+// `assert_in_place`.
+#[cfg(feature = "verify_in_place")]
+#[track_caller]
+pub(crate) fn record_boxed<T>() {
+    let size = mem::size_of::<T>();
+    if size >= MIN_CHECKED_SIZE {
+        let location = Location::caller();
+        BOXED.with(|b| match b.get() {
+            Some((largest, _)) if largest >= size => {}
+            _ => b.set(Some((size, location))),
+        });
+    }
+}
+
+/// Calls `f`, and panics if [`Box::new_with`] or [`Box::try_new_with`] built
+/// a value on the stack before moving it to the heap in the process.
+///
+/// This is meant to catch, in tests, large values that are not built in
+/// place, e.g. after a compiler update. This works by checking how deep `f`
+/// used the stack, up to 256 KiB, so it may also fire when `f` uses that
+/// much stack for other purposes. Values smaller than 64 KiB are not
+/// checked.
+///
+/// `f` is called on a new thread with an 8 MiB stack, whose stack usage is
+/// measured. A panic in `f` is propagated to the caller.
+///
+/// This is only available with the `verify_in_place` feature.
+///
+/// [`Box::new_with`]: ../trait.BoxExt.html#tymethod.new_with
+/// [`Box::try_new_with`]: ../trait.BoxExt.html#tymethod.try_new_with
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::testing::assert_in_place;
+/// use boxext::BoxExt;
+/// use std::panic;
+///
+/// fn main() {
+///     let copied = panic::catch_unwind(|| {
+///         assert_in_place(|| Box::new_with(|| [42u8; 1 << 20]));
+///     });
+///     // Without optimizations, the array is built on the stack first.
+///     if cfg!(debug_assertions) {
+///         assert!(copied.is_err());
+///     }
+/// }
+/// ```
+#[cfg(feature = "verify_in_place")]
+pub fn assert_in_place<R: Send, F: FnOnce() -> R + Send>(f: F) -> R {
+    let outcome = thread::scope(|s| {
+        thread::Builder::new()
+            .stack_size(THREAD_STACK_SIZE)
+            .spawn_scoped(s, || {
+                stack_probe(true);
+                let result = call(f);
+                let depth = stack_probe(false);
+                (result, depth, BOXED.with(Cell::get))
+            })
+            .expect("failed to spawn thread")
+            .join()
+    });
+    let (result, depth, boxed) = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => panic::resume_unwind(e),
+    };
+    if let Some((size, location)) = boxed {
+        if depth >= cmp::min(size, PROBE_DEPTH - PROBE_GAP) {
+            panic!(
+                "{} bytes of stack used while placing a value of {} bytes on the heap at {}",
+                depth, size, location
+            );
+        }
+    }
+    result
+}
@@ -0,0 +1,520 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::alloc::Layout;
+use core::fmt;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+use {new_slice_box, try_new_slice_box, UninitBox};
+
+/// Extensions to `Box<T>` giving access to its storage.
+pub trait BoxStorageExt<T> {
+    /// Drops the boxed value in place, keeping the allocation.
+    ///
+    /// The storage can then be refilled with [`BoxUninitExt::write`] or
+    /// [`BoxUninitExt::write_with`], without going through the allocator.
+    ///
+    /// If dropping the value panics, the memory is freed.
+    ///
+    /// [`BoxUninitExt::write`]: trait.BoxUninitExt.html#tymethod.write
+    /// [`BoxUninitExt::write_with`]: trait.BoxUninitExt.html#tymethod.write_with
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::{BoxStorageExt, BoxUninitExt};
+    ///
+    /// fn main() {
+    ///     let names = Box::new(vec!["a".to_string(), "b".to_string()]);
+    ///     let addr = &*names as *const _ as usize;
+    ///     let storage = names.deinit();
+    ///     let names = BoxUninitExt::write_with(storage, || vec!["c".to_string()]);
+    ///     assert_eq!(&*names as *const _ as usize, addr);
+    ///     assert_eq!(*names, ["c"]);
+    /// }
+    /// ```
+    fn deinit(self) -> Box<MaybeUninit<T>>;
+
+    /// Keeps the allocation without dropping the boxed value.
+    ///
+    /// This is meant for values whose ownership was transferred elsewhere,
+    /// e.g. copied bitwise to C code, which will release their resources.
+    /// As with `mem::forget`, the destructor of the value never runs, but
+    /// the allocation is not leaked, and can be refilled with
+    /// [`BoxUninitExt::write`] or [`BoxUninitExt::write_with`].
+    ///
+    /// [`BoxUninitExt::write`]: trait.BoxUninitExt.html#tymethod.write
+    /// [`BoxUninitExt::write_with`]: trait.BoxUninitExt.html#tymethod.write_with
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::{BoxStorageExt, BoxUninitExt};
+    /// use std::fs::File;
+    /// use std::ptr;
+    ///
+    /// fn main() {
+    ///     let file = Box::new(File::open("Cargo.toml").unwrap());
+    ///     // Stand-in for a transfer to another subsystem that will close
+    ///     // the file.
+    ///     let transferred = unsafe { ptr::read(&*file) };
+    ///     let storage = file.forget_contents();
+    ///     drop(transferred);
+    ///     let file = BoxUninitExt::write(storage, File::open("Cargo.toml").unwrap());
+    ///     assert!(file.metadata().is_ok());
+    /// }
+    /// ```
+    fn forget_contents(self) -> Box<MaybeUninit<T>>;
+
+    /// Drops the boxed value in place, and reuses the allocation for the
+    /// result of `f`, of a possibly different type `U`.
+    ///
+    /// This requires `U` to have the same size and alignment as `T`.
+    /// Otherwise, the box is returned as is, and `f` is not called. As both
+    /// are known at compile time, the check is optimized away.
+    ///
+    /// If dropping the value or `f` panics, the memory is freed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::BoxStorageExt;
+    ///
+    /// struct Request {
+    ///     id: u64,
+    ///     body: Vec<u8>,
+    /// }
+    ///
+    /// struct Response {
+    ///     id: u64,
+    ///     status: Vec<u8>,
+    /// }
+    ///
+    /// fn main() {
+    ///     let mut req = Box::new(Request { id: 0, body: Vec::new() });
+    ///     for id in 1..4 {
+    ///         let resp = req
+    ///             .recycle_with(|| Response { id, status: b"ok".to_vec() })
+    ///             .ok()
+    ///             .unwrap();
+    ///         assert_eq!(resp.id, id);
+    ///         req = resp
+    ///             .recycle_with(|| Request { id: id + 1, body: Vec::new() })
+    ///             .ok()
+    ///             .unwrap();
+    ///     }
+    ///     assert_eq!(req.id, 4);
+    ///
+    ///     let small = Box::new(1u8);
+    ///     assert!(small.recycle_with(|| 1u64).is_err());
+    /// }
+    /// ```
+    fn recycle_with<U, F: FnOnce() -> U>(self, f: F) -> Result<Box<U>, Self>
+    where
+        Self: Sized;
+
+    /// Moves the boxed value out, keeping the allocation as a [`RawAlloc`],
+    /// to be refilled later.
+    ///
+    /// [`RawAlloc`]: struct.RawAlloc.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::{BoxStorageExt, RawAlloc};
+    ///
+    /// struct Pool {
+    ///     free: Vec<RawAlloc<[u8; 4096]>>,
+    /// }
+    ///
+    /// impl Pool {
+    ///     fn get(&mut self) -> Box<[u8; 4096]> {
+    ///         let storage = self.free.pop().unwrap_or_else(RawAlloc::new);
+    ///         storage.write_with(|| [0; 4096])
+    ///     }
+    ///
+    ///     fn put(&mut self, buf: Box<[u8; 4096]>) -> [u8; 4096] {
+    ///         let (value, storage) = buf.take_value();
+    ///         self.free.push(storage);
+    ///         value
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let mut pool = Pool { free: Vec::new() };
+    ///     let mut buf = pool.get();
+    ///     let addr = &*buf as *const _ as usize;
+    ///     buf[0] = 1;
+    ///     assert_eq!(pool.put(buf)[0], 1);
+    ///     let buf = pool.get();
+    ///     assert_eq!(&*buf as *const _ as usize, addr);
+    ///     assert_eq!(buf[0], 0);
+    /// }
+    /// ```
+    fn take_value(self) -> (T, RawAlloc<T>)
+    where
+        Self: Sized;
+}
+
+impl<T> BoxStorageExt<T> for Box<T> {
+    fn deinit(self) -> Box<MaybeUninit<T>> {
+        unsafe {
+            let raw = Box::into_raw(self);
+            // Take ownership of the memory first, so that it is freed if
+            // dropping the value panics.
+            let storage = Box::from_raw(raw as *mut MaybeUninit<T>);
+            ptr::drop_in_place(raw);
+            storage
+        }
+    }
+
+    #[inline]
+    fn forget_contents(self) -> Box<MaybeUninit<T>> {
+        unsafe { Box::from_raw(Box::into_raw(self) as *mut MaybeUninit<T>) }
+    }
+
+    #[inline]
+    fn recycle_with<U, F: FnOnce() -> U>(self, f: F) -> Result<Box<U>, Self> {
+        if Layout::new::<T>() != Layout::new::<U>() {
+            return Err(self);
+        }
+        let storage = self.deinit();
+        // The layouts are the same, so the memory can be deallocated as a
+        // `U`.
+        let storage = unsafe { Box::from_raw(Box::into_raw(storage) as *mut MaybeUninit<U>) };
+        Ok(BoxUninitExt::write_with(storage, f))
+    }
+
+    #[inline]
+    fn take_value(self) -> (T, RawAlloc<T>) {
+        let storage = self.forget_contents();
+        let value = unsafe { ptr::read(storage.as_ptr()) };
+        (value, RawAlloc(storage))
+    }
+}
+
+/// Heap allocation for a `T`, without a value.
+///
+/// This is an opaque handle around a `Box<MaybeUninit<T>>`, obtained from
+/// [`BoxStorageExt::take_value`] or [`RawAlloc::new`], and refilled into a
+/// `Box<T>` with [`RawAlloc::write`] or [`RawAlloc::write_with`]. The memory
+/// is freed if it is dropped.
+///
+/// [`BoxStorageExt::take_value`]: trait.BoxStorageExt.html#tymethod.take_value
+/// [`RawAlloc::new`]: #method.new
+/// [`RawAlloc::write`]: #method.write
+/// [`RawAlloc::write_with`]: #method.write_with
+pub struct RawAlloc<T>(Box<MaybeUninit<T>>);
+
+impl<T> RawAlloc<T> {
+    /// Allocates memory on the heap.
+    ///
+    /// This doesn't actually allocate if `T` is zero-sized.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[inline]
+    #[track_caller]
+    pub fn new() -> Self {
+        RawAlloc(BoxUninitExt::new_uninit())
+    }
+
+    /// Fallible [`RawAlloc::new`]
+    ///
+    /// [`RawAlloc::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    #[inline]
+    pub fn try_new() -> Option<Self> {
+        BoxUninitExt::try_new_uninit().map(RawAlloc)
+    }
+
+    /// Writes `value` into the allocation, and converts to `Box<T>`.
+    #[inline]
+    pub fn write(self, value: T) -> Box<T> {
+        BoxUninitExt::write(self.0, value)
+    }
+
+    /// Writes the result of `f` into the allocation, and converts to
+    /// `Box<T>`.
+    ///
+    /// See [`BoxUninitExt::write_with`].
+    ///
+    /// [`BoxUninitExt::write_with`]: trait.BoxUninitExt.html#tymethod.write_with
+    #[inline]
+    pub fn write_with<F: FnOnce() -> T>(self, f: F) -> Box<T> {
+        BoxUninitExt::write_with(self.0, f)
+    }
+
+    /// Returns the underlying `Box<MaybeUninit<T>>`.
+    #[inline]
+    pub fn into_uninit(self) -> Box<MaybeUninit<T>> {
+        self.0
+    }
+}
+
+impl<T> Default for RawAlloc<T> {
+    #[track_caller]
+    fn default() -> Self {
+        RawAlloc::new()
+    }
+}
+
+impl<T> fmt::Debug for RawAlloc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("RawAlloc").field(&self.0.as_ptr()).finish()
+    }
+}
+
+/// Extensions to `Box<MaybeUninit<T>>`.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::BoxUninitExt;
+/// use std::mem::MaybeUninit;
+///
+/// struct Packet {
+///     header: [u8; 16],
+///     payload: [u8; 1024],
+/// }
+///
+/// fn main() {
+///     let mut packet: Box<MaybeUninit<Packet>> = BoxUninitExt::new_uninit();
+///     let raw = packet.as_mut_ptr();
+///     unsafe {
+///         (*raw).header = [1; 16];
+///         (*raw).payload = [2; 1024];
+///     }
+///     let packet = unsafe { packet.assume_init_box() };
+///     assert_eq!(packet.header[0], 1);
+/// }
+/// ```
+pub trait BoxUninitExt<T>: Sized {
+    /// Allocates uninitialized memory on the heap.
+    ///
+    /// This doesn't actually allocate if `T` is zero-sized.
+    ///
+    /// Note that recent versions of Rust have an inherent `Box::new_uninit`
+    /// method with the same semantics.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_uninit() -> Self;
+
+    /// Fallible [`BoxUninitExt::new_uninit`]
+    ///
+    /// [`BoxUninitExt::new_uninit`]: #tymethod.new_uninit
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    fn try_new_uninit() -> Option<Self>;
+
+    /// Converts to `Box<T>`.
+    ///
+    /// # Safety
+    ///
+    /// The value must have been fully initialized.
+    unsafe fn assume_init_box(self) -> Box<T>;
+
+    /// Writes `value` into the storage, and converts to `Box<T>`.
+    ///
+    /// Note that recent versions of Rust have an inherent `Box::write` method
+    /// with the same semantics, which takes precedence over this one when
+    /// called with the method syntax.
+    fn write(self, value: T) -> Box<T>;
+
+    /// Writes the result of `f` into the storage, and converts to `Box<T>`.
+    ///
+    /// As with [`BoxExt::new_with`], this is expected to avoid copies when
+    /// building with optimization enabled.
+    ///
+    /// If `f` panics, the memory is freed.
+    ///
+    /// [`BoxExt::new_with`]: trait.BoxExt.html#tymethod.new_with
+    fn write_with<F: FnOnce() -> T>(self, f: F) -> Box<T>;
+}
+
+impl<T> BoxUninitExt<T> for Box<MaybeUninit<T>> {
+    #[inline]
+    #[track_caller]
+    fn new_uninit() -> Self {
+        // Uninitialized memory is a valid `MaybeUninit<T>`.
+        unsafe { UninitBox::new(false).assume_init() }
+    }
+
+    #[inline]
+    fn try_new_uninit() -> Option<Self> {
+        unsafe { Some(UninitBox::try_new(false).ok()?.assume_init()) }
+    }
+
+    #[inline]
+    unsafe fn assume_init_box(self) -> Box<T> {
+        Box::from_raw(Box::into_raw(self) as *mut T)
+    }
+
+    #[inline]
+    fn write(self, value: T) -> Box<T> {
+        unsafe {
+            let raw = Box::into_raw(self) as *mut T;
+            ptr::write(raw, value);
+            Box::from_raw(raw)
+        }
+    }
+
+    #[inline]
+    fn write_with<F: FnOnce() -> T>(mut self, f: F) -> Box<T> {
+        unsafe {
+            ptr::write(self.as_mut_ptr(), f());
+            Box::from_raw(Box::into_raw(self) as *mut T)
+        }
+    }
+}
+
+/// Extensions to `Box<[MaybeUninit<T>]>`.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::BoxUninitSliceExt;
+/// use std::mem::MaybeUninit;
+///
+/// // Decodes run-length encoded bytes, given as (count, byte) pairs.
+/// fn decode(encoded: &[(usize, u8)]) -> Box<[u8]> {
+///     let len = encoded.iter().map(|&(count, _)| count).sum();
+///     let mut buf: Box<[MaybeUninit<u8>]> = BoxUninitSliceExt::new_uninit_slice(len);
+///     let mut pos = 0;
+///     for &(count, byte) in encoded {
+///         for b in &mut buf[pos..pos + count] {
+///             *b = MaybeUninit::new(byte);
+///         }
+///         pos += count;
+///     }
+///     // All the bytes were written.
+///     unsafe { buf.assume_init_slice() }
+/// }
+///
+/// fn main() {
+///     assert_eq!(&*decode(&[(2, b'a'), (3, b'b')]), b"aabbb");
+///
+///     let squares: Box<[MaybeUninit<u64>]> = BoxUninitSliceExt::new_uninit_slice(8);
+///     let squares = squares.init_with(|i| (i * i) as u64);
+///     assert_eq!(squares[7], 49);
+/// }
+/// ```
+pub trait BoxUninitSliceExt<T>: Sized {
+    /// Allocates uninitialized memory on the heap for `len` values.
+    ///
+    /// This doesn't actually allocate if `T` is zero-sized or `len` is 0.
+    ///
+    /// Note that recent versions of Rust have an inherent
+    /// `Box::new_uninit_slice` function with the same semantics, which takes
+    /// precedence over this one when called with the `Box::` syntax.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_uninit_slice(len: usize) -> Self;
+
+    /// Fallible [`BoxUninitSliceExt::new_uninit_slice`]
+    ///
+    /// [`BoxUninitSliceExt::new_uninit_slice`]: #tymethod.new_uninit_slice
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    fn try_new_uninit_slice(len: usize) -> Option<Self>;
+
+    /// Converts to `Box<[T]>`.
+    ///
+    /// # Safety
+    ///
+    /// All the values must have been initialized.
+    unsafe fn assume_init_slice(self) -> Box<[T]>;
+
+    /// Writes the result of `f` for each index into the storage, and
+    /// converts to `Box<[T]>`.
+    ///
+    /// If `f` panics, the values written so far are dropped, and the memory
+    /// is freed.
+    fn init_with<F: FnMut(usize) -> T>(self, f: F) -> Box<[T]>;
+
+    /// Writes clones of the values in `src` into the storage, and converts
+    /// to `Box<[T]>`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` doesn't have the same length as the storage.
+    fn write_slice(self, src: &[T]) -> Box<[T]>
+    where
+        T: Clone;
+}
+
+// Drops the values initialized so far if initializing the next one panics.
+struct InitGuard<T> {
+    start: *mut T,
+    len: usize,
+}
+
+impl<T> Drop for InitGuard<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.start, self.len)) }
+    }
+}
+
+impl<T> BoxUninitSliceExt<T> for Box<[MaybeUninit<T>]> {
+    #[inline]
+    #[track_caller]
+    fn new_uninit_slice(len: usize) -> Self {
+        // Uninitialized memory is a valid `MaybeUninit<T>`.
+        unsafe { new_slice_box(len, false) }
+    }
+
+    #[inline]
+    fn try_new_uninit_slice(len: usize) -> Option<Self> {
+        unsafe { try_new_slice_box(len, false).ok() }
+    }
+
+    #[inline]
+    unsafe fn assume_init_slice(self) -> Box<[T]> {
+        Box::from_raw(Box::into_raw(self) as *mut [T])
+    }
+
+    fn init_with<F: FnMut(usize) -> T>(mut self, mut f: F) -> Box<[T]> {
+        let mut guard = InitGuard {
+            start: self.as_mut_ptr() as *mut T,
+            len: 0,
+        };
+        for i in 0..self.len() {
+            unsafe { ptr::write(guard.start.add(i), f(i)) };
+            guard.len += 1;
+        }
+        mem::forget(guard);
+        unsafe { self.assume_init_slice() }
+    }
+
+    #[track_caller]
+    fn write_slice(self, src: &[T]) -> Box<[T]>
+    where
+        T: Clone,
+    {
+        assert_eq!(src.len(), self.len(), "source slice length doesn't match");
+        self.init_with(|i| src[i].clone())
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::{ptr, slice};
+use {try_new_slice_box, AnyAllocError, BoxSliceExt, Zero};
+
+/// Extensions to the `Vec` type
+pub trait VecExt<T> {
+    /// Fills the spare capacity of the vector with zeroes, and returns it.
+    ///
+    /// This allows to hand the memory past the length of the vector to
+    /// functions writing into it, e.g. C functions, without going through
+    /// `resize(capacity, 0)`. After they wrote into it, `set_len` can be
+    /// used to grow the vector. The length of the vector is left unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::VecExt;
+    ///
+    /// // Stand-in for a C function writing into a buffer.
+    /// fn fill(buf: &mut [u8]) -> usize {
+    ///     buf[..5].copy_from_slice(b"hello");
+    ///     5
+    /// }
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::with_capacity(16);
+    ///     let spare = buf.spare_capacity_zeroed();
+    ///     assert!(spare.iter().all(|&b| b == 0));
+    ///     let written = fill(spare);
+    ///     unsafe { buf.set_len(written) };
+    ///     assert_eq!(buf, b"hello");
+    /// }
+    /// ```
+    fn spare_capacity_zeroed(&mut self) -> &mut [T]
+    where
+        T: Zero;
+
+    /// Creates a vector of `len` zeroed elements, with a capacity of `len`.
+    ///
+    /// This gets zeroed memory directly from the allocator, as
+    /// [`BoxSliceExt::new_zeroed_slice`] does.
+    ///
+    /// [`BoxSliceExt::new_zeroed_slice`]: trait.BoxSliceExt.html#tymethod.new_zeroed_slice
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::VecExt;
+    ///
+    /// fn main() {
+    ///     let mut samples: Vec<f32> = VecExt::with_capacity_zeroed(48000);
+    ///     assert_eq!(samples.len(), 48000);
+    ///     assert!(samples.iter().all(|&s| s == 0.0));
+    ///     samples.push(1.0);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for `T` types implementing the
+    /// [`Zero`] trait, and not available otherwise. See the definition of
+    /// that trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the vector overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn with_capacity_zeroed(len: usize) -> Self
+    where
+        Self: Sized,
+        T: Zero;
+
+    /// Fallible [`VecExt::with_capacity_zeroed`]
+    ///
+    /// [`VecExt::with_capacity_zeroed`]: #tymethod.with_capacity_zeroed
+    ///
+    /// This returns `None` if memory couldn't be allocated, or if the size
+    /// of the vector overflows.
+    fn try_with_capacity_zeroed(len: usize) -> Option<Self>
+    where
+        Self: Sized,
+        T: Zero;
+
+    /// Resizes the vector to `new_len` elements, filling new elements with
+    /// zeroes.
+    ///
+    /// When the vector needs to grow to more than twice its length, zeroed
+    /// memory is obtained directly from the allocator, and the existing
+    /// elements are copied over, instead of reallocating and writing zeroes.
+    /// Otherwise, this is equivalent to `resize(new_len, 0)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate boxext;
+    /// use boxext::VecExt;
+    ///
+    /// fn main() {
+    ///     let mut buf = vec![1u8, 2, 3];
+    ///     buf.resize_zeroed(1 << 20);
+    ///     assert_eq!(&buf[..4], &[1, 2, 3, 0]);
+    ///     assert!(buf[3..].iter().all(|&b| b == 0));
+    ///     buf.resize_zeroed(2);
+    ///     assert_eq!(buf, [1, 2]);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This method is only assumed safe for `T` types implementing the
+    /// [`Zero`] trait, and not available otherwise. See the definition of
+    /// that trait.
+    ///
+    /// [`Zero`]: trait.Zero.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the vector overflows. Calls
+    /// [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn resize_zeroed(&mut self, new_len: usize)
+    where
+        T: Zero;
+
+    /// Fallible [`VecExt::resize_zeroed`]
+    ///
+    /// [`VecExt::resize_zeroed`]: #tymethod.resize_zeroed
+    ///
+    /// This returns an error if memory couldn't be allocated, or if the size
+    /// of the vector overflows, in which case the vector is left untouched.
+    fn try_resize_zeroed(&mut self, new_len: usize) -> Result<(), AnyAllocError>
+    where
+        T: Zero;
+}
+
+// Replaces the contents of `v` with the zeroed vector `new`, after moving
+// the elements of `v` at its beginning.
+fn replace_with_zeroed<T: Zero>(v: &mut Vec<T>, mut new: Vec<T>) {
+    unsafe {
+        // The zeroed elements being overwritten don't need to be dropped.
+        ptr::copy_nonoverlapping(v.as_ptr(), new.as_mut_ptr(), v.len());
+        v.set_len(0);
+    }
+    *v = new;
+}
+
+impl<T> VecExt<T> for Vec<T> {
+    fn spare_capacity_zeroed(&mut self) -> &mut [T]
+    where
+        T: Zero,
+    {
+        let len = self.len();
+        let spare = self.capacity() - len;
+        unsafe {
+            let raw = self.as_mut_ptr().add(len);
+            ptr::write_bytes(raw, 0, spare);
+            slice::from_raw_parts_mut(raw, spare)
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn with_capacity_zeroed(len: usize) -> Self
+    where
+        T: Zero,
+    {
+        let b: Box<[T]> = BoxSliceExt::new_zeroed_slice(len);
+        b.into_vec()
+    }
+
+    #[inline]
+    fn try_with_capacity_zeroed(len: usize) -> Option<Self>
+    where
+        T: Zero,
+    {
+        let b: Box<[T]> = BoxSliceExt::try_new_zeroed_slice(len)?;
+        Some(b.into_vec())
+    }
+
+    #[track_caller]
+    fn resize_zeroed(&mut self, new_len: usize)
+    where
+        T: Zero,
+    {
+        let len = self.len();
+        if new_len <= len {
+            self.truncate(new_len);
+        } else if new_len > self.capacity() && new_len - len > len {
+            replace_with_zeroed(self, VecExt::with_capacity_zeroed(new_len));
+        } else {
+            self.reserve(new_len - len);
+            unsafe {
+                ptr::write_bytes(self.as_mut_ptr().add(len), 0, new_len - len);
+                self.set_len(new_len);
+            }
+        }
+    }
+
+    fn try_resize_zeroed(&mut self, new_len: usize) -> Result<(), AnyAllocError>
+    where
+        T: Zero,
+    {
+        let len = self.len();
+        if new_len <= len {
+            self.truncate(new_len);
+        } else if new_len > self.capacity() && new_len - len > len {
+            let new = unsafe { try_new_slice_box::<T>(new_len, true)? };
+            replace_with_zeroed(self, new.into_vec());
+        } else {
+            self.try_reserve(new_len - len)?;
+            unsafe {
+                ptr::write_bytes(self.as_mut_ptr().add(len), 0, new_len - len);
+                self.set_len(new_len);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+use {AlignedBox, AlignedBytes, SecureArc, SecureBox, SecureRc, Zero, ZeroOut};
+
+impl Zeroize for AlignedBytes {
+    fn zeroize(&mut self) {
+        (**self).zeroize();
+    }
+}
+
+impl<T: Zeroize> Zeroize for AlignedBox<T> {
+    fn zeroize(&mut self) {
+        (**self).zeroize();
+    }
+}
+
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// extern crate zeroize;
+/// use boxext::SecureBox;
+/// use zeroize::Zeroize;
+///
+/// fn main() {
+///     let mut key = SecureBox::new([0x42u8; 32]);
+///     key.zeroize();
+///     assert_eq!(*key, [0; 32]);
+/// }
+/// ```
+impl<T: Zeroize> Zeroize for SecureBox<T> {
+    fn zeroize(&mut self) {
+        (**self).zeroize();
+    }
+}
+
+impl<T> ZeroizeOnDrop for SecureBox<T> {}
+
+// `SecureRc` and `SecureArc` don't implement `Zeroize`, as the value may be
+// shared, but it is wiped when the last reference is dropped.
+impl<T> ZeroizeOnDrop for SecureRc<T> {}
+
+impl<T> ZeroizeOnDrop for SecureArc<T> {}
+
+/// Drops the boxed value and overwrites its memory with zeroes, leaving a
+/// zeroed value in the box.
+///
+/// Contrary to `Zeroize`, this works for any type implementing [`Zero`],
+/// since, for those, zeroed memory is a valid value. The writes are not
+/// optimized away, even if the box is dropped right after. If dropping the
+/// value panics, the memory is zeroed nonetheless.
+///
+/// This is the same as [`ZeroOut::zero_out_volatile`].
+///
+/// [`Zero`]: trait.Zero.html
+/// [`ZeroOut::zero_out_volatile`]: trait.ZeroOut.html#tymethod.zero_out_volatile
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{zeroize_box, BoxExt};
+///
+/// fn main() {
+///     let mut key = Box::new_with(|| [0x42u8; 32]);
+///     zeroize_box(&mut key);
+///     assert_eq!(*key, [0; 32]);
+/// }
+/// ```
+pub fn zeroize_box<T: Zero>(b: &mut Box<T>) {
+    b.zero_out_volatile();
+}
+
+/// Drops the values in the boxed slice and overwrites their memory with
+/// zeroes, leaving zeroed values in the slice.
+///
+/// See [`zeroize_box`].
+///
+/// [`zeroize_box`]: fn.zeroize_box.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::zeroize_boxed_slice;
+///
+/// fn main() {
+///     let mut buf = vec![0x42u64; 64].into_boxed_slice();
+///     zeroize_boxed_slice(&mut buf);
+///     assert!(buf.iter().all(|&x| x == 0));
+/// }
+/// ```
+pub fn zeroize_boxed_slice<T: Zero>(b: &mut Box<[T]>) {
+    b.zero_out_volatile();
+}
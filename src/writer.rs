@@ -0,0 +1,217 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::{fmt, mem, ptr, slice, str};
+use std::io::{self, ErrorKind};
+use {alloc_slice, dealloc_slice, try_alloc_slice, try_realloc_slice};
+
+/// A writer of bounded capacity into a boxed byte buffer.
+///
+/// This implements both `io::Write` and `fmt::Write`, writing directly into
+/// the buffer. Writes that don't fit in the remaining capacity fail without
+/// writing anything: `io::Write` returns an error of kind `WriteZero`, and
+/// `fmt::Write` returns `fmt::Error`.
+///
+/// Once done, the written bytes can be turned into a boxed slice or string,
+/// with the allocation shrunk to their length.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::BoxWriter;
+/// use std::io::Write;
+///
+/// fn main() {
+///     let mut msg = BoxWriter::new(64);
+///     msg.write_all(b"HELLO ").unwrap();
+///     write!(msg, "{} {}", 1, 2).unwrap();
+///     assert!(msg.write_all(&[0; 64]).is_err());
+///     assert_eq!(msg.as_bytes(), b"HELLO 1 2");
+///     let msg: Box<[u8]> = msg.into_boxed_slice();
+///     assert_eq!(&*msg, b"HELLO 1 2");
+///
+///     let mut line = BoxWriter::new(16);
+///     line.write_all(b"OK\r\n").unwrap();
+///     assert_eq!(&*line.into_boxed_str().unwrap(), "OK\r\n");
+/// }
+/// ```
+pub struct BoxWriter {
+    ptr: *mut u8,
+    len: usize,
+    capacity: usize,
+}
+
+impl BoxWriter {
+    /// Creates a new writer with room for `capacity` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn new(capacity: usize) -> Self {
+        BoxWriter {
+            ptr: unsafe { alloc_slice::<u8>(capacity, false) },
+            len: 0,
+            capacity,
+        }
+    }
+
+    /// Fallible [`BoxWriter::new`]
+    ///
+    /// [`BoxWriter::new`]: #method.new
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    pub fn try_new(capacity: usize) -> Option<Self> {
+        Some(BoxWriter {
+            ptr: unsafe { try_alloc_slice::<u8>(capacity, false).ok()? },
+            len: 0,
+            capacity,
+        })
+    }
+
+    /// Creates a new writer over an existing buffer.
+    ///
+    /// The content of the buffer is overwritten from the start.
+    pub fn from_boxed_slice(buf: Box<[u8]>) -> Self {
+        let capacity = buf.len();
+        BoxWriter {
+            ptr: Box::into_raw(buf) as *mut u8,
+            len: 0,
+            capacity,
+        }
+    }
+
+    /// Returns the number of bytes written so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether nothing was written yet.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the maximum number of bytes the writer can hold.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the bytes written so far.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    // Appends `buf` if it fits, returning whether it did.
+    fn append(&mut self, buf: &[u8]) -> bool {
+        if buf.len() > self.capacity - self.len {
+            return false;
+        }
+        unsafe {
+            ptr::copy_nonoverlapping(buf.as_ptr(), self.ptr.add(self.len), buf.len());
+        }
+        self.len += buf.len();
+        true
+    }
+
+    /// Turns the writer into a boxed slice of the bytes written.
+    ///
+    /// The allocation is shrunk to the number of bytes written.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be reallocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn into_boxed_slice(self) -> Box<[u8]> {
+        let (raw, len, capacity) = (self.ptr, self.len, self.capacity);
+        mem::forget(self);
+        unsafe {
+            let raw = if len != capacity {
+                match try_realloc_slice(raw, capacity, len) {
+                    Ok(raw) => raw,
+                    Err(e) => e.handle(),
+                }
+            } else {
+                raw
+            };
+            Box::from_raw(ptr::slice_from_raw_parts_mut(raw, len))
+        }
+    }
+
+    /// Turns the writer into a boxed string of the bytes written.
+    ///
+    /// The allocation is shrunk to the number of bytes written. If they are
+    /// not valid UTF-8, the writer is given back.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be reallocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    #[track_caller]
+    pub fn into_boxed_str(self) -> Result<Box<str>, Self> {
+        if str::from_utf8(self.as_bytes()).is_err() {
+            return Err(self);
+        }
+        let bytes = self.into_boxed_slice();
+        Ok(unsafe { Box::from_raw(Box::into_raw(bytes) as *mut str) })
+    }
+}
+
+impl io::Write for BoxWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.append(buf) {
+            Ok(buf.len())
+        } else {
+            Err(io::Error::new(ErrorKind::WriteZero, "buffer capacity exceeded"))
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Write for BoxWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.append(s.as_bytes()) {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
+unsafe impl Send for BoxWriter {}
+
+unsafe impl Sync for BoxWriter {}
+
+impl Drop for BoxWriter {
+    fn drop(&mut self) {
+        unsafe {
+            dealloc_slice(self.ptr, self.capacity);
+        }
+    }
+}
+
+impl fmt::Debug for BoxWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BoxWriter")
+            .field("len", &self.len)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
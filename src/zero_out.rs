@@ -0,0 +1,91 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use core::{mem, ptr};
+use heap::volatile_fill;
+use Zero;
+
+/// Extensions to reset boxed values to zero, keeping their allocation.
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// use boxext::{BoxExt, ZeroOut};
+///
+/// fn main() {
+///     let mut scratch: Box<[u8; 65536]> = BoxExt::new_zeroed();
+///     for request in 0..3u8 {
+///         scratch[..4].copy_from_slice(&[request; 4]);
+///         // ... handle the request ...
+///         scratch.zero_out();
+///         assert!(scratch.iter().all(|&b| b == 0));
+///     }
+/// }
+/// ```
+pub trait ZeroOut {
+    /// Drops the boxed value, and overwrites its memory with zeroes, leaving
+    /// a zeroed value in the box.
+    ///
+    /// If dropping the value panics, the memory is zeroed nonetheless.
+    fn zero_out(&mut self);
+
+    /// Same as [`zero_out`], but the writes are not optimized away, even if
+    /// the box is dropped right after.
+    ///
+    /// [`zero_out`]: #tymethod.zero_out
+    fn zero_out_volatile(&mut self);
+}
+
+// Overwrites `len` values of type `T` at `raw` with zeroes when dropped, so
+// that the memory is zeroed even if dropping the previous values panics.
+struct ZeroOnDrop<T: Zero> {
+    raw: *mut T,
+    len: usize,
+    volatile: bool,
+}
+
+impl<T: Zero> Drop for ZeroOnDrop<T> {
+    fn drop(&mut self) {
+        unsafe {
+            if self.volatile {
+                volatile_fill(self.raw as *mut u8, self.len * mem::size_of::<T>(), 0);
+            } else {
+                ptr::write_bytes(self.raw, 0, self.len);
+            }
+        }
+    }
+}
+
+// Drops the `len` values at `raw`, and zeroes their memory.
+unsafe fn zero_out<T: Zero>(raw: *mut T, len: usize, volatile: bool) {
+    let _zero = ZeroOnDrop { raw, len, volatile };
+    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(raw, len));
+}
+
+impl<T: Zero> ZeroOut for Box<T> {
+    fn zero_out(&mut self) {
+        unsafe { zero_out::<T>(&mut **self, 1, false) }
+    }
+
+    fn zero_out_volatile(&mut self) {
+        unsafe { zero_out::<T>(&mut **self, 1, true) }
+    }
+}
+
+impl<T: Zero> ZeroOut for Box<[T]> {
+    fn zero_out(&mut self) {
+        unsafe { zero_out(self.as_mut_ptr(), self.len(), false) }
+    }
+
+    fn zero_out_volatile(&mut self) {
+        unsafe { zero_out(self.as_mut_ptr(), self.len(), true) }
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright 2018 Mike Hommey
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use bytemuck::Zeroable;
+use UninitBox;
+
+/// Extensions to the `Box` type for types implementing `Zeroable` from the
+/// `bytemuck` crate.
+///
+/// These are equivalent to [`BoxExt::new_zeroed`] and
+/// [`BoxExt::try_new_zeroed`], without requiring a separate [`Zero`]
+/// implementation. `Zero` can't be implemented for all `Zeroable` types,
+/// as that would conflict with the crate's own implementations.
+///
+/// [`BoxExt::new_zeroed`]: trait.BoxExt.html#tymethod.new_zeroed
+/// [`BoxExt::try_new_zeroed`]: trait.BoxExt.html#tymethod.try_new_zeroed
+/// [`Zero`]: trait.Zero.html
+///
+/// # Example
+///
+/// ```
+/// extern crate boxext;
+/// extern crate bytemuck;
+/// use boxext::BoxZeroableExt;
+/// use bytemuck::Zeroable;
+///
+/// #[derive(Clone, Copy)]
+/// #[repr(C)]
+/// struct Vertex {
+///     pos: [f32; 3],
+///     color: u32,
+/// }
+///
+/// unsafe impl Zeroable for Vertex {}
+///
+/// fn main() {
+///     let mesh: Box<[Vertex; 1024]> = Box::new_zeroable();
+///     assert_eq!(mesh[0].color, 0);
+/// }
+/// ```
+pub trait BoxZeroableExt: Sized {
+    /// Type contained inside the `Box`.
+    type Inner;
+
+    /// Allocates memory on the heap and fills it with zeroes.
+    ///
+    /// # Panics
+    ///
+    /// Calls [`handle_alloc_error`] if memory couldn't be allocated.
+    ///
+    /// [`handle_alloc_error`]: https://doc.rust-lang.org/std/alloc/fn.handle_alloc_error.html
+    fn new_zeroable() -> Self
+    where
+        Self::Inner: Zeroable;
+
+    /// Fallible [`BoxZeroableExt::new_zeroable`]
+    ///
+    /// [`BoxZeroableExt::new_zeroable`]: #tymethod.new_zeroable
+    ///
+    /// This returns `None` if memory couldn't be allocated.
+    fn try_new_zeroable() -> Option<Self>
+    where
+        Self::Inner: Zeroable;
+}
+
+impl<T> BoxZeroableExt for Box<T> {
+    type Inner = T;
+
+    #[inline]
+    #[track_caller]
+    fn new_zeroable() -> Self
+    where
+        T: Zeroable,
+    {
+        unsafe { UninitBox::new(true).assume_init() }
+    }
+
+    #[inline]
+    fn try_new_zeroable() -> Option<Self>
+    where
+        T: Zeroable,
+    {
+        unsafe { Some(UninitBox::try_new(true).ok()?.assume_init()) }
+    }
+}